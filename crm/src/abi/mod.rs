@@ -1,53 +1,373 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use prost_types::{FieldMask, Timestamp};
 use tonic::codegen::tokio_stream::StreamExt;
+use tonic::transport::Channel;
 use tonic::{Response, Status};
+use tracing::warn;
 
-use crm_metadata::pb::MaterializeRequest;
+use crm_metadata::cache::Cache;
+use crm_metadata::pb::metadata_client::MetadataClient;
+use crm_metadata::pb::{BatchMaterializeRequest, Content};
+use crm_send::pb::send_request::Msg;
 use crm_send::pb::SendRequest;
-use user_stat::pb::QueryRequest;
+use user_stat::pb::{NotificationChannel, QueryRequest, User};
+
+use crate::config::{Channel as TemplateChannel, QuietHours, UnsubscribeConfig};
+use crate::pb::{
+    RecallRequest, RecallResponse, RemindRequest, RemindResponse, WelcomeRequest, WelcomeResponse,
+};
+use crate::quiet_hours::time_zone;
+use crate::template::{TemplateContext, Templates};
+use crate::unsubscribe::UnsubscribeToken;
+use crate::{Clock, CrmService};
 
-use crate::pb::{WelcomeRequest, WelcomeResponse};
-use crate::CrmService;
+mod notify;
+mod unsubscribe;
 
 const ADMIN: &str = "admin@crm.org";
+// fields of contents used by templates
+const TEMPLATE_FIELDS: &[&str] = &[
+    "name",
+    "description",
+    "publishers",
+    "url",
+    "image",
+    "content_type",
+    "views",
+    "likes",
+    "dislikes",
+];
+
+/// an email campaign, users could unsubscribe from its category
+#[derive(Clone)]
+struct EmailCampaign {
+    name: &'static str,
+    category: &'static str,
+    templates: Templates,
+    unsubscribe: UnsubscribeConfig,
+    quiet_hours: Option<QuietHours>,
+    default_time_zone: Tz,
+    clock: Arc<dyn Clock>,
+}
 
 impl CrmService {
     pub async fn welcome(
         &self,
         request: WelcomeRequest,
     ) -> Result<Response<WelcomeResponse>, Status> {
+        let campaign = self.email_campaign("welcome", "onboarding");
         let mut user_stats_client = self.user_stats.clone();
         let users = user_stats_client
-            .query(QueryRequest::new_with_interval(request.interval))
+            .query(self.audience(
+                campaign.query(QueryRequest::new_with_interval(request.interval)),
+                NotificationChannel::Email,
+                campaign.category,
+            ))
             .await?
             .into_inner();
 
-        let contents = self
-            .metadata
-            .clone()
-            .materialize(MaterializeRequest::new_with_ids(&request.content_ids))
+        let contents = self.materialize(&request.content_ids).await?;
+
+        let req = users.filter_map(move |user| {
+            let user = user.ok()?;
+            campaign.new_email(&user, &contents)
+        });
+
+        self.notify(NotificationChannel::Email, req).await?;
+
+        let resp = WelcomeResponse { id: request.id };
+        Ok(Response::new(resp))
+    }
+
+    pub async fn recall(&self, request: RecallRequest) -> Result<Response<RecallResponse>, Status> {
+        if request.last_visit_interval == 0 && request.last_watched_interval == 0 {
+            return Err(Status::invalid_argument(
+                "last_visit_interval or last_watched_interval is required",
+            ));
+        }
+
+        let campaign = self.email_campaign("recall", "marketing");
+        let mut user_stats_client = self.user_stats.clone();
+        let users = user_stats_client
+            .query(self.audience(
+                campaign.query(QueryRequest::new_with_recall(
+                    request.last_visit_interval,
+                    request.last_watched_interval,
+                )),
+                NotificationChannel::Email,
+                campaign.category,
+            ))
             .await?
             .into_inner();
 
-        let contents = contents.filter_map(|c| c.ok()).collect::<Vec<_>>().await;
-
-        let mut notify = self.notification.clone();
+        let contents = self.materialize(&request.content_ids).await?;
 
         let req = users.filter_map(move |user| {
             let user = user.ok()?;
-            Some(SendRequest::new_email(
-                "Welcome".to_string(),
-                ADMIN.to_string(),
-                &[user.email],
-                format!(
-                    "Hi, {}! Welcome to CRM! \nContents for you: {:?}",
-                    user.name, &contents
-                ),
-            ))
+            campaign.new_email(&user, &contents)
         });
 
-        notify.send(req).await?;
+        self.notify(NotificationChannel::Email, req).await?;
 
-        let resp = WelcomeResponse { id: request.id };
+        let resp = RecallResponse { id: request.id };
+        Ok(Response::new(resp))
+    }
+
+    pub async fn remind(&self, request: RemindRequest) -> Result<Response<RemindResponse>, Status> {
+        let campaign = self.email_campaign("remind", "reminder");
+        let mut user_stats_client = self.user_stats.clone();
+        let users = user_stats_client
+            .query(self.audience(
+                campaign.query(QueryRequest::new_with_remind(request.last_visit_interval)),
+                NotificationChannel::Email,
+                campaign.category,
+            ))
+            .await?
+            .into_inner();
+
+        let metadata = self.metadata.clone();
+        let cache = self.contents.clone();
+        let req = users
+            .then(move |user| {
+                let metadata = metadata.clone();
+                let cache = cache.clone();
+                let campaign = campaign.clone();
+                async move {
+                    let user = user.ok()?;
+                    if user.started_but_not_finished.is_empty() {
+                        return None;
+                    }
+                    // every user has its own unfinished contents
+                    let contents =
+                        match materialize(metadata, &cache, &user.started_but_not_finished).await {
+                            Ok(contents) => contents,
+                            Err(e) => {
+                                warn!("Failed to materialize contents for {}: {}", user.email, e);
+                                return None;
+                            }
+                        };
+                    campaign.new_email(&user, &contents)
+                }
+            })
+            .filter_map(|req| req);
+
+        self.notify(NotificationChannel::Email, req).await?;
+
+        let resp = RemindResponse { id: request.id };
         Ok(Response::new(resp))
     }
+
+    async fn materialize(&self, ids: &[u32]) -> Result<Vec<Content>, Status> {
+        materialize(self.metadata.clone(), &self.contents, ids).await
+    }
+
+    fn email_campaign(&self, name: &'static str, category: &'static str) -> EmailCampaign {
+        EmailCampaign {
+            name,
+            category,
+            templates: self.templates.clone(),
+            unsubscribe: self.config.unsubscribe.clone(),
+            quiet_hours: self.config.quiet_hours.campaigns.get(name).copied(),
+            default_time_zone: self.config.quiet_hours.default_time_zone,
+            clock: self.clock.clone(),
+        }
+    }
+}
+
+impl EmailCampaign {
+    /// the audience query selects the user fields used by the templates, others are empty
+    fn query(&self, req: QueryRequest) -> QueryRequest {
+        req.with_fields(self.templates.user_fields(self.name))
+    }
+
+    // users whose email fails to render are skipped
+    fn new_email(&self, user: &User, contents: &[Content]) -> Option<(String, SendRequest)> {
+        let token = UnsubscribeToken::new(
+            &user.email,
+            NotificationChannel::Email,
+            self.category,
+            self.clock.now(),
+        );
+        let unsubscribe_url = token.url(&self.unsubscribe.url, &self.unsubscribe.secret);
+        let ctx = TemplateContext::new(user, contents, &unsubscribe_url);
+        let rendered = match self
+            .templates
+            .render(self.name, TemplateChannel::Email, &ctx)
+        {
+            Ok(rendered) => rendered,
+            Err(e) => {
+                warn!(
+                    "Failed to render {} email for {}: {:#}",
+                    self.name, user.email, e
+                );
+                return None;
+            }
+        };
+
+        let mut req = SendRequest::new_email(
+            rendered.subject,
+            ADMIN.to_string(),
+            std::slice::from_ref(&user.email),
+            rendered.body,
+        );
+        // RFC 8058 one-click unsubscribe
+        if let Some(Msg::Email(email)) = req.msg.as_mut() {
+            email.headers.insert(
+                "List-Unsubscribe".to_string(),
+                format!("<{}>", unsubscribe_url),
+            );
+            email.headers.insert(
+                "List-Unsubscribe-Post".to_string(),
+                "List-Unsubscribe=One-Click".to_string(),
+            );
+        }
+        // crm_send holds the email until the quiet hours end
+        if let Some(send_at) = self.send_at(user) {
+            req = req.with_send_at(Timestamp {
+                seconds: send_at.timestamp(),
+                nanos: send_at.timestamp_subsec_nanos() as i32,
+            });
+        }
+        Some((user.email.clone(), req))
+    }
+
+    // None if the email could be sent now
+    fn send_at(&self, user: &User) -> Option<DateTime<Utc>> {
+        let tz = time_zone(&user.time_zone, self.default_time_zone);
+        self.quiet_hours?.defer(self.clock.now(), tz)
+    }
+}
+
+/// contents in the order of ids, unknown ones are skipped. Only contents which
+/// aren't cached are looked up
+async fn materialize(
+    mut metadata: MetadataClient<Channel>,
+    cache: &Cache<u32, Content>,
+    ids: &[u32],
+) -> Result<Vec<Content>, Status> {
+    let mut found = HashMap::with_capacity(ids.len());
+    let mut misses = Vec::new();
+    for &id in ids {
+        match cache.get(&id) {
+            Some(content) => {
+                found.insert(id, content);
+            }
+            None => misses.push(id),
+        }
+    }
+    if !misses.is_empty() {
+        let req = BatchMaterializeRequest {
+            ids: misses,
+            field_mask: Some(FieldMask {
+                paths: TEMPLATE_FIELDS.iter().map(|f| f.to_string()).collect(),
+            }),
+        };
+        let resp = metadata.batch_materialize(req).await?.into_inner();
+        for content in resp.contents {
+            cache.insert(content.id, content.clone());
+            found.insert(content.id, content);
+        }
+    }
+    // a duplicate id is returned once
+    Ok(ids.iter().filter_map(|id| found.remove(id)).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::AppConfig;
+
+    struct FixedClock(DateTime<Utc>);
+
+    impl Clock for FixedClock {
+        fn now(&self) -> DateTime<Utc> {
+            self.0
+        }
+    }
+
+    fn new_campaign(now: &str) -> anyhow::Result<EmailCampaign> {
+        let config = AppConfig::load()?;
+        Ok(EmailCampaign {
+            name: "recall",
+            category: "marketing",
+            templates: Templates::load(&config.templates)?,
+            unsubscribe: config.unsubscribe,
+            quiet_hours: Some(QuietHours {
+                start: "21:00".parse()?,
+                end: "09:00".parse()?,
+            }),
+            default_time_zone: Tz::UTC,
+            clock: Arc::new(FixedClock(now.parse()?)),
+        })
+    }
+
+    #[test]
+    fn email_should_be_deferred_out_of_quiet_hours() -> anyhow::Result<()> {
+        // 23:30 in Shanghai, 11:30 in New York
+        let campaign = new_campaign("2024-09-20T15:30:00Z")?;
+        let mut user = User {
+            email: "tyr@acme.org".to_string(),
+            name: "Tyr".to_string(),
+            time_zone: "Asia/Shanghai".to_string(),
+            ..Default::default()
+        };
+        let (_, req) = campaign.new_email(&user, &[]).unwrap();
+        let send_at = "2024-09-21T01:00:00Z".parse::<DateTime<Utc>>()?;
+        assert_eq!(Some(send_at.timestamp()), req.send_at.map(|t| t.seconds));
+
+        user.time_zone = "America/New_York".to_string();
+        let (_, req) = campaign.new_email(&user, &[]).unwrap();
+        assert_eq!(None, req.send_at);
+
+        // falls back to UTC, 15:30
+        user.time_zone = String::new();
+        let (_, req) = campaign.new_email(&user, &[]).unwrap();
+        assert_eq!(None, req.send_at);
+
+        let campaign = EmailCampaign {
+            quiet_hours: None,
+            ..new_campaign("2024-09-20T15:30:00Z")?
+        };
+        user.time_zone = "Asia/Shanghai".to_string();
+        let (_, req) = campaign.new_email(&user, &[]).unwrap();
+        assert_eq!(None, req.send_at);
+        Ok(())
+    }
+
+    #[test]
+    fn campaign_query_should_select_template_fields() -> anyhow::Result<()> {
+        let campaign = new_campaign("2024-09-20T15:30:00Z")?;
+        let req = campaign.query(QueryRequest::new_with_remind(7));
+        assert_eq!(
+            vec!["started_but_not_finished", "locale", "time_zone", "name"],
+            req.fields
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn cached_contents_should_not_be_looked_up() -> anyhow::Result<()> {
+        // nothing listens on the port, any lookup fails
+        let channel = Channel::from_static("http://[::1]:1").connect_lazy();
+        let metadata = MetadataClient::new(channel);
+        let cache = Cache::new(&AppConfig::load()?.content_cache);
+        for id in [1, 2] {
+            let content = Content {
+                id,
+                name: format!("Content {}", id),
+                ..Default::default()
+            };
+            cache.insert(id, content);
+        }
+
+        let contents = materialize(metadata.clone(), &cache, &[2, 1, 2]).await?;
+        let ids = contents.iter().map(|c| c.id).collect::<Vec<_>>();
+        assert_eq!(vec![2, 1], ids);
+        assert!(materialize(metadata, &cache, &[3]).await.is_err());
+        Ok(())
+    }
 }
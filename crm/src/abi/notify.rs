@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use chrono::{DateTime, Duration, Utc};
+use prost_types::Timestamp;
+use tonic::codegen::tokio_stream::{Stream, StreamExt};
+use tonic::Status;
+use tracing::warn;
+
+use crm_send::pb::{SendRequest, SendStatus};
+use user_stat::pb::{Filter, MarkNotifiedRequest, NotificationChannel, QueryRequest, TimeQuery};
+
+use crate::CrmService;
+
+// max emails of a MarkNotified request
+const MARK_BATCH_SIZE: usize = 1000;
+
+impl CrmService {
+    /// skip users who opted out of the channel or the category,
+    /// or were notified on the channel within the cooldown
+    pub(crate) fn audience(
+        &self,
+        req: QueryRequest,
+        channel: NotificationChannel,
+        category: &str,
+    ) -> QueryRequest {
+        let req = req.and_filter(Filter::subscribed(channel, category));
+        let cooldown = match channel {
+            NotificationChannel::Email => self.config.cooldown.email,
+            NotificationChannel::InApp => self.config.cooldown.in_app,
+            NotificationChannel::Sms => self.config.cooldown.sms,
+            NotificationChannel::Unspecified => 0,
+        };
+        match channel.column() {
+            Ok(column) if cooldown > 0 => {
+                req.and_filter(cooldown_filter(column, cooldown, self.clock.now()))
+            }
+            _ => req,
+        }
+    }
+
+    /// send the notifications, users whose notification is accepted by crm_send
+    /// are marked as notified on the channel. A broken response stream fails the
+    /// call once the users accepted so far are marked
+    pub(crate) async fn notify<S>(
+        &self,
+        channel: NotificationChannel,
+        reqs: S,
+    ) -> Result<(), Status>
+    where
+        S: Stream<Item = (String, SendRequest)> + Send + 'static,
+    {
+        // SendRequest.id -> email of the user
+        let recipients = Arc::new(Mutex::new(HashMap::new()));
+        let pending = recipients.clone();
+        let reqs = reqs.map(move |(email, req)| {
+            pending.lock().unwrap().insert(req.id.clone(), email);
+            req
+        });
+
+        let mut responses = self.notification.clone().send(reqs).await?.into_inner();
+        let mut notified = Vec::new();
+        let mut failed = None;
+        while let Some(res) = responses.next().await {
+            match res {
+                Ok(res) => {
+                    let email = recipients.lock().unwrap().remove(&res.id);
+                    if let (Some(email), true) = (email, is_accepted(res.status)) {
+                        notified.push(email);
+                    }
+                }
+                Err(e) => {
+                    // still mark the users already notified
+                    warn!("Failed to receive send response: {}", e);
+                    failed = Some(e);
+                    break;
+                }
+            }
+        }
+
+        let mut user_stats = self.user_stats.clone();
+        for emails in notified.chunks(MARK_BATCH_SIZE) {
+            let req = MarkNotifiedRequest {
+                channel: channel as i32,
+                emails: emails.to_vec(),
+                notified_at: None,
+            };
+            user_stats.mark_notified(req).await?;
+        }
+        match failed {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+}
+
+// queued messages are retried by crm_send until delivered, scheduled ones
+// are sent after the quiet hours
+fn is_accepted(status: i32) -> bool {
+    [SendStatus::Sent, SendStatus::Queued, SendStatus::Scheduled]
+        .iter()
+        .any(|s| *s as i32 == status)
+}
+
+fn cooldown_filter(column: &str, cooldown: u64, now: DateTime<Utc>) -> Filter {
+    let since = now - Duration::seconds(cooldown as i64);
+    let range = TimeQuery {
+        lower: None,
+        upper: Some(Timestamp {
+            seconds: since.timestamp(),
+            nanos: since.timestamp_subsec_nanos() as i32,
+        }),
+    };
+    Filter::or(vec![Filter::is_null(column), Filter::time(column, range)])
+}
+
+#[cfg(test)]
+mod tests {
+    use user_stat::UserStatsService;
+
+    use super::*;
+
+    #[test]
+    fn cooldown_filter_should_work() -> anyhow::Result<()> {
+        let req = QueryRequest::new_with_interval(1).and_filter(cooldown_filter(
+            "last_email_notification",
+            3600,
+            Utc::now(),
+        ));
+        let sql = UserStatsService::query_sql(req)?.into_sql();
+        assert_eq!(
+            "SELECT email, name, locale, time_zone FROM user_stats WHERE created_at BETWEEN $1 AND $2 \
+             AND ((last_email_notification IS NULL) OR (last_email_notification <= $3))",
+            sql
+        );
+        Ok(())
+    }
+}
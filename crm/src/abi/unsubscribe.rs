@@ -0,0 +1,31 @@
+use tonic::{Response, Status};
+
+use user_stat::pb::{Preference, UpdatePreferencesRequest};
+
+use crate::pb::{UnsubscribeRequest, UnsubscribeResponse};
+use crate::unsubscribe::UnsubscribeToken;
+use crate::CrmService;
+
+impl CrmService {
+    pub async fn unsubscribe(
+        &self,
+        request: UnsubscribeRequest,
+    ) -> Result<Response<UnsubscribeResponse>, Status> {
+        let token = UnsubscribeToken::verify(
+            &request.token,
+            &self.config.unsubscribe.secret,
+            self.config.unsubscribe.max_age,
+            self.clock.now(),
+        )
+        .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let req = UpdatePreferencesRequest {
+            email: token.email.clone(),
+            preferences: vec![Preference::new(token.channel, token.category, false)],
+        };
+        self.user_stats.clone().update_preferences(req).await?;
+
+        let resp = UnsubscribeResponse { email: token.email };
+        Ok(Response::new(resp))
+    }
+}
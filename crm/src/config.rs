@@ -1,13 +1,33 @@
+use std::collections::HashMap;
 use std::fs::File;
+use std::path::PathBuf;
 
 use anyhow::bail;
+use chrono::NaiveTime;
+use chrono_tz::Tz;
+use crm_metadata::cache::CacheConfig;
 use serde::{Deserialize, Serialize};
 
+// unsubscribe tokens are signed with HMAC-SHA256, a shorter key is easy to guess
+const MIN_SECRET_LEN: usize = 32;
+// keeps the secret out of the config file
+const SECRET_ENV: &str = "CRM_UNSUBSCRIBE_SECRET";
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppConfig {
     pub server: ServerConfig,
     pub db_url: String,
     pub auth: AuthConfig,
+    #[serde(default)]
+    pub templates: TemplatesConfig,
+    #[serde(default)]
+    pub cooldown: CooldownConfig,
+    #[serde(default)]
+    pub quiet_hours: QuietHoursConfig,
+    // contents materialized for campaigns
+    #[serde(default)]
+    pub content_cache: CacheConfig,
+    pub unsubscribe: UnsubscribeConfig,
 }
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
@@ -22,17 +42,175 @@ pub struct AuthConfig {
     pub sk: String,
 }
 
+/// one-click unsubscribe links in notifications
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnsubscribeConfig {
+    // page which calls the Unsubscribe rpc with the `token` query parameter
+    pub url: String,
+    // key to sign unsubscribe tokens, overridden by the CRM_UNSUBSCRIBE_SECRET env variable
+    #[serde(default)]
+    pub secret: String,
+    // seconds an unsubscribe link is valid after the notification is sent
+    #[serde(default = "default_max_age")]
+    pub max_age: u64,
+}
+
+/// campaigns skip users notified on the channel within the cooldown,
+/// in seconds, 0 means no cooldown
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CooldownConfig {
+    pub email: u64,
+    pub in_app: u64,
+    pub sms: u64,
+}
+
+/// campaigns defer notifications out of their quiet hours, in the time zone
+/// of each user
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuietHoursConfig {
+    // used when a user has no valid time zone
+    #[serde(default = "default_time_zone")]
+    pub default_time_zone: Tz,
+    // quiet hours by campaign, a campaign without them sends at once
+    #[serde(default)]
+    pub campaigns: HashMap<String, QuietHours>,
+}
+
+/// local time range without notifications, `start` after `end` wraps midnight,
+/// e.g. 21:00 - 09:00
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+/// notification templates, `dir` holds a `<campaign>.yml` for each campaign
+/// in the same format as `campaigns`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplatesConfig {
+    // used when a template has no variant for the locale of a user
+    #[serde(default = "default_locale")]
+    pub default_locale: String,
+    #[serde(default)]
+    pub dir: Option<PathBuf>,
+    #[serde(default)]
+    pub campaigns: HashMap<String, CampaignTemplates>,
+}
+
+/// templates of a campaign by channel and locale
+pub type CampaignTemplates = HashMap<Channel, HashMap<String, TemplateSource>>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Channel {
+    Email,
+    Sms,
+    InApp,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TemplateSource {
+    // email subject or in-app title, sms has no subject
+    #[serde(default)]
+    pub subject: Option<String>,
+    pub body: String,
+}
+
+impl Default for CooldownConfig {
+    fn default() -> Self {
+        Self {
+            email: 86400,
+            in_app: 86400,
+            sms: 86400,
+        }
+    }
+}
+
+impl Default for QuietHoursConfig {
+    fn default() -> Self {
+        Self {
+            default_time_zone: default_time_zone(),
+            campaigns: HashMap::new(),
+        }
+    }
+}
+
+impl Default for TemplatesConfig {
+    fn default() -> Self {
+        Self {
+            default_locale: default_locale(),
+            dir: None,
+            campaigns: HashMap::new(),
+        }
+    }
+}
+
+fn default_locale() -> String {
+    "en".to_string()
+}
+
+fn default_time_zone() -> Tz {
+    Tz::UTC
+}
+
+fn default_max_age() -> u64 {
+    // 90 days
+    90 * 86400
+}
+
+impl UnsubscribeConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if self.secret.len() < MIN_SECRET_LEN {
+            bail!(
+                "Unsubscribe secret must be at least {} bytes, set it with {}",
+                MIN_SECRET_LEN,
+                SECRET_ENV
+            );
+        }
+        if self.max_age == 0 {
+            bail!("Unsubscribe max_age must be positive");
+        }
+        Ok(())
+    }
+}
+
 impl AppConfig {
     pub fn load() -> anyhow::Result<Self> {
-        match (
+        let mut config: Self = match (
             File::open("crm.yml"),
             File::open("/etc/config/crm.yml"),
             std::env::var("CRM_CONFIG"),
         ) {
-            (Ok(file), _, _) => Ok(serde_yaml::from_reader(file)?),
-            (_, Ok(file), _) => Ok(serde_yaml::from_reader(file)?),
-            (_, _, Ok(path)) => Ok(serde_yaml::from_str(&path)?),
+            (Ok(file), _, _) => serde_yaml::from_reader(file)?,
+            (_, Ok(file), _) => serde_yaml::from_reader(file)?,
+            (_, _, Ok(path)) => serde_yaml::from_str(&path)?,
             _ => bail!("no config file found"),
+        };
+        if let Ok(secret) = std::env::var(SECRET_ENV) {
+            config.unsubscribe.secret = secret;
         }
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_unsubscribe_secret_should_be_rejected() {
+        let mut config = UnsubscribeConfig {
+            url: "https://crm.org/unsubscribe".to_string(),
+            secret: String::new(),
+            max_age: default_max_age(),
+        };
+        assert!(config.validate().is_err());
+        config.secret = "secret".to_string();
+        assert!(config.validate().is_err());
+        config.secret = "6fa1d1b2c8e54b0f9d3a7e2c4b8f1a90".to_string();
+        assert!(config.validate().is_ok());
+        config.max_age = 0;
+        assert!(config.validate().is_err());
     }
 }
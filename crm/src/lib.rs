@@ -1,21 +1,37 @@
+use std::sync::Arc;
+
+use anyhow::Context;
 use tonic::transport::Channel;
 /// CrmService is the service
 /// intended to use crm_metadata, crm_send and user_stat
 use tonic::{async_trait, Request, Response, Status};
 
-pub use config::AppConfig;
+pub use clock::{Clock, SystemClock};
+pub use config::{
+    AppConfig, CooldownConfig, QuietHours, QuietHoursConfig, TemplatesConfig, UnsubscribeConfig,
+};
+use crm_metadata::cache::Cache;
 use crm_metadata::pb::metadata_client::MetadataClient;
+use crm_metadata::pb::Content;
 use crm_send::pb::notification_client::NotificationClient;
 use user_stat::pb::user_stats_client::UserStatsClient;
 
+use crate::config::Channel as TemplateChannel;
+use crate::template::Templates;
+
 use crate::pb::crm_server::{Crm, CrmServer};
 use crate::pb::{
-    RecallRequest, RecallResponse, RemindRequest, RemindResponse, WelcomeRequest, WelcomeResponse,
+    RecallRequest, RecallResponse, RemindRequest, RemindResponse, UnsubscribeRequest,
+    UnsubscribeResponse, WelcomeRequest, WelcomeResponse,
 };
 
 mod abi;
+mod clock;
 mod config;
 pub mod pb;
+mod quiet_hours;
+mod template;
+mod unsubscribe;
 
 #[allow(unused)]
 pub struct CrmService {
@@ -23,6 +39,10 @@ pub struct CrmService {
     user_stats: UserStatsClient<Channel>,
     notification: NotificationClient<Channel>,
     metadata: MetadataClient<Channel>,
+    // contents shared by the users of campaigns
+    contents: Arc<Cache<u32, Content>>,
+    templates: Templates,
+    clock: Arc<dyn Clock>,
 }
 
 #[async_trait]
@@ -36,36 +56,56 @@ impl Crm for CrmService {
 
     async fn recall(
         &self,
-        _request: Request<RecallRequest>,
+        request: Request<RecallRequest>,
     ) -> Result<Response<RecallResponse>, Status> {
-        todo!()
+        self.recall(request.into_inner()).await
     }
 
     async fn remind(
         &self,
-        _request: Request<RemindRequest>,
+        request: Request<RemindRequest>,
     ) -> Result<Response<RemindResponse>, Status> {
-        todo!()
+        self.remind(request.into_inner()).await
+    }
+
+    async fn unsubscribe(
+        &self,
+        request: Request<UnsubscribeRequest>,
+    ) -> Result<Response<UnsubscribeResponse>, Status> {
+        self.unsubscribe(request.into_inner()).await
     }
 }
 
 impl CrmService {
-    pub async fn new(config: AppConfig) -> Self {
-        let user_stats = UserStatsClient::connect(config.server.user_stat.clone())
-            .await
-            .unwrap();
-        let notification = NotificationClient::connect(config.server.notification.clone())
-            .await
-            .unwrap();
-        let metadata = MetadataClient::connect(config.server.metadata.clone())
-            .await
-            .unwrap();
-        Self {
+    /// fails on an invalid config or when a service can't be connected
+    pub async fn new(config: AppConfig) -> anyhow::Result<Self> {
+        config
+            .unsubscribe
+            .validate()
+            .context("Invalid unsubscribe config")?;
+        let templates = Templates::load(&config.templates).context("Failed to load templates")?;
+        for campaign in ["welcome", "recall", "remind"] {
+            templates.require(campaign, TemplateChannel::Email)?;
+        }
+        let user_stats = UserStatsClient::connect(config.server.user_stat.clone()).await?;
+        let notification = NotificationClient::connect(config.server.notification.clone()).await?;
+        let metadata = MetadataClient::connect(config.server.metadata.clone()).await?;
+        let contents = Arc::new(Cache::new(&config.content_cache));
+        Ok(Self {
             config,
             user_stats,
             notification,
             metadata,
-        }
+            contents,
+            templates,
+            clock: Arc::new(SystemClock),
+        })
+    }
+
+    /// use another clock to decide quiet hours
+    pub fn with_clock(mut self, clock: impl Clock) -> Self {
+        self.clock = Arc::new(clock);
+        self
     }
 
     pub fn into_server(self) -> CrmServer<Self> {
@@ -54,6 +54,19 @@ pub struct RemindResponse {
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
 }
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UnsubscribeRequest {
+    /// token of the unsubscribe link in a notification
+    #[prost(string, tag = "1")]
+    pub token: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UnsubscribeResponse {
+    #[prost(string, tag = "1")]
+    pub email: ::prost::alloc::string::String,
+}
 /// Generated client implementations.
 pub mod crm_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
@@ -189,6 +202,25 @@ pub mod crm_client {
                 .insert(GrpcMethod::new("crm.Crm", "Remind"));
             self.inner.unary(req, path, codec).await
         }
+        /// opt out of the channel and category of a notification
+        pub async fn unsubscribe(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UnsubscribeRequest>,
+        ) -> std::result::Result<tonic::Response<super::UnsubscribeResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/crm.Crm/Unsubscribe");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("crm.Crm", "Unsubscribe"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -213,6 +245,11 @@ pub mod crm_server {
             &self,
             request: tonic::Request<super::RemindRequest>,
         ) -> std::result::Result<tonic::Response<super::RemindResponse>, tonic::Status>;
+        /// opt out of the channel and category of a notification
+        async fn unsubscribe(
+            &self,
+            request: tonic::Request<super::UnsubscribeRequest>,
+        ) -> std::result::Result<tonic::Response<super::UnsubscribeResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct CrmServer<T: Crm> {
@@ -404,6 +441,44 @@ pub mod crm_server {
                     };
                     Box::pin(fut)
                 }
+                "/crm.Crm/Unsubscribe" => {
+                    #[allow(non_camel_case_types)]
+                    struct UnsubscribeSvc<T: Crm>(pub Arc<T>);
+                    impl<T: Crm> tonic::server::UnaryService<super::UnsubscribeRequest> for UnsubscribeSvc<T> {
+                        type Response = super::UnsubscribeResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UnsubscribeRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { <T as Crm>::unsubscribe(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UnsubscribeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
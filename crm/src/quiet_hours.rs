@@ -0,0 +1,107 @@
+use chrono::{DateTime, Days, NaiveDateTime, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
+
+use crate::config::QuietHours;
+
+/// the time zone of a user, the default one if it's empty or unknown
+pub fn time_zone(name: &str, default: Tz) -> Tz {
+    name.parse().unwrap_or(default)
+}
+
+impl QuietHours {
+    /// when the quiet hours end if `now` is in them, None if it's allowed to send now
+    pub fn defer(&self, now: DateTime<Utc>, tz: Tz) -> Option<DateTime<Utc>> {
+        let local = now.with_timezone(&tz).naive_local();
+        let time = local.time();
+        let quiet = if self.start <= self.end {
+            self.start <= time && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        };
+        if !quiet {
+            return None;
+        }
+
+        // the quiet hours end today, or tomorrow if they wrap midnight
+        let mut date = local.date();
+        if time >= self.end {
+            date = date.checked_add_days(Days::new(1))?;
+        }
+        Some(to_utc(date.and_time(self.end), tz))
+    }
+}
+
+// a local time skipped by a DST transition is moved forward
+fn to_utc(local: NaiveDateTime, tz: Tz) -> DateTime<Utc> {
+    let mut local = local;
+    loop {
+        if let Some(t) = tz.from_local_datetime(&local).earliest() {
+            return t.with_timezone(&Utc);
+        }
+        local += TimeDelta::minutes(30);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn quiet_hours(start: &str, end: &str) -> QuietHours {
+        QuietHours {
+            start: start.parse().unwrap(),
+            end: end.parse().unwrap(),
+        }
+    }
+
+    fn utc(s: &str) -> DateTime<Utc> {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn quiet_hours_should_wrap_midnight() {
+        let night = quiet_hours("21:00", "09:00");
+        let shanghai = Tz::Asia__Shanghai;
+        // 20:00 in Shanghai
+        assert_eq!(None, night.defer(utc("2024-09-20T12:00:00Z"), shanghai));
+        // 23:30 in Shanghai, deferred to 09:00 of the next day
+        assert_eq!(
+            Some(utc("2024-09-21T01:00:00Z")),
+            night.defer(utc("2024-09-20T15:30:00Z"), shanghai)
+        );
+        // 06:00 in Shanghai
+        assert_eq!(
+            Some(utc("2024-09-21T01:00:00Z")),
+            night.defer(utc("2024-09-20T22:00:00Z"), shanghai)
+        );
+        // the same instant is allowed in New York, 18:00
+        assert_eq!(
+            None,
+            night.defer(utc("2024-09-20T22:00:00Z"), Tz::America__New_York)
+        );
+
+        let lunch = quiet_hours("12:00", "13:30");
+        assert_eq!(
+            Some(utc("2024-09-20T13:30:00Z")),
+            lunch.defer(utc("2024-09-20T12:10:00Z"), Tz::UTC)
+        );
+        assert_eq!(None, lunch.defer(utc("2024-09-20T13:30:00Z"), Tz::UTC));
+    }
+
+    #[test]
+    fn quiet_hours_should_skip_dst_gap() {
+        // 02:30 doesn't exist in New York on 2024-03-10
+        let quiet = quiet_hours("00:00", "02:30");
+        assert_eq!(
+            Some(utc("2024-03-10T07:00:00Z")),
+            quiet.defer(utc("2024-03-10T06:00:00Z"), Tz::America__New_York)
+        );
+    }
+
+    #[test]
+    fn time_zone_should_fall_back_to_default() {
+        let default = Tz::Asia__Shanghai;
+        assert_eq!(Tz::Europe__Berlin, time_zone("Europe/Berlin", default));
+        assert_eq!(Tz::Asia__Shanghai, time_zone("", default));
+        assert_eq!(Tz::Asia__Shanghai, time_zone("Mars/Olympus", default));
+    }
+}
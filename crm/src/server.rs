@@ -1,3 +1,4 @@
+use anyhow::Context;
 use tracing::info;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::fmt::Layer;
@@ -12,11 +13,9 @@ async fn main() -> anyhow::Result<()> {
     let layer = Layer::new().with_filter(LevelFilter::INFO);
     tracing_subscriber::registry().with(layer).init();
 
-    let config = AppConfig::load().expect("Failed to load config");
-    let addr = format!("[::1]:{}", config.server.port)
-        .parse()
-        .expect("Failed to parse address ()");
-    let svc = CrmService::new(config).await.into_server();
+    let config = AppConfig::load().context("Failed to load config")?;
+    let addr = format!("[::1]:{}", config.server.port).parse()?;
+    let svc = CrmService::new(config).await?.into_server();
     info!("gRPC server listening on {}", addr);
 
     tonic::transport::Server::builder()
@@ -0,0 +1,632 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context};
+use chrono::{DateTime, SecondsFormat, Utc};
+use minijinja::{Environment, UndefinedBehavior};
+use prost_types::Timestamp;
+use serde::Serialize;
+
+use crm_metadata::pb::{Content, ContentType, Publisher};
+use user_stat::pb::{Gender, User};
+
+use crate::config::{CampaignTemplates, Channel, TemplateSource, TemplatesConfig};
+
+// fields of `UserVars`, they are named after the columns of user_stats
+const USER_FIELDS: &[&str] = &[
+    "email",
+    "name",
+    "gender",
+    "locale",
+    "time_zone",
+    "created_at",
+    "last_visited_at",
+    "last_watched_at",
+    "last_email_notification",
+    "last_in_app_notification",
+    "last_sms_notification",
+    "recent_watched",
+    "viewed_but_not_started",
+    "started_but_not_finished",
+    "finished",
+];
+
+/// compiled notification templates, every template is validated when loaded
+#[derive(Clone)]
+pub struct Templates {
+    env: Arc<Environment<'static>>,
+    // (campaign, channel, locale) of every template
+    variants: Arc<HashSet<(String, Channel, String)>>,
+    // user fields used by the templates of a campaign
+    user_fields: Arc<HashMap<String, BTreeSet<&'static str>>>,
+    default_locale: String,
+}
+
+/// variables available in templates
+#[derive(Debug, Serialize)]
+pub struct TemplateContext<'a> {
+    pub user: UserVars<'a>,
+    pub contents: Vec<ContentVars<'a>>,
+    // one-click unsubscribe link of the notification
+    pub unsubscribe_url: &'a str,
+}
+
+/// fields of the user, times are RFC 3339 in UTC and none if unknown
+#[derive(Debug, Clone, Serialize)]
+pub struct UserVars<'a> {
+    pub email: &'a str,
+    pub name: &'a str,
+    pub gender: &'static str,
+    pub locale: &'a str,
+    pub time_zone: &'a str,
+    pub created_at: Option<String>,
+    pub last_visited_at: Option<String>,
+    pub last_watched_at: Option<String>,
+    pub last_email_notification: Option<String>,
+    pub last_in_app_notification: Option<String>,
+    pub last_sms_notification: Option<String>,
+    // content ids
+    pub recent_watched: &'a [u32],
+    pub viewed_but_not_started: &'a [u32],
+    pub started_but_not_finished: &'a [u32],
+    pub finished: &'a [u32],
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ContentVars<'a> {
+    pub id: u32,
+    pub name: &'a str,
+    pub description: &'a str,
+    pub url: &'a str,
+    pub image: &'a str,
+    pub content_type: &'static str,
+    pub publishers: Vec<&'a str>,
+    pub views: u64,
+    pub likes: u64,
+    pub dislikes: u64,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Rendered {
+    pub subject: String,
+    pub body: String,
+}
+
+impl Templates {
+    /// load templates from the config and the template dir
+    pub fn load(config: &TemplatesConfig) -> anyhow::Result<Self> {
+        let mut campaigns = config.campaigns.clone();
+        if let Some(dir) = &config.dir {
+            for (name, templates) in load_dir(dir)? {
+                if campaigns.insert(name.clone(), templates).is_some() {
+                    bail!(
+                        "Campaign {} is defined in both config and {}",
+                        name,
+                        dir.display()
+                    );
+                }
+            }
+        }
+
+        let mut env = Environment::new();
+        env.set_undefined_behavior(UndefinedBehavior::Strict);
+        env.set_trim_blocks(true);
+        let mut variants = HashSet::new();
+        for (campaign, channels) in campaigns {
+            for (channel, locales) in channels {
+                if !locales.contains_key(&config.default_locale) {
+                    bail!(
+                        "Template {}/{} has no variant for the default locale {}",
+                        campaign,
+                        channel_name(channel),
+                        config.default_locale
+                    );
+                }
+                for (locale, source) in locales {
+                    add_template(&mut env, &campaign, channel, &locale, source)?;
+                    variants.insert((campaign.clone(), channel, locale));
+                }
+            }
+        }
+
+        let user_fields = used_user_fields(&env, &variants);
+        let templates = Self {
+            env: Arc::new(env),
+            variants: Arc::new(variants),
+            user_fields: Arc::new(user_fields),
+            default_locale: config.default_locale.clone(),
+        };
+        templates.validate()?;
+        Ok(templates)
+    }
+
+    /// make sure a campaign has a template for the channel
+    pub fn require(&self, campaign: &str, channel: Channel) -> anyhow::Result<()> {
+        let key = (campaign.to_string(), channel, self.default_locale.clone());
+        if !self.variants.contains(&key) {
+            bail!("Template {}/{} not found", campaign, channel_name(channel));
+        }
+        Ok(())
+    }
+
+    /// user fields the templates of a campaign use, they have to be queried
+    pub fn user_fields(&self, campaign: &str) -> Vec<String> {
+        self.user_fields
+            .get(campaign)
+            .map(|fields| fields.iter().map(|f| f.to_string()).collect())
+            .unwrap_or_default()
+    }
+
+    /// render the variant for the locale of the user, it falls back to the language
+    /// (`zh` for `zh-CN`) and then the default locale
+    pub fn render(
+        &self,
+        campaign: &str,
+        channel: Channel,
+        ctx: &TemplateContext,
+    ) -> anyhow::Result<Rendered> {
+        let locale = self.resolve_locale(campaign, channel, ctx.user.locale);
+        let subject = match channel {
+            Channel::Sms => String::new(),
+            Channel::Email | Channel::InApp => {
+                let name = template_name(campaign, channel, locale, "subject");
+                self.env.get_template(&name)?.render(ctx)?
+            }
+        };
+        let name = template_name(campaign, channel, locale, "body");
+        let body = self.env.get_template(&name)?.render(ctx)?;
+        Ok(Rendered { subject, body })
+    }
+
+    fn resolve_locale<'a>(&'a self, campaign: &str, channel: Channel, locale: &'a str) -> &'a str {
+        let language = locale.split(['-', '_']).next().unwrap_or_default();
+        [locale, language]
+            .into_iter()
+            .find(|l| {
+                !l.is_empty()
+                    && self
+                        .variants
+                        .contains(&(campaign.to_string(), channel, l.to_string()))
+            })
+            .unwrap_or(&self.default_locale)
+    }
+
+    // render every template with sample variables, so unknown variables fail at startup.
+    // A user with every field set and one without the optional fields are rendered, so a
+    // template which doesn't handle unknown times fails here rather than for each user
+    fn validate(&self) -> anyhow::Result<()> {
+        let now = Some(Timestamp {
+            seconds: Utc::now().timestamp(),
+            nanos: 0,
+        });
+        let full = User {
+            email: "tyr@acme.org".to_string(),
+            name: "Tyr Chen".to_string(),
+            gender: Gender::Female as i32,
+            time_zone: "Asia/Shanghai".to_string(),
+            created_at: now,
+            last_visited_at: now,
+            last_watched_at: now,
+            last_email_notification: now,
+            last_in_app_notification: now,
+            last_sms_notification: now,
+            recent_watched: vec![1],
+            viewed_but_not_started: vec![1],
+            started_but_not_finished: vec![1],
+            finished: vec![1],
+            ..Default::default()
+        };
+        let sparse = User {
+            email: "tyr@acme.org".to_string(),
+            name: "Tyr Chen".to_string(),
+            ..Default::default()
+        };
+        let contents = vec![Content {
+            id: 1,
+            name: "Sample".to_string(),
+            publishers: vec![Publisher::default()],
+            ..Default::default()
+        }];
+        for (campaign, channel, locale) in self.variants.iter() {
+            for user in [&full, &sparse] {
+                let mut ctx =
+                    TemplateContext::new(user, &contents, "https://crm.org/unsubscribe?token=t");
+                ctx.user.locale = locale;
+                self.render(campaign, *channel, &ctx).with_context(|| {
+                    format!(
+                        "Invalid template {}/{}/{}",
+                        campaign,
+                        channel_name(*channel),
+                        locale
+                    )
+                })?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl<'a> TemplateContext<'a> {
+    pub fn new(user: &'a User, contents: &'a [Content], unsubscribe_url: &'a str) -> Self {
+        Self {
+            user: UserVars::from(user),
+            contents: contents.iter().map(ContentVars::from).collect(),
+            unsubscribe_url,
+        }
+    }
+}
+
+impl<'a> From<&'a User> for UserVars<'a> {
+    fn from(user: &'a User) -> Self {
+        let gender = Gender::try_from(user.gender)
+            .unwrap_or(Gender::Unknown)
+            .as_str_name()
+            .trim_start_matches("GENDER_");
+        Self {
+            email: &user.email,
+            name: &user.name,
+            gender,
+            locale: &user.locale,
+            time_zone: &user.time_zone,
+            created_at: to_rfc3339(user.created_at),
+            last_visited_at: to_rfc3339(user.last_visited_at),
+            last_watched_at: to_rfc3339(user.last_watched_at),
+            last_email_notification: to_rfc3339(user.last_email_notification),
+            last_in_app_notification: to_rfc3339(user.last_in_app_notification),
+            last_sms_notification: to_rfc3339(user.last_sms_notification),
+            recent_watched: &user.recent_watched,
+            viewed_but_not_started: &user.viewed_but_not_started,
+            started_but_not_finished: &user.started_but_not_finished,
+            finished: &user.finished,
+        }
+    }
+}
+
+impl<'a> From<&'a Content> for ContentVars<'a> {
+    fn from(content: &'a Content) -> Self {
+        let content_type = ContentType::try_from(content.content_type)
+            .unwrap_or(ContentType::Other)
+            .as_str_name()
+            .trim_start_matches("CONTENT_TYPE_");
+        Self {
+            id: content.id,
+            name: &content.name,
+            description: &content.description,
+            url: &content.url,
+            image: &content.image,
+            content_type,
+            publishers: content.publishers.iter().map(|p| p.name.as_str()).collect(),
+            views: content.views,
+            likes: content.likes,
+            dislikes: content.dislikes,
+        }
+    }
+}
+
+fn add_template(
+    env: &mut Environment<'static>,
+    campaign: &str,
+    channel: Channel,
+    locale: &str,
+    source: TemplateSource,
+) -> anyhow::Result<()> {
+    let subject = match (channel, source.subject) {
+        (Channel::Sms, Some(_)) => bail!("Sms template {} can't have a subject", campaign),
+        (Channel::Sms, None) => None,
+        (_, Some(subject)) => Some(subject),
+        (_, None) => bail!(
+            "Template {}/{}/{} has no subject",
+            campaign,
+            channel_name(channel),
+            locale
+        ),
+    };
+    if let Some(subject) = subject {
+        env.add_template_owned(template_name(campaign, channel, locale, "subject"), subject)?;
+    }
+    env.add_template_owned(
+        template_name(campaign, channel, locale, "body"),
+        source.body,
+    )?;
+    Ok(())
+}
+
+// user fields read by the templates of each campaign
+fn used_user_fields(
+    env: &Environment<'static>,
+    variants: &HashSet<(String, Channel, String)>,
+) -> HashMap<String, BTreeSet<&'static str>> {
+    let mut fields: HashMap<String, BTreeSet<&'static str>> = HashMap::new();
+    for (campaign, channel, locale) in variants {
+        let used = fields.entry(campaign.clone()).or_default();
+        for part in ["subject", "body"] {
+            let name = template_name(campaign, *channel, locale, part);
+            let Ok(template) = env.get_template(&name) else {
+                // sms has no subject
+                continue;
+            };
+            scan_user_fields(template.source(), used);
+        }
+    }
+    fields
+}
+
+// `undeclared_variables` of minijinja skips sliced expressions such as
+// `user.created_at[:4]`, so the expressions in the tags are scanned instead.
+// `user.<field>` reads the field, `user` used in any other way could read every field
+fn scan_user_fields(source: &str, used: &mut BTreeSet<&'static str>) {
+    let mut rest = source;
+    while let Some((start, close)) = [("{{", "}}"), ("{%", "%}")]
+        .into_iter()
+        .filter_map(|(open, close)| rest.find(open).map(|i| (i, close)))
+        .min()
+    {
+        let tag = &rest[start + 2..];
+        let end = tag.find(close).unwrap_or(tag.len());
+        scan_expr(&tag[..end], used);
+        rest = &tag[end..];
+    }
+}
+
+fn scan_expr(expr: &str, used: &mut BTreeSet<&'static str>) {
+    let chars = expr.chars().collect::<Vec<_>>();
+    // last char before the current token which isn't a whitespace
+    let mut prev = ' ';
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c == '\'' || c == '"' {
+            i += 1;
+            while i < chars.len() && chars[i] != c {
+                i += if chars[i] == '\\' { 2 } else { 1 };
+            }
+            i += 1;
+            prev = c;
+        } else if c.is_alphabetic() || c == '_' {
+            let ident = read_ident(&chars, &mut i);
+            // `x.user` is an attribute of another variable
+            if ident == "user" && prev != '.' {
+                match read_attr(&chars, &mut i) {
+                    // unknown fields fail the validation
+                    Some(field) => used.extend(USER_FIELDS.iter().filter(|f| **f == field)),
+                    None => used.extend(USER_FIELDS),
+                }
+            }
+            prev = 'a';
+        } else {
+            if !c.is_whitespace() {
+                prev = c;
+            }
+            i += 1;
+        }
+    }
+}
+
+fn read_ident(chars: &[char], i: &mut usize) -> String {
+    let start = *i;
+    while *i < chars.len() && (chars[*i].is_alphanumeric() || chars[*i] == '_') {
+        *i += 1;
+    }
+    chars[start..*i].iter().collect()
+}
+
+// `.<ident>` after the variable
+fn read_attr(chars: &[char], i: &mut usize) -> Option<String> {
+    let skip_whitespace = |i: &mut usize| {
+        while *i < chars.len() && chars[*i].is_whitespace() {
+            *i += 1;
+        }
+    };
+    let mut j = *i;
+    skip_whitespace(&mut j);
+    if chars.get(j) != Some(&'.') {
+        return None;
+    }
+    j += 1;
+    skip_whitespace(&mut j);
+    let attr = read_ident(chars, &mut j);
+    if attr.is_empty() {
+        return None;
+    }
+    *i = j;
+    Some(attr)
+}
+
+fn load_dir(dir: &Path) -> anyhow::Result<HashMap<String, CampaignTemplates>> {
+    let mut campaigns = HashMap::new();
+    let entries = fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("yml") {
+            continue;
+        }
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| anyhow!("Invalid template file: {}", path.display()))?;
+        let file = fs::File::open(&path)?;
+        let templates = serde_yaml::from_reader(file)
+            .with_context(|| format!("Failed to parse {}", path.display()))?;
+        campaigns.insert(name.to_string(), templates);
+    }
+    Ok(campaigns)
+}
+
+fn to_rfc3339(ts: Option<Timestamp>) -> Option<String> {
+    ts.and_then(|ts| DateTime::from_timestamp(ts.seconds, ts.nanos as u32))
+        .map(|t| t.to_rfc3339_opts(SecondsFormat::Secs, true))
+}
+
+fn template_name(campaign: &str, channel: Channel, locale: &str, part: &str) -> String {
+    format!("{}/{}/{}/{}", campaign, channel_name(channel), locale, part)
+}
+
+fn channel_name(channel: Channel) -> &'static str {
+    match channel {
+        Channel::Email => "email",
+        Channel::Sms => "sms",
+        Channel::InApp => "in_app",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(yml: &str) -> TemplatesConfig {
+        serde_yaml::from_str(yml).unwrap()
+    }
+
+    #[test]
+    fn render_should_fall_back_to_language_and_default_locale() -> anyhow::Result<()> {
+        let templates = Templates::load(&config(
+            r#"
+            campaigns:
+              welcome:
+                email:
+                  en:
+                    subject: "Welcome {{ user.name }}"
+                    body: "{% for c in contents %}{{ c.name }}({{ c.content_type }}) {% endfor %}"
+                  zh:
+                    subject: "欢迎 {{ user.name }}"
+                    body: "{{ contents | length }} 个推荐"
+            "#,
+        ))?;
+        let contents = vec![
+            Content {
+                name: "a".to_string(),
+                content_type: ContentType::Anime as i32,
+                ..Default::default()
+            },
+            Content {
+                name: "b".to_string(),
+                ..Default::default()
+            },
+        ];
+        let mut user = User {
+            name: "Tyr".to_string(),
+            locale: "zh-CN".to_string(),
+            ..Default::default()
+        };
+
+        let ctx = TemplateContext::new(&user, &contents, "");
+        let rendered = templates.render("welcome", Channel::Email, &ctx)?;
+        assert_eq!("欢迎 Tyr", rendered.subject);
+        assert_eq!("2 个推荐", rendered.body);
+
+        user.locale = "fr".to_string();
+        let ctx = TemplateContext::new(&user, &contents, "");
+        let rendered = templates.render("welcome", Channel::Email, &ctx)?;
+        assert_eq!("Welcome Tyr", rendered.subject);
+        assert_eq!("a(ANIME) b(MOVIE) ", rendered.body);
+
+        assert!(templates.require("welcome", Channel::Email).is_ok());
+        assert!(templates.require("welcome", Channel::Sms).is_err());
+        assert!(templates.require("recall", Channel::Email).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn user_fields_should_be_rendered() -> anyhow::Result<()> {
+        let templates = Templates::load(&config(
+            r#"
+            campaigns:
+              remind:
+                sms:
+                  en:
+                    body: "{{ user.gender }} {{ user.created_at[:4] }} {{ user.finished | length }}"
+            "#,
+        ))?;
+        assert_eq!(
+            vec!["created_at", "finished", "gender"],
+            templates.user_fields("remind")
+        );
+        assert!(templates.user_fields("welcome").is_empty());
+        let user = User {
+            gender: Gender::Male as i32,
+            created_at: Some(Timestamp {
+                seconds: 1_725_000_000,
+                nanos: 0,
+            }),
+            finished: vec![1, 2],
+            ..Default::default()
+        };
+        let ctx = TemplateContext::new(&user, &[], "");
+        let rendered = templates.render("remind", Channel::Sms, &ctx)?;
+        assert_eq!("MALE 2024 2", rendered.body);
+        assert_eq!(Some("2024-08-30T06:40:00Z"), ctx.user.created_at.as_deref());
+        assert_eq!(None, ctx.user.last_visited_at);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_templates_should_fail_to_load() {
+        // unknown variable
+        assert!(Templates::load(&config(
+            r#"
+            campaigns:
+              welcome:
+                sms:
+                  en:
+                    body: "{{ user.age }}"
+            "#,
+        ))
+        .is_err());
+        // syntax error
+        assert!(Templates::load(&config(
+            r#"
+            campaigns:
+              welcome:
+                sms:
+                  en:
+                    body: "{% for c in contents %}"
+            "#,
+        ))
+        .is_err());
+        // no default locale
+        assert!(Templates::load(&config(
+            r#"
+            campaigns:
+              welcome:
+                sms:
+                  zh:
+                    body: "hi"
+            "#,
+        ))
+        .is_err());
+        // unknown times are not handled
+        assert!(Templates::load(&config(
+            r#"
+            campaigns:
+              recall:
+                sms:
+                  en:
+                    body: "{{ user.last_visited_at | length }}"
+            "#,
+        ))
+        .is_err());
+        // email without subject
+        assert!(Templates::load(&config(
+            r#"
+            campaigns:
+              welcome:
+                email:
+                  en:
+                    body: "hi"
+            "#,
+        ))
+        .is_err());
+    }
+
+    #[test]
+    fn templates_in_dir_should_be_valid() -> anyhow::Result<()> {
+        let templates = Templates::load(&config("dir: templates"))?;
+        for campaign in ["welcome", "recall", "remind"] {
+            templates.require(campaign, Channel::Email)?;
+            assert_eq!(vec!["name"], templates.user_fields(campaign));
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,159 @@
+use anyhow::{anyhow, bail};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+use user_stat::pb::NotificationChannel;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// what a user unsubscribes from, signed into the unsubscribe link of a notification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UnsubscribeToken {
+    pub email: String,
+    pub channel: NotificationChannel,
+    pub category: String,
+    // unix seconds when the notification was sent, old links expire
+    pub issued_at: i64,
+}
+
+impl UnsubscribeToken {
+    pub fn new(
+        email: impl Into<String>,
+        channel: NotificationChannel,
+        category: &str,
+        issued_at: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            email: email.into(),
+            channel,
+            category: category.to_string(),
+            issued_at: issued_at.timestamp(),
+        }
+    }
+
+    /// `<payload>.<signature>`, both are url safe base64
+    pub fn sign(&self, secret: &str) -> String {
+        // the category goes last, it could contain anything
+        let payload = format!(
+            "{}\n{}\n{}\n{}",
+            self.issued_at, self.email, self.channel as i32, self.category
+        );
+        let signature = mac(secret, payload.as_bytes()).finalize().into_bytes();
+        format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(payload),
+            URL_SAFE_NO_PAD.encode(signature)
+        )
+    }
+
+    /// a token is valid for `max_age` seconds after it's issued
+    pub fn verify(
+        token: &str,
+        secret: &str,
+        max_age: u64,
+        now: DateTime<Utc>,
+    ) -> anyhow::Result<Self> {
+        let invalid = || anyhow!("Invalid unsubscribe token");
+        let (payload, signature) = token.split_once('.').ok_or_else(invalid)?;
+        let payload = URL_SAFE_NO_PAD.decode(payload).map_err(|_| invalid())?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| invalid())?;
+        // constant time comparison
+        mac(secret, &payload)
+            .verify_slice(&signature)
+            .map_err(|_| invalid())?;
+
+        let payload = String::from_utf8(payload).map_err(|_| invalid())?;
+        let mut parts = payload.splitn(4, '\n');
+        let (Some(issued_at), Some(email), Some(channel), Some(category)) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            bail!(invalid());
+        };
+        let issued_at = issued_at.parse::<i64>().map_err(|_| invalid())?;
+        if now.timestamp().saturating_sub(issued_at) > max_age as i64 {
+            bail!("Unsubscribe token has expired");
+        }
+        let channel = channel
+            .parse::<i32>()
+            .ok()
+            .and_then(|c| NotificationChannel::try_from(c).ok())
+            .ok_or_else(invalid)?;
+        Ok(Self {
+            email: email.to_string(),
+            channel,
+            category: category.to_string(),
+            issued_at,
+        })
+    }
+
+    /// the unsubscribe link of a notification
+    pub fn url(&self, base_url: &str, secret: &str) -> String {
+        let sep = if base_url.contains('?') { '&' } else { '?' };
+        format!("{}{}token={}", base_url, sep, self.sign(secret))
+    }
+}
+
+fn mac(secret: &str, payload: &[u8]) -> HmacSha256 {
+    let mut mac =
+        HmacSha256::new_from_slice(secret.as_bytes()).expect("HMAC takes a key of any size");
+    mac.update(payload);
+    mac
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAX_AGE: u64 = 86400;
+
+    #[test]
+    fn unsubscribe_token_should_round_trip() -> anyhow::Result<()> {
+        let now = Utc::now();
+        let token =
+            UnsubscribeToken::new("tyr@acme.org", NotificationChannel::Email, "marketing", now);
+        let signed = token.sign("secret");
+        assert_eq!(
+            token,
+            UnsubscribeToken::verify(&signed, "secret", MAX_AGE, now)?
+        );
+
+        assert!(UnsubscribeToken::verify(&signed, "another secret", MAX_AGE, now).is_err());
+        let (_, signature) = signed.split_once('.').unwrap();
+        let forged = UnsubscribeToken::new("alice@acme.org", NotificationChannel::Email, "", now)
+            .sign("secret");
+        let (payload, _) = forged.split_once('.').unwrap();
+        let forged = format!("{}.{}", payload, signature);
+        assert!(UnsubscribeToken::verify(&forged, "secret", MAX_AGE, now).is_err());
+        assert!(UnsubscribeToken::verify("abc", "secret", MAX_AGE, now).is_err());
+
+        assert_eq!(
+            format!("https://crm.org/unsubscribe?token={}", signed),
+            token.url("https://crm.org/unsubscribe", "secret")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn expired_unsubscribe_token_should_be_rejected() -> anyhow::Result<()> {
+        let issued_at = "2024-09-01T00:00:00Z".parse::<DateTime<Utc>>()?;
+        let token = UnsubscribeToken::new(
+            "tyr@acme.org",
+            NotificationChannel::Email,
+            "marketing",
+            issued_at,
+        );
+        let signed = token.sign("secret");
+
+        let now = issued_at + chrono::Duration::seconds(MAX_AGE as i64);
+        assert_eq!(
+            token,
+            UnsubscribeToken::verify(&signed, "secret", MAX_AGE, now)?
+        );
+        let now = now + chrono::Duration::seconds(1);
+        assert!(UnsubscribeToken::verify(&signed, "secret", MAX_AGE, now).is_err());
+        Ok(())
+    }
+}
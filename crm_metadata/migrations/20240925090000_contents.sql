@@ -0,0 +1,31 @@
+-- Add migration script here
+CREATE TABLE publishers(
+  id serial PRIMARY KEY,
+  name varchar(128) NOT NULL,
+  avatar text NOT NULL DEFAULT '',
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE contents(
+  id serial PRIMARY KEY,
+  name varchar(256) NOT NULL,
+  description text NOT NULL DEFAULT '',
+  url text NOT NULL DEFAULT '',
+  image text NOT NULL DEFAULT '',
+  content_type varchar(16) NOT NULL DEFAULT 'other'
+    CHECK (content_type IN ('movie', 'tv_series', 'anime', 'short', 'other')),
+  views bigint NOT NULL DEFAULT 0,
+  likes bigint NOT NULL DEFAULT 0,
+  dislikes bigint NOT NULL DEFAULT 0,
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- publishers of a content, in the order of position
+CREATE TABLE content_publishers(
+  content_id int NOT NULL REFERENCES contents(id) ON DELETE CASCADE,
+  publisher_id int NOT NULL REFERENCES publishers(id) ON DELETE CASCADE,
+  position int NOT NULL DEFAULT 0,
+  PRIMARY KEY (content_id, publisher_id)
+);
+
+CREATE INDEX content_publishers_publisher_id_idx ON content_publishers(publisher_id);
@@ -0,0 +1,561 @@
+use std::collections::HashMap;
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Utc};
+use itertools::Itertools;
+use prost_types::Timestamp;
+use sqlx::{PgConnection, Postgres, QueryBuilder};
+use tonic::{Response, Status};
+
+use crate::abi::{
+    check_name, check_url, decode_token, encode_token, internal, page_size, to_datetime, to_db_ids,
+};
+use crate::pb::{
+    Content, ContentFilter, ContentInput, ContentType, CreateContentRequest, DeleteContentRequest,
+    DeleteResponse, GetContentRequest, ListContentsRequest, ListContentsResponse, Publisher,
+    UpdateContentRequest,
+};
+use crate::{MetadataService, ServiceResult};
+
+const CONTENT_COLUMNS: &str =
+    "id, name, description, url, image, content_type, views, likes, dislikes, created_at";
+const MAX_NAME_LEN: usize = 256;
+// paths of a field mask on Content
+const CONTENT_FIELDS: &[&str] = &[
+    "id",
+    "name",
+    "description",
+    "publishers",
+    "url",
+    "image",
+    "content_type",
+    "created_at",
+    "views",
+    "likes",
+    "dislikes",
+];
+
+#[derive(Debug, sqlx::FromRow)]
+struct ContentRow {
+    id: i32,
+    name: String,
+    description: String,
+    url: String,
+    image: String,
+    content_type: String,
+    views: i64,
+    likes: i64,
+    dislikes: i64,
+    created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, sqlx::FromRow)]
+struct PublisherRow {
+    content_id: i32,
+    id: i32,
+    name: String,
+    avatar: String,
+}
+
+/// a validated ContentInput
+struct ContentFields<'a> {
+    name: &'a str,
+    description: &'a str,
+    url: &'a str,
+    image: &'a str,
+    content_type: &'static str,
+    publisher_ids: Vec<i32>,
+}
+
+impl MetadataService {
+    pub async fn create_content(&self, req: CreateContentRequest) -> ServiceResult<Content> {
+        let input = req.content.unwrap_or_default();
+        let fields = input
+            .validate()
+            .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+
+        let mut tx = self.pool.begin().await.map_err(internal)?;
+        let id: i32 = sqlx::query_scalar(
+            "INSERT INTO contents(name, description, url, image, content_type) \
+             VALUES ($1, $2, $3, $4, $5) RETURNING id",
+        )
+        .bind(fields.name)
+        .bind(fields.description)
+        .bind(fields.url)
+        .bind(fields.image)
+        .bind(fields.content_type)
+        .fetch_one(&mut *tx)
+        .await
+        .map_err(internal)?;
+        link_publishers(&mut tx, id, &fields.publisher_ids).await?;
+        tx.commit().await.map_err(internal)?;
+
+        self.find_content(id as u32).await.map(Response::new)
+    }
+
+    pub async fn update_content(&self, req: UpdateContentRequest) -> ServiceResult<Content> {
+        let input = req.content.unwrap_or_default();
+        let fields = input
+            .validate()
+            .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+        let id = to_db_id(req.id).ok_or_else(|| content_not_found(req.id))?;
+
+        let mut tx = self.pool.begin().await.map_err(internal)?;
+        let ret = sqlx::query(
+            "UPDATE contents SET name = $2, description = $3, url = $4, image = $5, \
+             content_type = $6 WHERE id = $1",
+        )
+        .bind(id)
+        .bind(fields.name)
+        .bind(fields.description)
+        .bind(fields.url)
+        .bind(fields.image)
+        .bind(fields.content_type)
+        .execute(&mut *tx)
+        .await
+        .map_err(internal)?;
+        if ret.rows_affected() == 0 {
+            return Err(content_not_found(req.id));
+        }
+        sqlx::query("DELETE FROM content_publishers WHERE content_id = $1")
+            .bind(id)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+        link_publishers(&mut tx, id, &fields.publisher_ids).await?;
+        tx.commit().await.map_err(internal)?;
+        self.contents.remove(&req.id);
+
+        self.find_content(req.id).await.map(Response::new)
+    }
+
+    pub async fn delete_content(&self, req: DeleteContentRequest) -> ServiceResult<DeleteResponse> {
+        let id = to_db_id(req.id).ok_or_else(|| content_not_found(req.id))?;
+        let ret = sqlx::query("DELETE FROM contents WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(internal)?;
+        if ret.rows_affected() == 0 {
+            return Err(content_not_found(req.id));
+        }
+        self.contents.remove(&req.id);
+        Ok(Response::new(DeleteResponse { id: req.id }))
+    }
+
+    pub async fn get_content(&self, req: GetContentRequest) -> ServiceResult<Content> {
+        self.find_content(req.id).await.map(Response::new)
+    }
+
+    pub async fn list_contents(
+        &self,
+        req: ListContentsRequest,
+    ) -> ServiceResult<ListContentsResponse> {
+        let page_size = page_size(req.page_size);
+        let mut builder =
+            list_sql(&req, page_size).map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+        let mut rows: Vec<ContentRow> = builder
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(internal)?;
+
+        // one more row is fetched to tell whether there is a next page
+        let next_page_token = if rows.len() > page_size as usize {
+            rows.truncate(page_size as usize);
+            rows.last()
+                .map(|row| encode_token(row.id as u32))
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+        let contents = self
+            .with_publishers(rows)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to load contents: {:#}", e)))?;
+        Ok(Response::new(ListContentsResponse {
+            contents,
+            next_page_token,
+        }))
+    }
+
+    async fn find_content(&self, id: u32) -> Result<Content, Status> {
+        let mut contents = self
+            .load_contents(&[id])
+            .await
+            .map_err(|e| Status::internal(format!("Failed to load content: {:#}", e)))?;
+        contents.remove(&id).ok_or_else(|| content_not_found(id))
+    }
+
+    /// contents with their publishers by id, unknown ids are omitted
+    pub(crate) async fn load_contents(&self, ids: &[u32]) -> anyhow::Result<HashMap<u32, Content>> {
+        let ids = to_db_ids(ids);
+        if ids.is_empty() {
+            return Ok(HashMap::new());
+        }
+        let rows: Vec<ContentRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM contents WHERE id = ANY($1)",
+            CONTENT_COLUMNS
+        ))
+        .bind(&ids)
+        .fetch_all(&self.pool)
+        .await?;
+        let contents = self.with_publishers(rows).await?;
+        Ok(contents.into_iter().map(|c| (c.id, c)).collect())
+    }
+
+    // contents of the rows in the same order
+    async fn with_publishers(&self, rows: Vec<ContentRow>) -> anyhow::Result<Vec<Content>> {
+        let ids = rows.iter().map(|row| row.id).collect::<Vec<_>>();
+        let publishers: Vec<PublisherRow> = sqlx::query_as(
+            "SELECT cp.content_id, p.id, p.name, p.avatar FROM content_publishers cp \
+             JOIN publishers p ON p.id = cp.publisher_id \
+             WHERE cp.content_id = ANY($1) ORDER BY cp.content_id, cp.position, p.id",
+        )
+        .bind(&ids)
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut publishers = publishers
+            .into_iter()
+            .into_group_map_by(|row| row.content_id);
+        rows.into_iter()
+            .map(|row| {
+                let publishers = publishers.remove(&row.id).unwrap_or_default();
+                let mut content = Content::try_from(row)?;
+                content.publishers = publishers.into_iter().map(Publisher::from).collect();
+                Ok(content)
+            })
+            .collect()
+    }
+}
+
+impl Content {
+    /// keep the id and the fields in paths, all fields if paths is empty
+    pub(crate) fn mask(self, paths: &[String]) -> Self {
+        if paths.is_empty() {
+            return self;
+        }
+        let mut masked = Content {
+            id: self.id,
+            ..Default::default()
+        };
+        let mut content = self;
+        for path in paths {
+            match path.as_str() {
+                "name" => masked.name = std::mem::take(&mut content.name),
+                "description" => masked.description = std::mem::take(&mut content.description),
+                "publishers" => masked.publishers = std::mem::take(&mut content.publishers),
+                "url" => masked.url = std::mem::take(&mut content.url),
+                "image" => masked.image = std::mem::take(&mut content.image),
+                "content_type" => masked.content_type = content.content_type,
+                "created_at" => masked.created_at = content.created_at,
+                "views" => masked.views = content.views,
+                "likes" => masked.likes = content.likes,
+                "dislikes" => masked.dislikes = content.dislikes,
+                _ => {}
+            }
+        }
+        masked
+    }
+}
+
+pub(crate) fn check_field_mask(paths: &[String]) -> anyhow::Result<()> {
+    match paths
+        .iter()
+        .find(|path| !CONTENT_FIELDS.contains(&path.as_str()))
+    {
+        Some(path) => bail!("Unknown field in field mask: {}", path),
+        None => Ok(()),
+    }
+}
+
+impl ContentInput {
+    fn validate(&self) -> anyhow::Result<ContentFields<'_>> {
+        let content_type = ContentType::try_from(self.content_type)
+            .map_err(|_| anyhow!("Unknown content type: {}", self.content_type))?;
+        check_url("url", &self.url, true)?;
+        check_url("image", &self.image, false)?;
+        // keep the first of duplicate publishers
+        let publisher_ids = self
+            .publisher_ids
+            .iter()
+            .unique()
+            .copied()
+            .collect::<Vec<_>>();
+        let db_ids = to_db_ids(&publisher_ids);
+        if db_ids.len() < publisher_ids.len() {
+            bail!("Invalid publisher id");
+        }
+        Ok(ContentFields {
+            name: check_name(&self.name, MAX_NAME_LEN)?,
+            description: &self.description,
+            url: &self.url,
+            image: &self.image,
+            content_type: content_type.name(),
+            publisher_ids: db_ids,
+        })
+    }
+}
+
+// publishers must exist, they are linked in the given order
+async fn link_publishers(
+    conn: &mut PgConnection,
+    content_id: i32,
+    publisher_ids: &[i32],
+) -> Result<(), Status> {
+    if publisher_ids.is_empty() {
+        return Ok(());
+    }
+    let ret = sqlx::query(
+        "INSERT INTO content_publishers(content_id, publisher_id, position) \
+         SELECT $1, p.id, ids.position::int FROM unnest($2::int[]) WITH ORDINALITY AS ids(id, position) \
+         JOIN publishers p ON p.id = ids.id",
+    )
+    .bind(content_id)
+    .bind(publisher_ids)
+    .execute(conn)
+    .await
+    .map_err(internal)?;
+    if ret.rows_affected() < publisher_ids.len() as u64 {
+        return Err(Status::invalid_argument("Publisher not found"));
+    }
+    Ok(())
+}
+
+fn list_sql(
+    req: &ListContentsRequest,
+    page_size: u32,
+) -> anyhow::Result<QueryBuilder<'static, Postgres>> {
+    let mut builder = QueryBuilder::new(format!(
+        "SELECT {} FROM contents WHERE TRUE",
+        CONTENT_COLUMNS
+    ));
+    if let Some(filter) = req.filter.as_ref() {
+        push_filter(&mut builder, filter)?;
+    }
+    if !req.page_token.is_empty() {
+        builder
+            .push(" AND id < ")
+            .push_bind(decode_token(&req.page_token)? as i64);
+    }
+    builder
+        .push(" ORDER BY id DESC LIMIT ")
+        .push_bind(page_size as i64 + 1);
+    Ok(builder)
+}
+
+fn push_filter(
+    builder: &mut QueryBuilder<'static, Postgres>,
+    filter: &ContentFilter,
+) -> anyhow::Result<()> {
+    if !filter.content_types.is_empty() {
+        let types = filter
+            .content_types
+            .iter()
+            .map(|&t| {
+                ContentType::try_from(t)
+                    .map(|t| t.name().to_string())
+                    .map_err(|_| anyhow!("Unknown content type: {}", t))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        builder
+            .push(" AND content_type = ANY(")
+            .push_bind(types)
+            .push(")");
+    }
+    if !filter.publisher_ids.is_empty() {
+        builder
+            .push(" AND id IN (SELECT content_id FROM content_publishers WHERE publisher_id = ANY(")
+            .push_bind(to_db_ids(&filter.publisher_ids))
+            .push("))");
+    }
+    if let Some(ts) = filter.created_after {
+        builder
+            .push(" AND created_at >= ")
+            .push_bind(to_datetime(ts)?);
+    }
+    if let Some(ts) = filter.created_before {
+        builder
+            .push(" AND created_at < ")
+            .push_bind(to_datetime(ts)?);
+    }
+    Ok(())
+}
+
+fn to_db_id(id: u32) -> Option<i32> {
+    i32::try_from(id).ok()
+}
+
+fn content_not_found(id: u32) -> Status {
+    Status::not_found(format!("Content {} not found", id))
+}
+
+impl ContentType {
+    /// name of the content_type column
+    pub fn name(&self) -> &'static str {
+        match self {
+            ContentType::Movie => "movie",
+            ContentType::TvSeries => "tv_series",
+            ContentType::Anime => "anime",
+            ContentType::Short => "short",
+            ContentType::Other => "other",
+        }
+    }
+
+    pub fn from_name(name: &str) -> anyhow::Result<Self> {
+        let content_type = match name {
+            "movie" => ContentType::Movie,
+            "tv_series" => ContentType::TvSeries,
+            "anime" => ContentType::Anime,
+            "short" => ContentType::Short,
+            "other" => ContentType::Other,
+            _ => bail!("Unknown content type: {}", name),
+        };
+        Ok(content_type)
+    }
+}
+
+impl TryFrom<ContentRow> for Content {
+    type Error = anyhow::Error;
+
+    fn try_from(row: ContentRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            id: row.id as u32,
+            name: row.name,
+            description: row.description,
+            publishers: vec![],
+            url: row.url,
+            image: row.image,
+            content_type: ContentType::from_name(&row.content_type)? as i32,
+            created_at: Some(to_ts(row.created_at)),
+            views: row.views as u64,
+            likes: row.likes as u64,
+            dislikes: row.dislikes as u64,
+        })
+    }
+}
+
+impl From<PublisherRow> for Publisher {
+    fn from(row: PublisherRow) -> Self {
+        Self {
+            id: row.id as u32,
+            name: row.name,
+            avatar: row.avatar,
+        }
+    }
+}
+
+fn to_ts(t: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: t.timestamp(),
+        nanos: t.timestamp_subsec_nanos() as i32,
+    }
+}
+
+#[cfg(feature = "test-util")]
+mod fake_content {
+    use chrono::{DateTime, Days, TimeZone, Utc};
+    use fake::faker::chrono::en::DateTimeBetween;
+    use fake::faker::internet::en::DomainSuffix;
+    use fake::faker::lorem::en::Sentence;
+    use fake::faker::name::en::Name;
+    use fake::Fake;
+    use rand::{thread_rng, Rng};
+
+    use super::to_ts;
+    use crate::pb::{Content, ContentType, Publisher};
+    use crate::MetadataService;
+
+    impl MetadataService {
+        /// insert random contents with their publishers, e.g. for tests
+        pub async fn insert_fake_contents(&self, count: usize) -> anyhow::Result<Vec<Content>> {
+            let mut tx = self.pool.begin().await?;
+            let mut ids = Vec::with_capacity(count);
+            for _ in 0..count {
+                let content = Content::new(0);
+                let content_type = ContentType::try_from(content.content_type)?;
+                let created_at = content
+                    .created_at
+                    .and_then(|ts| Utc.timestamp_opt(ts.seconds, ts.nanos as u32).single())
+                    .unwrap_or_else(Utc::now);
+                let id: i32 = sqlx::query_scalar(
+                    "INSERT INTO contents(name, description, url, image, content_type, \
+                     views, likes, dislikes, created_at) \
+                     VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id",
+                )
+                .bind(&content.name)
+                .bind(&content.description)
+                .bind(&content.url)
+                .bind(&content.image)
+                .bind(content_type.name())
+                .bind(content.views as i64)
+                .bind(content.likes as i64)
+                .bind(content.dislikes as i64)
+                .bind(created_at)
+                .fetch_one(&mut *tx)
+                .await?;
+                for (position, publisher) in content.publishers.iter().enumerate() {
+                    let publisher_id: i32 = sqlx::query_scalar(
+                        "INSERT INTO publishers(name, avatar) VALUES ($1, $2) RETURNING id",
+                    )
+                    .bind(&publisher.name)
+                    .bind(&publisher.avatar)
+                    .fetch_one(&mut *tx)
+                    .await?;
+                    sqlx::query(
+                        "INSERT INTO content_publishers(content_id, publisher_id, position) \
+                         VALUES ($1, $2, $3)",
+                    )
+                    .bind(id)
+                    .bind(publisher_id)
+                    .bind(position as i32)
+                    .execute(&mut *tx)
+                    .await?;
+                }
+                ids.push(id as u32);
+            }
+            tx.commit().await?;
+
+            let mut contents = self.load_contents(&ids).await?;
+            Ok(ids.iter().filter_map(|id| contents.remove(id)).collect())
+        }
+    }
+
+    impl Content {
+        pub fn new(id: u32) -> Self {
+            let psize = thread_rng().gen_range(1..5);
+            let mut publishers = Vec::with_capacity(psize);
+            for _ in 0..psize {
+                publishers.push(Publisher::new());
+            }
+            Self {
+                id,
+                name: Name().fake(),
+                description: Sentence(1..3).fake(),
+                publishers,
+                url: format!("https://example.{}", DomainSuffix().fake::<String>()),
+                image: "https://placehold.co/1600x900".to_string(),
+                content_type: thread_rng().gen_range(0..5),
+                created_at: Some(to_ts(DateTimeBetween(before(120), before(1)).fake())),
+                views: (1000..1000000).fake(),
+                likes: (5..100000).fake(),
+                dislikes: (5..100000).fake(),
+            }
+        }
+    }
+
+    impl Publisher {
+        pub fn new() -> Self {
+            Self {
+                id: (100..1000000).fake(),
+                name: Name().fake(),
+                avatar: "https://placehold.co/600x600".to_string(),
+            }
+        }
+    }
+
+    fn before(days: u64) -> DateTime<Utc> {
+        Utc::now().checked_sub_days(Days::new(days)).unwrap()
+    }
+}
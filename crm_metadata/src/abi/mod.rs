@@ -1,90 +1,141 @@
-use std::collections::HashSet;
-
-use chrono::{DateTime, Days, Utc};
-use fake::faker::chrono::en::DateTimeBetween;
-use fake::faker::internet::en::DomainSuffix;
-use fake::faker::lorem::en::Sentence;
-use fake::faker::name::en::Name;
-use fake::{Fake, Faker};
+use std::collections::{HashMap, HashSet};
+
+use anyhow::{anyhow, bail};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
 use futures::{Stream, StreamExt};
-use prost_types::Timestamp;
-use rand::{thread_rng, Rng};
+use itertools::Itertools;
 use tokio::sync::mpsc;
 use tonic::codegen::tokio_stream;
 use tonic::codegen::tokio_stream::wrappers::ReceiverStream;
-use tonic::Response;
+use tonic::{Response, Status};
+use tracing::warn;
+use url::Url;
 
-use crate::pb::{Content, MaterializeRequest, Publisher};
+use crate::abi::content::check_field_mask;
+use crate::pb::{BatchMaterializeRequest, BatchMaterializeResponse, Content, MaterializeRequest};
 use crate::{MetadataService, ResponseStream, ServiceResult};
 
+mod content;
+mod publisher;
+
 const CHANNEL_SIZE: usize = 1024;
+// max ids looked up at once
+const BATCH_SIZE: usize = 100;
+// max ids of a BatchMaterialize request
+const MAX_IDS: usize = 1000;
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
+
 impl MetadataService {
     // it's hard to construct a tonic::Streaming request for test
     // so use generic stream instead
-    pub async fn materialize<S>(&self, mut stream: S) -> ServiceResult<ResponseStream>
+    /// contents are returned in the order of requests, unknown ids are reported
+    /// by a NotFound status after the contents
+    pub async fn materialize<S>(&self, stream: S) -> ServiceResult<ResponseStream>
     where
         S: Stream<Item = Result<MaterializeRequest, tonic::Status>> + Send + 'static + Unpin,
     {
         let (tx, rx) = mpsc::channel::<Result<Content, tonic::Status>>(CHANNEL_SIZE);
+        let svc = self.clone();
 
         tokio::spawn(async move {
-            while let Some(Ok(req)) = stream.next().await {
-                // get request id from client
-                let id = req.id;
-                // generate dummy content
-                let content = Content::new(id);
-                // Send to client
-                tx.send(Ok(content)).await.unwrap();
+            let mut missing = Vec::new();
+            // requests which are ready at the same time are looked up together
+            let mut chunks = stream.ready_chunks(BATCH_SIZE);
+            while let Some(chunk) = chunks.next().await {
+                let len = chunk.len();
+                let ids = chunk
+                    .into_iter()
+                    .map_while(Result::ok)
+                    .map(|req| req.id)
+                    .collect::<Vec<_>>();
+                let stop = ids.len() < len;
+                let contents = match svc.get_contents(&ids).await {
+                    Ok(contents) => contents,
+                    Err(e) => {
+                        warn!("Failed to load contents: {:#}", e);
+                        let status = Status::internal(format!("Failed to load contents: {}", e));
+                        let _ = tx.send(Err(status)).await;
+                        return;
+                    }
+                };
+                for id in ids {
+                    let Some(content) = contents.get(&id) else {
+                        missing.push(id);
+                        continue;
+                    };
+                    if let Err(e) = tx.send(Ok(content.clone())).await {
+                        warn!("Failed to send content: {}", e);
+                        return;
+                    }
+                }
+                if stop {
+                    break;
+                }
+            }
+            if !missing.is_empty() {
+                let status =
+                    Status::not_found(format!("Contents not found: {}", missing.iter().join(", ")));
+                let _ = tx.send(Err(status)).await;
             }
         });
 
         let ret_stream = ReceiverStream::new(rx);
         Ok(Response::new(Box::pin(ret_stream)))
     }
-}
 
-impl Content {
-    pub fn new(id: u32) -> Self {
-        let psize = thread_rng().gen_range(1..5);
-        let mut publishers = Vec::with_capacity(psize);
-        for _ in 0..psize {
-            publishers.push(Publisher::new());
-        }
-        Self {
-            id,
-            name: Name().fake(),
-            description: Sentence(1..3).fake(),
-            publishers,
-            url: DomainSuffix().fake(),
-            image: "https://placehold.co/1600x900".to_string(),
-            content_type: Faker.fake(),
-            created_at: Some(timestamp()),
-            views: (1000..1000000).fake(),
-            likes: (5..100000).fake(),
-            dislikes: (5..100000).fake(),
+    /// contents in the order of ids, a duplicate id is returned once and unknown
+    /// ids are reported in `missing`
+    pub async fn batch_materialize(
+        &self,
+        req: BatchMaterializeRequest,
+    ) -> ServiceResult<BatchMaterializeResponse> {
+        if req.ids.len() > MAX_IDS {
+            return Err(Status::invalid_argument(format!(
+                "Too many ids, max is {}",
+                MAX_IDS
+            )));
         }
-    }
-}
-
-fn before(days: u64) -> DateTime<Utc> {
-    Utc::now().checked_sub_days(Days::new(days)).unwrap()
-}
+        let paths = req.field_mask.map(|mask| mask.paths).unwrap_or_default();
+        check_field_mask(&paths).map_err(|e| Status::invalid_argument(e.to_string()))?;
 
-fn timestamp() -> Timestamp {
-    let fake_ts: DateTime<Utc> = DateTimeBetween(before(120), before(1)).fake();
-    Timestamp {
-        seconds: fake_ts.timestamp(),
-        nanos: fake_ts.timestamp_subsec_nanos() as i32,
+        let ids = req.ids.into_iter().unique().collect::<Vec<_>>();
+        let mut contents = self.get_contents(&ids).await.map_err(|e| {
+            warn!("Failed to load contents: {:#}", e);
+            Status::internal(format!("Failed to load contents: {}", e))
+        })?;
+        let mut resp = BatchMaterializeResponse::default();
+        for id in ids {
+            match contents.remove(&id) {
+                Some(content) => resp.contents.push(content.mask(&paths)),
+                None => resp.missing.push(id),
+            }
+        }
+        Ok(Response::new(resp))
     }
-}
 
-impl Publisher {
-    pub fn new() -> Self {
-        Self {
-            id: (100..1000000).fake(),
-            name: Name().fake(),
-            avatar: "https://placehold.co/600x600".to_string(),
+    /// contents by id from the cache, the others are loaded and cached.
+    /// Unknown ids are omitted
+    async fn get_contents(&self, ids: &[u32]) -> anyhow::Result<HashMap<u32, Content>> {
+        let mut contents = HashMap::with_capacity(ids.len());
+        let mut misses = Vec::new();
+        for &id in ids {
+            match self.contents.get(&id) {
+                Some(content) => {
+                    contents.insert(id, content);
+                }
+                None => misses.push(id),
+            }
+        }
+        if !misses.is_empty() {
+            for (id, content) in self.load_contents(&misses).await? {
+                self.contents.insert(id, content.clone());
+                contents.insert(id, content);
+            }
         }
+        Ok(contents)
     }
 }
 
@@ -98,10 +149,75 @@ impl MaterializeRequest {
     }
 }
 
+fn page_size(size: u32) -> u32 {
+    match size {
+        0 => DEFAULT_PAGE_SIZE,
+        size => size.min(MAX_PAGE_SIZE),
+    }
+}
+
+// pages are ordered by id desc, the token is the last id of the previous page
+fn encode_token(id: u32) -> String {
+    URL_SAFE_NO_PAD.encode(id.to_string())
+}
+
+fn decode_token(token: &str) -> anyhow::Result<u32> {
+    URL_SAFE_NO_PAD
+        .decode(token)
+        .ok()
+        .and_then(|bytes| String::from_utf8(bytes).ok())
+        .and_then(|id| id.parse().ok())
+        .ok_or_else(|| anyhow!("Invalid page token"))
+}
+
+fn to_datetime(ts: prost_types::Timestamp) -> anyhow::Result<DateTime<Utc>> {
+    DateTime::from_timestamp(ts.seconds, ts.nanos as u32)
+        .ok_or_else(|| anyhow!("Invalid timestamp: {}", ts))
+}
+
+// ids of the db, which are out of its range are dropped
+fn to_db_ids(ids: &[u32]) -> Vec<i32> {
+    ids.iter()
+        .filter_map(|&id| i32::try_from(id).ok())
+        .collect()
+}
+
+fn check_name(name: &str, max_len: usize) -> anyhow::Result<&str> {
+    let name = name.trim();
+    if name.is_empty() {
+        bail!("Empty name");
+    }
+    if name.chars().count() > max_len {
+        bail!("Name is longer than {} characters", max_len);
+    }
+    Ok(name)
+}
+
+fn internal(e: sqlx::Error) -> Status {
+    Status::internal(e.to_string())
+}
+
+/// only absolute http(s) urls are accepted, an optional one may be empty
+fn check_url(field: &str, url: &str, required: bool) -> anyhow::Result<()> {
+    if url.is_empty() {
+        if required {
+            bail!("Empty {}", field);
+        }
+        return Ok(());
+    }
+    let parsed = Url::parse(url).map_err(|e| anyhow!("Invalid {} {}: {}", field, url, e))?;
+    if !matches!(parsed.scheme(), "http" | "https") || parsed.host().is_none() {
+        bail!("Invalid {} {}: not a http(s) url", field, url);
+    }
+    Ok(())
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
+    use prost_types::FieldMask;
     use tonic::codegen::tokio_stream;
+    use tonic::Code;
 
     use crate::AppConfig;
 
@@ -109,19 +225,87 @@ mod tests {
 
     #[tokio::test]
     async fn test_materialize() -> Result<()> {
-        let svc = MetadataService::new(AppConfig::load().unwrap());
-        let stream = tokio_stream::iter(
-            vec![
-                Ok(MaterializeRequest { id: 1 }),
-                Ok(MaterializeRequest { id: 2 }),
-            ]
-            .into_iter(),
+        let svc = MetadataService::new(AppConfig::load().unwrap()).await;
+        let contents = svc.insert_fake_contents(2).await?;
+        let stream = tokio_stream::iter(vec![
+            Ok(MaterializeRequest { id: contents[1].id }),
+            Ok(MaterializeRequest { id: u32::MAX }),
+            Ok(MaterializeRequest { id: contents[0].id }),
+        ]);
+        let res = svc
+            .materialize(stream)
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await;
+
+        assert_eq!(3, res.len());
+        assert_eq!(&contents[1], res[0].as_ref().unwrap());
+        assert_eq!(&contents[0], res[1].as_ref().unwrap());
+        assert!(!contents[0].publishers.is_empty());
+        let status = res[2].as_ref().unwrap_err();
+        assert_eq!(Code::NotFound, status.code());
+        assert_eq!(
+            format!("Contents not found: {}", u32::MAX),
+            status.message()
         );
-        // Send request
-        let mut resp_stream = svc.materialize(stream).await?.into_inner();
-        while let Some(Ok(material)) = resp_stream.next().await {
-            println!("material: {:#?}", material);
-        }
         Ok(())
     }
+
+    #[tokio::test]
+    async fn batch_materialize_should_mask_fields() -> Result<()> {
+        let svc = MetadataService::new(AppConfig::load().unwrap()).await;
+        let contents = svc.insert_fake_contents(2).await?;
+        let req = BatchMaterializeRequest {
+            ids: vec![contents[1].id, u32::MAX, contents[0].id, contents[1].id],
+            field_mask: Some(FieldMask {
+                paths: vec!["name".to_string(), "url".to_string()],
+            }),
+        };
+        let res = svc.batch_materialize(req).await?.into_inner();
+
+        let ids = res.contents.iter().map(|c| c.id).collect::<Vec<_>>();
+        assert_eq!(vec![contents[1].id, contents[0].id], ids);
+        assert_eq!(vec![u32::MAX], res.missing);
+        let content = &res.contents[0];
+        assert_eq!(contents[1].name, content.name);
+        assert_eq!(contents[1].url, content.url);
+        assert!(content.publishers.is_empty());
+        assert_eq!(None, content.created_at);
+
+        // served from the cache
+        assert_eq!(
+            Some(&contents[0]),
+            svc.contents.get(&contents[0].id).as_ref()
+        );
+
+        let req = BatchMaterializeRequest {
+            ids: vec![contents[0].id],
+            field_mask: Some(FieldMask {
+                paths: vec!["title".to_string()],
+            }),
+        };
+        let status = svc.batch_materialize(req).await.unwrap_err();
+        assert_eq!(Code::InvalidArgument, status.code());
+        Ok(())
+    }
+
+    #[test]
+    fn check_url_should_work() {
+        assert!(check_url("url", "https://acme.org/movies/1", true).is_ok());
+        assert!(check_url("url", "http://acme.org", true).is_ok());
+        assert!(check_url("image", "", false).is_ok());
+        assert!(check_url("url", "", true).is_err());
+        assert!(check_url("url", "acme.org", true).is_err());
+        assert!(check_url("url", "ftp://acme.org/1", true).is_err());
+        assert!(check_url("url", "javascript:alert(1)", true).is_err());
+    }
+
+    #[test]
+    fn page_token_should_round_trip() {
+        assert_eq!(42, decode_token(&encode_token(42)).unwrap());
+        assert!(decode_token("not-a-token").is_err());
+        assert_eq!(DEFAULT_PAGE_SIZE, page_size(0));
+        assert_eq!(MAX_PAGE_SIZE, page_size(u32::MAX));
+    }
 }
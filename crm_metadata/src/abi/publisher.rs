@@ -0,0 +1,162 @@
+use sqlx::{Postgres, QueryBuilder};
+use tonic::{Response, Status};
+
+use crate::abi::{check_name, check_url, decode_token, encode_token, internal, page_size};
+use crate::pb::{
+    CreatePublisherRequest, DeletePublisherRequest, DeleteResponse, GetPublisherRequest,
+    ListPublishersRequest, ListPublishersResponse, Publisher, PublisherInput,
+    UpdatePublisherRequest,
+};
+use crate::{MetadataService, ServiceResult};
+
+const MAX_NAME_LEN: usize = 128;
+
+#[derive(Debug, sqlx::FromRow)]
+struct PublisherRow {
+    id: i32,
+    name: String,
+    avatar: String,
+}
+
+impl MetadataService {
+    pub async fn create_publisher(&self, req: CreatePublisherRequest) -> ServiceResult<Publisher> {
+        let input = req.publisher.unwrap_or_default();
+        let name = input
+            .validate()
+            .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+        let row: PublisherRow = sqlx::query_as(
+            "INSERT INTO publishers(name, avatar) VALUES ($1, $2) RETURNING id, name, avatar",
+        )
+        .bind(name)
+        .bind(&input.avatar)
+        .fetch_one(&self.pool)
+        .await
+        .map_err(internal)?;
+        Ok(Response::new(row.into()))
+    }
+
+    pub async fn update_publisher(&self, req: UpdatePublisherRequest) -> ServiceResult<Publisher> {
+        let input = req.publisher.unwrap_or_default();
+        let name = input
+            .validate()
+            .map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+        let id = i32::try_from(req.id).map_err(|_| publisher_not_found(req.id))?;
+        let row: Option<PublisherRow> = sqlx::query_as(
+            "UPDATE publishers SET name = $2, avatar = $3 WHERE id = $1 \
+             RETURNING id, name, avatar",
+        )
+        .bind(id)
+        .bind(name)
+        .bind(&input.avatar)
+        .fetch_optional(&self.pool)
+        .await
+        .map_err(internal)?;
+        let row = row.ok_or_else(|| publisher_not_found(req.id))?;
+        // cached contents of the publisher are outdated
+        let content_ids = self.publisher_contents(id).await?;
+        self.invalidate(&content_ids);
+        Ok(Response::new(row.into()))
+    }
+
+    /// the publisher is unlinked from its contents, which are kept
+    pub async fn delete_publisher(
+        &self,
+        req: DeletePublisherRequest,
+    ) -> ServiceResult<DeleteResponse> {
+        let id = i32::try_from(req.id).map_err(|_| publisher_not_found(req.id))?;
+        // the links are gone after the publisher is deleted
+        let content_ids = self.publisher_contents(id).await?;
+        let ret = sqlx::query("DELETE FROM publishers WHERE id = $1")
+            .bind(id)
+            .execute(&self.pool)
+            .await
+            .map_err(internal)?;
+        if ret.rows_affected() == 0 {
+            return Err(publisher_not_found(req.id));
+        }
+        self.invalidate(&content_ids);
+        Ok(Response::new(DeleteResponse { id: req.id }))
+    }
+
+    pub async fn get_publisher(&self, req: GetPublisherRequest) -> ServiceResult<Publisher> {
+        let id = i32::try_from(req.id).map_err(|_| publisher_not_found(req.id))?;
+        let row: Option<PublisherRow> =
+            sqlx::query_as("SELECT id, name, avatar FROM publishers WHERE id = $1")
+                .bind(id)
+                .fetch_optional(&self.pool)
+                .await
+                .map_err(internal)?;
+        row.map(|row| Response::new(row.into()))
+            .ok_or_else(|| publisher_not_found(req.id))
+    }
+
+    pub async fn list_publishers(
+        &self,
+        req: ListPublishersRequest,
+    ) -> ServiceResult<ListPublishersResponse> {
+        let page_size = page_size(req.page_size);
+        let mut builder = QueryBuilder::<Postgres>::new("SELECT id, name, avatar FROM publishers");
+        if !req.page_token.is_empty() {
+            let last_id = decode_token(&req.page_token)
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+            builder.push(" WHERE id < ").push_bind(last_id as i64);
+        }
+        builder
+            .push(" ORDER BY id DESC LIMIT ")
+            .push_bind(page_size as i64 + 1);
+        let mut rows: Vec<PublisherRow> = builder
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(internal)?;
+
+        // one more row is fetched to tell whether there is a next page
+        let next_page_token = if rows.len() > page_size as usize {
+            rows.truncate(page_size as usize);
+            rows.last()
+                .map(|row| encode_token(row.id as u32))
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+        Ok(Response::new(ListPublishersResponse {
+            publishers: rows.into_iter().map(Publisher::from).collect(),
+            next_page_token,
+        }))
+    }
+
+    async fn publisher_contents(&self, id: i32) -> Result<Vec<i32>, Status> {
+        sqlx::query_scalar("SELECT content_id FROM content_publishers WHERE publisher_id = $1")
+            .bind(id)
+            .fetch_all(&self.pool)
+            .await
+            .map_err(internal)
+    }
+
+    fn invalidate(&self, content_ids: &[i32]) {
+        for &id in content_ids {
+            self.contents.remove(&(id as u32));
+        }
+    }
+}
+
+impl PublisherInput {
+    fn validate(&self) -> anyhow::Result<&str> {
+        check_url("avatar", &self.avatar, false)?;
+        check_name(&self.name, MAX_NAME_LEN)
+    }
+}
+
+impl From<PublisherRow> for Publisher {
+    fn from(row: PublisherRow) -> Self {
+        Self {
+            id: row.id as u32,
+            name: row.name,
+            avatar: row.avatar,
+        }
+    }
+}
+
+fn publisher_not_found(id: u32) -> Status {
+    Status::not_found(format!("Publisher {} not found", id))
+}
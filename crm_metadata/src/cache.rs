@@ -0,0 +1,120 @@
+use std::hash::Hash;
+use std::num::NonZeroUsize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use lru::LruCache;
+use serde::{Deserialize, Serialize};
+
+/// in-process cache of contents, shared by crm_metadata and its clients
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    // max entries, the least recently used one is evicted when it's full
+    pub capacity: usize,
+    // seconds an entry is kept, 0 disables the cache
+    pub ttl: u64,
+}
+
+/// LRU cache whose entries expire after a ttl
+pub struct Cache<K, V> {
+    entries: Mutex<LruCache<K, Entry<V>>>,
+    ttl: Duration,
+}
+
+struct Entry<V> {
+    value: V,
+    expires_at: Instant,
+}
+
+impl<K: Hash + Eq, V: Clone> Cache<K, V> {
+    pub fn new(config: &CacheConfig) -> Self {
+        let capacity = NonZeroUsize::new(config.capacity).unwrap_or(NonZeroUsize::MIN);
+        Self {
+            entries: Mutex::new(LruCache::new(capacity)),
+            ttl: Duration::from_secs(config.ttl),
+        }
+    }
+
+    pub fn get(&self, key: &K) -> Option<V> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some(entry) if entry.expires_at > Instant::now() => Some(entry.value.clone()),
+            Some(_) => {
+                entries.pop(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    pub fn insert(&self, key: K, value: V) {
+        if self.ttl.is_zero() {
+            return;
+        }
+        let entry = Entry {
+            value,
+            expires_at: Instant::now() + self.ttl,
+        };
+        self.entries.lock().unwrap().put(key, entry);
+    }
+
+    pub fn remove(&self, key: &K) {
+        self.entries.lock().unwrap().pop(key);
+    }
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 10000,
+            ttl: 60,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cache_should_evict_least_recently_used() {
+        let cache = Cache::new(&CacheConfig {
+            capacity: 2,
+            ttl: 60,
+        });
+        cache.insert(1, "a");
+        cache.insert(2, "b");
+        assert_eq!(Some("a"), cache.get(&1));
+        cache.insert(3, "c");
+        assert_eq!(None, cache.get(&2));
+        assert_eq!(Some("a"), cache.get(&1));
+        assert_eq!(Some("c"), cache.get(&3));
+        cache.remove(&3);
+        assert_eq!(None, cache.get(&3));
+    }
+
+    #[test]
+    fn cache_should_expire_entries() {
+        let cache = Cache::new(&CacheConfig {
+            capacity: 2,
+            ttl: 60,
+        });
+        cache.insert(1, "a");
+        cache
+            .entries
+            .lock()
+            .unwrap()
+            .get_mut(&1)
+            .unwrap()
+            .expires_at = Instant::now();
+        assert_eq!(None, cache.get(&1));
+
+        let disabled = Cache::new(&CacheConfig {
+            capacity: 2,
+            ttl: 0,
+        });
+        disabled.insert(1, "a");
+        assert_eq!(None, disabled.get(&1));
+    }
+}
@@ -3,11 +3,15 @@ use std::fs::File;
 use anyhow::bail;
 use serde::{Deserialize, Serialize};
 
+use crate::cache::CacheConfig;
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct AppConfig {
     pub server: ServerConfig,
     pub db_url: String,
     pub auth: AuthConfig,
+    #[serde(default)]
+    pub cache: CacheConfig,
 }
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
@@ -1,20 +1,39 @@
+use std::ops::Deref;
 use std::pin::Pin;
+use std::sync::Arc;
 
 use futures::Stream;
+use sqlx::PgPool;
 use tonic::{async_trait, Request, Response, Status, Streaming};
 
 pub use config::AppConfig;
 
+use crate::cache::Cache;
 use crate::pb::metadata_server::{Metadata, MetadataServer};
-use crate::pb::{Content, MaterializeRequest};
+use crate::pb::{
+    BatchMaterializeRequest, BatchMaterializeResponse, Content, CreateContentRequest,
+    CreatePublisherRequest, DeleteContentRequest, DeletePublisherRequest, DeleteResponse,
+    GetContentRequest, GetPublisherRequest, ListContentsRequest, ListContentsResponse,
+    ListPublishersRequest, ListPublishersResponse, MaterializeRequest, Publisher,
+    UpdateContentRequest, UpdatePublisherRequest,
+};
 
 mod abi;
+pub mod cache;
 mod config;
 pub mod pb;
 
-#[allow(unused)]
+#[derive(Clone)]
 pub struct MetadataService {
+    inner: Arc<MetadataServiceInner>,
+}
+
+#[allow(unused)]
+pub struct MetadataServiceInner {
     config: AppConfig,
+    pool: PgPool,
+    // contents by id, invalidated when they or their publishers change
+    contents: Cache<u32, Content>,
 }
 
 type ServiceResult<T> = Result<Response<T>, Status>;
@@ -30,11 +49,102 @@ impl Metadata for MetadataService {
         let req = request.into_inner();
         self.materialize(req).await
     }
+
+    async fn batch_materialize(
+        &self,
+        request: Request<BatchMaterializeRequest>,
+    ) -> ServiceResult<BatchMaterializeResponse> {
+        self.batch_materialize(request.into_inner()).await
+    }
+
+    async fn create_content(
+        &self,
+        request: Request<CreateContentRequest>,
+    ) -> ServiceResult<Content> {
+        self.create_content(request.into_inner()).await
+    }
+
+    async fn update_content(
+        &self,
+        request: Request<UpdateContentRequest>,
+    ) -> ServiceResult<Content> {
+        self.update_content(request.into_inner()).await
+    }
+
+    async fn delete_content(
+        &self,
+        request: Request<DeleteContentRequest>,
+    ) -> ServiceResult<DeleteResponse> {
+        self.delete_content(request.into_inner()).await
+    }
+
+    async fn get_content(&self, request: Request<GetContentRequest>) -> ServiceResult<Content> {
+        self.get_content(request.into_inner()).await
+    }
+
+    async fn list_contents(
+        &self,
+        request: Request<ListContentsRequest>,
+    ) -> ServiceResult<ListContentsResponse> {
+        self.list_contents(request.into_inner()).await
+    }
+
+    async fn create_publisher(
+        &self,
+        request: Request<CreatePublisherRequest>,
+    ) -> ServiceResult<Publisher> {
+        self.create_publisher(request.into_inner()).await
+    }
+
+    async fn update_publisher(
+        &self,
+        request: Request<UpdatePublisherRequest>,
+    ) -> ServiceResult<Publisher> {
+        self.update_publisher(request.into_inner()).await
+    }
+
+    async fn delete_publisher(
+        &self,
+        request: Request<DeletePublisherRequest>,
+    ) -> ServiceResult<DeleteResponse> {
+        self.delete_publisher(request.into_inner()).await
+    }
+
+    async fn get_publisher(
+        &self,
+        request: Request<GetPublisherRequest>,
+    ) -> ServiceResult<Publisher> {
+        self.get_publisher(request.into_inner()).await
+    }
+
+    async fn list_publishers(
+        &self,
+        request: Request<ListPublishersRequest>,
+    ) -> ServiceResult<ListPublishersResponse> {
+        self.list_publishers(request.into_inner()).await
+    }
+}
+
+impl Deref for MetadataService {
+    type Target = MetadataServiceInner;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
+    }
 }
 
 impl MetadataService {
-    pub fn new(config: AppConfig) -> Self {
-        Self { config }
+    pub async fn new(config: AppConfig) -> Self {
+        let pool = PgPool::connect(&config.db_url)
+            .await
+            .expect("Failed to connect to db");
+        let contents = Cache::new(&config.cache);
+        Self {
+            inner: Arc::new(MetadataServiceInner {
+                config,
+                pool,
+                contents,
+            }),
+        }
     }
 
     pub fn into_server(self) -> MetadataServer<Self> {
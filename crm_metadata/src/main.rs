@@ -16,7 +16,7 @@ async fn main() -> anyhow::Result<()> {
     let addr = format!("[::1]:{}", config.server.port)
         .parse()
         .expect("Failed to parse address ()");
-    let svc = MetadataService::new(config).into_server();
+    let svc = MetadataService::new(config).await.into_server();
     info!("gRPC server listening on {}", addr);
 
     tonic::transport::Server::builder()
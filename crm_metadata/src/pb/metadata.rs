@@ -42,6 +42,171 @@ pub struct MaterializeRequest {
     #[prost(uint32, tag = "1")]
     pub id: u32,
 }
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BatchMaterializeRequest {
+    #[prost(uint32, repeated, tag = "1")]
+    pub ids: ::prost::alloc::vec::Vec<u32>,
+    /// paths of Content fields to return, e.g. "name", "url", all fields if empty.
+    /// id is always returned
+    #[prost(message, optional, tag = "2")]
+    pub field_mask: ::core::option::Option<::prost_types::FieldMask>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BatchMaterializeResponse {
+    /// in the order of ids, without duplicates
+    #[prost(message, repeated, tag = "1")]
+    pub contents: ::prost::alloc::vec::Vec<Content>,
+    #[prost(uint32, repeated, tag = "2")]
+    pub missing: ::prost::alloc::vec::Vec<u32>,
+}
+/// fields of a content set by the content team
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ContentInput {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    #[prost(string, tag = "2")]
+    pub description: ::prost::alloc::string::String,
+    /// existing publishers, in display order
+    #[prost(uint32, repeated, tag = "3")]
+    pub publisher_ids: ::prost::alloc::vec::Vec<u32>,
+    /// http(s) url of the content
+    #[prost(string, tag = "4")]
+    pub url: ::prost::alloc::string::String,
+    /// http(s) url of the cover, optional
+    #[prost(string, tag = "5")]
+    pub image: ::prost::alloc::string::String,
+    #[prost(enumeration = "ContentType", tag = "6")]
+    pub content_type: i32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CreateContentRequest {
+    #[prost(message, optional, tag = "1")]
+    pub content: ::core::option::Option<ContentInput>,
+}
+/// all the fields of the content are replaced
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdateContentRequest {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(message, optional, tag = "2")]
+    pub content: ::core::option::Option<ContentInput>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetContentRequest {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeleteContentRequest {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ContentFilter {
+    /// any type if empty
+    #[prost(enumeration = "ContentType", repeated, tag = "1")]
+    pub content_types: ::prost::alloc::vec::Vec<i32>,
+    /// contents of any of the publishers, any publisher if empty
+    #[prost(uint32, repeated, tag = "2")]
+    pub publisher_ids: ::prost::alloc::vec::Vec<u32>,
+    #[prost(message, optional, tag = "3")]
+    pub created_after: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "4")]
+    pub created_before: ::core::option::Option<::prost_types::Timestamp>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListContentsRequest {
+    #[prost(message, optional, tag = "1")]
+    pub filter: ::core::option::Option<ContentFilter>,
+    /// 100 if 0
+    #[prost(uint32, tag = "2")]
+    pub page_size: u32,
+    /// next_page_token of the previous page
+    #[prost(string, tag = "3")]
+    pub page_token: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListContentsResponse {
+    /// newest first
+    #[prost(message, repeated, tag = "1")]
+    pub contents: ::prost::alloc::vec::Vec<Content>,
+    /// empty for the last page
+    #[prost(string, tag = "2")]
+    pub next_page_token: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PublisherInput {
+    #[prost(string, tag = "1")]
+    pub name: ::prost::alloc::string::String,
+    /// http(s) url of the avatar, optional
+    #[prost(string, tag = "2")]
+    pub avatar: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CreatePublisherRequest {
+    #[prost(message, optional, tag = "1")]
+    pub publisher: ::core::option::Option<PublisherInput>,
+}
+/// all the fields of the publisher are replaced
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdatePublisherRequest {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+    #[prost(message, optional, tag = "2")]
+    pub publisher: ::core::option::Option<PublisherInput>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetPublisherRequest {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+}
+/// the publisher is removed from its contents
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeletePublisherRequest {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListPublishersRequest {
+    /// 100 if 0
+    #[prost(uint32, tag = "1")]
+    pub page_size: u32,
+    /// next_page_token of the previous page
+    #[prost(string, tag = "2")]
+    pub page_token: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListPublishersResponse {
+    /// newest first
+    #[prost(message, repeated, tag = "1")]
+    pub publishers: ::prost::alloc::vec::Vec<Publisher>,
+    /// empty for the last page
+    #[prost(string, tag = "2")]
+    pub next_page_token: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct DeleteResponse {
+    #[prost(uint32, tag = "1")]
+    pub id: u32,
+}
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
 #[repr(i32)]
 pub enum ContentType {
@@ -181,6 +346,196 @@ pub mod metadata_client {
                 .insert(GrpcMethod::new("metadata.Metadata", "Materialize"));
             self.inner.streaming(req, path, codec).await
         }
+        pub async fn batch_materialize(
+            &mut self,
+            request: impl tonic::IntoRequest<super::BatchMaterializeRequest>,
+        ) -> std::result::Result<tonic::Response<super::BatchMaterializeResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/BatchMaterialize");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "BatchMaterialize"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn create_content(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CreateContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::Content>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/CreateContent");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "CreateContent"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn update_content(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdateContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::Content>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/UpdateContent");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "UpdateContent"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn delete_content(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DeleteContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::DeleteResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/DeleteContent");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "DeleteContent"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_content(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::Content>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/GetContent");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "GetContent"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn list_contents(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListContentsRequest>,
+        ) -> std::result::Result<tonic::Response<super::ListContentsResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/ListContents");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "ListContents"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn create_publisher(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CreatePublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::Publisher>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/CreatePublisher");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "CreatePublisher"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn update_publisher(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdatePublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::Publisher>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/UpdatePublisher");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "UpdatePublisher"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn delete_publisher(
+            &mut self,
+            request: impl tonic::IntoRequest<super::DeletePublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::DeleteResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/DeletePublisher");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "DeletePublisher"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn get_publisher(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetPublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::Publisher>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/GetPublisher");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "GetPublisher"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn list_publishers(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListPublishersRequest>,
+        ) -> std::result::Result<tonic::Response<super::ListPublishersResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/metadata.Metadata/ListPublishers");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("metadata.Metadata", "ListPublishers"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -199,6 +554,50 @@ pub mod metadata_server {
             &self,
             request: tonic::Request<tonic::Streaming<super::MaterializeRequest>>,
         ) -> std::result::Result<tonic::Response<Self::MaterializeStream>, tonic::Status>;
+        async fn batch_materialize(
+            &self,
+            request: tonic::Request<super::BatchMaterializeRequest>,
+        ) -> std::result::Result<tonic::Response<super::BatchMaterializeResponse>, tonic::Status>;
+        async fn create_content(
+            &self,
+            request: tonic::Request<super::CreateContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::Content>, tonic::Status>;
+        async fn update_content(
+            &self,
+            request: tonic::Request<super::UpdateContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::Content>, tonic::Status>;
+        async fn delete_content(
+            &self,
+            request: tonic::Request<super::DeleteContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::DeleteResponse>, tonic::Status>;
+        async fn get_content(
+            &self,
+            request: tonic::Request<super::GetContentRequest>,
+        ) -> std::result::Result<tonic::Response<super::Content>, tonic::Status>;
+        async fn list_contents(
+            &self,
+            request: tonic::Request<super::ListContentsRequest>,
+        ) -> std::result::Result<tonic::Response<super::ListContentsResponse>, tonic::Status>;
+        async fn create_publisher(
+            &self,
+            request: tonic::Request<super::CreatePublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::Publisher>, tonic::Status>;
+        async fn update_publisher(
+            &self,
+            request: tonic::Request<super::UpdatePublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::Publisher>, tonic::Status>;
+        async fn delete_publisher(
+            &self,
+            request: tonic::Request<super::DeletePublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::DeleteResponse>, tonic::Status>;
+        async fn get_publisher(
+            &self,
+            request: tonic::Request<super::GetPublisherRequest>,
+        ) -> std::result::Result<tonic::Response<super::Publisher>, tonic::Status>;
+        async fn list_publishers(
+            &self,
+            request: tonic::Request<super::ListPublishersRequest>,
+        ) -> std::result::Result<tonic::Response<super::ListPublishersResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct MetadataServer<T: Metadata> {
@@ -317,6 +716,455 @@ pub mod metadata_server {
                     };
                     Box::pin(fut)
                 }
+                "/metadata.Metadata/BatchMaterialize" => {
+                    #[allow(non_camel_case_types)]
+                    struct BatchMaterializeSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::BatchMaterializeRequest>
+                        for BatchMaterializeSvc<T>
+                    {
+                        type Response = super::BatchMaterializeResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::BatchMaterializeRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::batch_materialize(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = BatchMaterializeSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/CreateContent" => {
+                    #[allow(non_camel_case_types)]
+                    struct CreateContentSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::CreateContentRequest> for CreateContentSvc<T> {
+                        type Response = super::Content;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreateContentRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::create_content(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CreateContentSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/UpdateContent" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdateContentSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::UpdateContentRequest> for UpdateContentSvc<T> {
+                        type Response = super::Content;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdateContentRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::update_content(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UpdateContentSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/DeleteContent" => {
+                    #[allow(non_camel_case_types)]
+                    struct DeleteContentSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::DeleteContentRequest> for DeleteContentSvc<T> {
+                        type Response = super::DeleteResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DeleteContentRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::delete_content(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = DeleteContentSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/GetContent" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetContentSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::GetContentRequest> for GetContentSvc<T> {
+                        type Response = super::Content;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetContentRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as Metadata>::get_content(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetContentSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/ListContents" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListContentsSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::ListContentsRequest> for ListContentsSvc<T> {
+                        type Response = super::ListContentsResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListContentsRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::list_contents(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ListContentsSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/CreatePublisher" => {
+                    #[allow(non_camel_case_types)]
+                    struct CreatePublisherSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::CreatePublisherRequest>
+                        for CreatePublisherSvc<T>
+                    {
+                        type Response = super::Publisher;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CreatePublisherRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::create_publisher(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CreatePublisherSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/UpdatePublisher" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdatePublisherSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::UpdatePublisherRequest>
+                        for UpdatePublisherSvc<T>
+                    {
+                        type Response = super::Publisher;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdatePublisherRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::update_publisher(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UpdatePublisherSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/DeletePublisher" => {
+                    #[allow(non_camel_case_types)]
+                    struct DeletePublisherSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::DeletePublisherRequest>
+                        for DeletePublisherSvc<T>
+                    {
+                        type Response = super::DeleteResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::DeletePublisherRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::delete_publisher(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = DeletePublisherSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/GetPublisher" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetPublisherSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::GetPublisherRequest> for GetPublisherSvc<T> {
+                        type Response = super::Publisher;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetPublisherRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::get_publisher(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetPublisherSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/metadata.Metadata/ListPublishers" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListPublishersSvc<T: Metadata>(pub Arc<T>);
+                    impl<T: Metadata> tonic::server::UnaryService<super::ListPublishersRequest>
+                        for ListPublishersSvc<T>
+                    {
+                        type Response = super::ListPublishersResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListPublishersRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Metadata>::list_publishers(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ListPublishersSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
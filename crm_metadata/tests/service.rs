@@ -1,46 +1,269 @@
 use std::net::SocketAddr;
 use std::time::Duration;
 
-use futures::TryStreamExt;
+use futures::{StreamExt, TryStreamExt};
+use rand::{thread_rng, Rng};
 use tokio::time::sleep;
 use tonic::codegen::tokio_stream;
+use tonic::Code;
 
 use crm_metadata::pb::metadata_client::MetadataClient;
-use crm_metadata::pb::MaterializeRequest;
+use crm_metadata::pb::{
+    ContentFilter, ContentInput, ContentType, CreateContentRequest, CreatePublisherRequest,
+    DeleteContentRequest, DeletePublisherRequest, GetContentRequest, ListContentsRequest,
+    MaterializeRequest, Publisher, PublisherInput, UpdateContentRequest,
+};
 use crm_metadata::{AppConfig, MetadataService};
 
 #[tokio::test]
 async fn test_metadata() -> anyhow::Result<()> {
-    let addr = start_server().await?;
+    let (addr, svc) = start_server().await?;
+    let contents = svc.insert_fake_contents(2).await?;
     let mut client = MetadataClient::connect(format!("http://{}", addr)).await?;
 
     let stream = tokio_stream::iter(vec![
-        MaterializeRequest { id: 1 },
-        MaterializeRequest { id: 2 },
+        MaterializeRequest { id: contents[0].id },
+        MaterializeRequest { id: contents[1].id },
     ]);
 
     // let req = Request::new(stream);
     let res = client.materialize(stream).await?.into_inner();
     let res = res.try_collect::<Vec<_>>().await?;
 
+    assert_eq!(contents, res);
+    Ok(())
+}
+
+#[tokio::test]
+async fn unknown_content_should_be_not_found() -> anyhow::Result<()> {
+    let (addr, svc) = start_server().await?;
+    let contents = svc.insert_fake_contents(1).await?;
+    let mut client = MetadataClient::connect(format!("http://{}", addr)).await?;
+
+    let stream = tokio_stream::iter(vec![
+        MaterializeRequest { id: u32::MAX },
+        MaterializeRequest { id: contents[0].id },
+    ]);
+    let res = client
+        .materialize(stream)
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+
+    // known contents are still returned
     assert_eq!(2, res.len());
+    assert_eq!(&contents[0], res[0].as_ref().unwrap());
+    assert_eq!(Code::NotFound, res[1].as_ref().unwrap_err().code());
+    Ok(())
+}
+
+#[tokio::test]
+async fn content_crud_should_work() -> anyhow::Result<()> {
+    let (addr, _) = start_server().await?;
+    let mut client = MetadataClient::connect(format!("http://{}", addr)).await?;
+    let alice = create_publisher(&mut client, "Alice").await?;
+    let bob = create_publisher(&mut client, "Bob").await?;
+
+    let mut input = ContentInput {
+        name: "The Matrix".to_string(),
+        publisher_ids: vec![bob.id, alice.id, bob.id],
+        url: "https://acme.org/movies/matrix".to_string(),
+        content_type: ContentType::Movie as i32,
+        ..Default::default()
+    };
+    let req = CreateContentRequest {
+        content: Some(input.clone()),
+    };
+    let content = client.create_content(req).await?.into_inner();
+    assert_eq!("The Matrix", content.name);
+    assert_eq!(vec![bob.clone(), alice.clone()], content.publishers);
+
+    let req = GetContentRequest { id: content.id };
+    assert_eq!(content, client.get_content(req).await?.into_inner());
+
+    input.publisher_ids = vec![alice.id];
+    input.content_type = ContentType::Anime as i32;
+    let req = UpdateContentRequest {
+        id: content.id,
+        content: Some(input),
+    };
+    let updated = client.update_content(req).await?.into_inner();
+    assert_eq!(ContentType::Anime as i32, updated.content_type);
+    assert_eq!(vec![alice.clone()], updated.publishers);
+
+    // contents are kept when their publisher is deleted
+    let req = DeletePublisherRequest { id: alice.id };
+    client.delete_publisher(req).await?;
+    let req = GetContentRequest { id: content.id };
+    assert!(client
+        .get_content(req)
+        .await?
+        .into_inner()
+        .publishers
+        .is_empty());
+
+    let req = DeleteContentRequest { id: content.id };
+    assert_eq!(
+        content.id,
+        client.delete_content(req).await?.into_inner().id
+    );
+    let req = GetContentRequest { id: content.id };
+    let status = client.get_content(req).await.unwrap_err();
+    assert_eq!(Code::NotFound, status.code());
     Ok(())
 }
 
-async fn start_server() -> anyhow::Result<SocketAddr> {
+#[tokio::test]
+async fn invalid_content_should_be_rejected() -> anyhow::Result<()> {
+    let (addr, _) = start_server().await?;
+    let mut client = MetadataClient::connect(format!("http://{}", addr)).await?;
+    let valid = ContentInput {
+        name: "Spirited Away".to_string(),
+        url: "https://acme.org/movies/spirited-away".to_string(),
+        ..Default::default()
+    };
+    let invalid = vec![
+        ContentInput {
+            url: "acme.org/movies/1".to_string(),
+            ..valid.clone()
+        },
+        ContentInput {
+            image: "ftp://acme.org/1.png".to_string(),
+            ..valid.clone()
+        },
+        ContentInput {
+            content_type: 42,
+            ..valid.clone()
+        },
+        ContentInput {
+            name: " ".to_string(),
+            ..valid.clone()
+        },
+        ContentInput {
+            publisher_ids: vec![i32::MAX as u32],
+            ..valid.clone()
+        },
+    ];
+    for input in invalid {
+        let req = CreateContentRequest {
+            content: Some(input),
+        };
+        let status = client.create_content(req).await.unwrap_err();
+        assert_eq!(Code::InvalidArgument, status.code());
+    }
+
+    let req = UpdateContentRequest {
+        id: u32::MAX,
+        content: Some(valid),
+    };
+    let status = client.update_content(req).await.unwrap_err();
+    assert_eq!(Code::NotFound, status.code());
+    Ok(())
+}
+
+#[tokio::test]
+async fn list_contents_should_filter_and_paginate() -> anyhow::Result<()> {
+    let (addr, _) = start_server().await?;
+    let mut client = MetadataClient::connect(format!("http://{}", addr)).await?;
+    let publisher = create_publisher(&mut client, "Studio").await?;
+
+    let mut ids = Vec::new();
+    for (i, content_type) in [ContentType::Anime, ContentType::Movie, ContentType::Anime]
+        .into_iter()
+        .enumerate()
+    {
+        let req = CreateContentRequest {
+            content: Some(ContentInput {
+                name: format!("Content {}", i),
+                publisher_ids: vec![publisher.id],
+                url: format!("https://acme.org/contents/{}", i),
+                content_type: content_type as i32,
+                ..Default::default()
+            }),
+        };
+        ids.push(client.create_content(req).await?.into_inner().id);
+    }
+
+    let filter = ContentFilter {
+        publisher_ids: vec![publisher.id],
+        ..Default::default()
+    };
+    let req = ListContentsRequest {
+        filter: Some(filter.clone()),
+        page_size: 2,
+        ..Default::default()
+    };
+    let page = client.list_contents(req).await?.into_inner();
+    assert_eq!(
+        vec![ids[2], ids[1]],
+        page.contents.iter().map(|c| c.id).collect::<Vec<_>>()
+    );
+    assert!(!page.next_page_token.is_empty());
+
+    let req = ListContentsRequest {
+        filter: Some(filter.clone()),
+        page_size: 2,
+        page_token: page.next_page_token,
+    };
+    let page = client.list_contents(req).await?.into_inner();
+    assert_eq!(
+        vec![ids[0]],
+        page.contents.iter().map(|c| c.id).collect::<Vec<_>>()
+    );
+    assert!(page.next_page_token.is_empty());
+
+    let req = ListContentsRequest {
+        filter: Some(ContentFilter {
+            content_types: vec![ContentType::Anime as i32],
+            ..filter
+        }),
+        ..Default::default()
+    };
+    let page = client.list_contents(req).await?.into_inner();
+    assert_eq!(
+        vec![ids[2], ids[0]],
+        page.contents.iter().map(|c| c.id).collect::<Vec<_>>()
+    );
+
+    let req = ListContentsRequest {
+        page_token: "not-a-token".to_string(),
+        ..Default::default()
+    };
+    let status = client.list_contents(req).await.unwrap_err();
+    assert_eq!(Code::InvalidArgument, status.code());
+    Ok(())
+}
+
+async fn create_publisher(
+    client: &mut MetadataClient<tonic::transport::Channel>,
+    name: &str,
+) -> anyhow::Result<Publisher> {
+    let req = CreatePublisherRequest {
+        publisher: Some(PublisherInput {
+            name: name.to_string(),
+            avatar: format!("https://acme.org/avatars/{}.png", name),
+        }),
+    };
+    Ok(client.create_publisher(req).await?.into_inner())
+}
+
+async fn start_server() -> anyhow::Result<(SocketAddr, MetadataService)> {
     let config = AppConfig::load()?;
-    let addr = format!("[::1]:{}", config.server.port).parse()?;
+    let port = thread_rng().gen_range(51000..65500);
+    let addr = format!("[::1]:{}", port).parse()?;
 
-    let svc = MetadataService::new(config);
+    let svc = MetadataService::new(config).await;
+    let server = svc.clone();
     tokio::spawn(async move {
         tonic::transport::Server::builder()
-            .add_service(svc.into_server())
+            .add_service(server.into_server())
             .serve(addr)
             .await
             .unwrap();
     });
 
-    sleep(Duration::from_micros(1)).await;
+    sleep(Duration::from_millis(10)).await;
 
-    Ok(addr)
+    Ok((addr, svc))
 }
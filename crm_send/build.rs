@@ -1,17 +1,33 @@
 use std::fs;
 
+use proto_builder_trait::tonic::BuilderAttributes;
+
 fn main() -> anyhow::Result<()> {
     fs::create_dir_all("src/pb")?;
 
     let builder = tonic_build::configure();
 
-    builder.out_dir("src/pb").compile(
-        &[
-            "../protos/notification/messages.proto",
-            "../protos/notification/rpc.proto",
-        ],
-        &["../protos"],
-    )?;
+    builder
+        .out_dir("src/pb")
+        .with_serde(
+            &[
+                "EmailMessage",
+                "Attachment",
+                "Attachment.source",
+                "SmsMessage",
+                "InAppMessage",
+            ],
+            true,
+            true,
+            None,
+        )
+        .compile(
+            &[
+                "../protos/notification/messages.proto",
+                "../protos/notification/rpc.proto",
+            ],
+            &["../protos"],
+        )?;
 
     Ok(())
 }
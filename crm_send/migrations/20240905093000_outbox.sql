@@ -0,0 +1,20 @@
+-- Add migration script here
+CREATE TYPE outbox_status AS ENUM ('pending', 'sending', 'sent', 'rejected', 'dead');
+
+-- SendRequests are persisted here before they are acknowledged
+CREATE TABLE outbox(
+  id varchar(64) NOT NULL PRIMARY KEY,
+  channel varchar(16) NOT NULL,
+  -- protobuf encoded SendRequest
+  payload bytea NOT NULL,
+  status outbox_status NOT NULL DEFAULT 'pending',
+  attempts int NOT NULL DEFAULT 0,
+  -- when a pending message is retried, or when the lease of a sending message expires
+  next_attempt_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  last_error text,
+  provider_message_id text,
+  created_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  updated_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX outbox_next_attempt_at_idx ON outbox(next_attempt_at) WHERE status IN ('pending', 'sending');
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- protobuf encoded SendResponse of the first attempt, replayed for duplicate requests
+ALTER TABLE outbox ADD COLUMN response bytea;
@@ -0,0 +1,6 @@
+-- Add migration script here
+-- states reported by provider callbacks
+ALTER TYPE outbox_status ADD VALUE 'delivered';
+ALTER TYPE outbox_status ADD VALUE 'bounced';
+
+CREATE INDEX outbox_created_at_idx ON outbox(created_at);
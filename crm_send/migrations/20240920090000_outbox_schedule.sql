@@ -0,0 +1,5 @@
+-- Add migration script here
+-- scheduled messages are held until next_attempt_at
+ALTER TYPE outbox_status ADD VALUE 'scheduled';
+ALTER TYPE outbox_status ADD VALUE 'expired';
+ALTER TYPE outbox_status ADD VALUE 'cancelled';
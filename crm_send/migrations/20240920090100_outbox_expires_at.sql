@@ -0,0 +1,6 @@
+-- Add migration script here
+-- a message which isn't sent before expires_at is dropped
+ALTER TABLE outbox ADD COLUMN expires_at timestamptz;
+
+DROP INDEX outbox_next_attempt_at_idx;
+CREATE INDEX outbox_next_attempt_at_idx ON outbox(next_attempt_at) WHERE status IN ('pending', 'sending', 'scheduled');
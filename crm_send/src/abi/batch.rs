@@ -0,0 +1,200 @@
+use std::collections::HashSet;
+
+use anyhow::anyhow;
+
+use crate::abi::Sender;
+use crate::pb::send_request::Msg;
+use crate::pb::send_request::Msg::{Email, InApp, Sms};
+use crate::pb::{SendRequest, SendResponse};
+use crate::provider::{Delivery, Rejected};
+use crate::NotificationService;
+
+/// consecutive requests merged into one provider call
+struct Batch {
+    // the message without recipients
+    key: Option<Msg>,
+    recipients: usize,
+    reqs: Vec<SendRequest>,
+}
+
+impl NotificationService {
+    /// send requests which arrived together, the responses are in the order of requests
+    pub(crate) async fn send_batch(&self, reqs: Vec<SendRequest>) -> Vec<SendResponse> {
+        let mut responses = Vec::with_capacity(reqs.len());
+        for batch in self.split(reqs) {
+            if batch.reqs.len() == 1 {
+                let req = batch.reqs.into_iter().next().unwrap();
+                responses.push(self.send_one(req).await);
+            } else {
+                responses.extend(self.send_merged(batch.reqs).await);
+            }
+        }
+        responses
+    }
+
+    // group consecutive requests which only differ in recipients, up to the batch size
+    // of the channel
+    fn split(&self, reqs: Vec<SendRequest>) -> Vec<Batch> {
+        let mut batches: Vec<Batch> = Vec::new();
+        let mut ids = HashSet::new();
+        for req in reqs {
+            let limit = req.msg.as_ref().map(|m| self.batch_size(m)).unwrap_or(0);
+            let key = match limit > 1 {
+                true => req.msg.as_ref().map(without_recipients),
+                false => None,
+            };
+            let recipients = req.msg.as_ref().map(recipients).unwrap_or(0);
+            if let Some(batch) = batches.last_mut() {
+                if key.is_some()
+                    && batch.key == key
+                    && batch.recipients + recipients <= limit
+                    && !ids.contains(&req.id)
+                {
+                    ids.insert(req.id.clone());
+                    batch.recipients += recipients;
+                    batch.reqs.push(req);
+                    continue;
+                }
+            }
+            ids = HashSet::from([req.id.clone()]);
+            batches.push(Batch {
+                key,
+                recipients,
+                reqs: vec![req],
+            });
+        }
+        batches
+    }
+
+    // max recipients of a provider call, emails with cc or bcc are sent on their own
+    fn batch_size(&self, msg: &Msg) -> usize {
+        let config = &self.config.providers.batch;
+        match msg {
+            Email(email) if email.cc.is_empty() && email.bcc.is_empty() => config.email as usize,
+            Sms(_) => config.sms as usize,
+            _ => 0,
+        }
+    }
+
+    // persist the requests, then make the first attempt of the accepted ones at once
+    async fn send_merged(&self, reqs: Vec<SendRequest>) -> Vec<SendResponse> {
+        let mut accepted = Vec::with_capacity(reqs.len());
+        for req in &reqs {
+            accepted.push(self.accept(req).await);
+        }
+        let msgs = accepted.iter().filter_map(|a| a.as_ref().ok().copied());
+        let res = match merge(msgs) {
+            Some(msg) => msg.send(self).await,
+            None => Err(anyhow!("No message is accepted")),
+        };
+        // one invalid recipient rejects the whole call, so each message is sent
+        // on its own to find out which one is rejected
+        let rejected = res.as_ref().is_err_and(|e| e.is::<Rejected>());
+
+        let mut responses = Vec::with_capacity(reqs.len());
+        for (req, accepted) in reqs.iter().zip(accepted) {
+            let resp = match accepted {
+                Ok(msg) if rejected => self.finish(req.id.clone(), msg.send(self).await).await,
+                Ok(_) => self.finish(req.id.clone(), share(&res)).await,
+                Err(resp) => resp,
+            };
+            responses.push(resp);
+        }
+        responses
+    }
+}
+
+fn without_recipients(msg: &Msg) -> Msg {
+    let mut msg = msg.clone();
+    match &mut msg {
+        Email(email) => email.recipients.clear(),
+        Sms(sms) => sms.recipients.clear(),
+        InApp(_) => {}
+    }
+    msg
+}
+
+fn recipients(msg: &Msg) -> usize {
+    match msg {
+        Email(email) => email.recipients.len(),
+        Sms(sms) => sms.recipients.len(),
+        InApp(_) => 1,
+    }
+}
+
+// a merged email is sent to all recipients as bcc, so they don't see each other. Its
+// To header is the undisclosed-recipients group
+fn merge<'a>(mut msgs: impl Iterator<Item = &'a Msg>) -> Option<Msg> {
+    let mut merged = msgs.next()?.clone();
+    match &mut merged {
+        Email(email) => {
+            email.bcc = std::mem::take(&mut email.recipients);
+            for msg in msgs {
+                if let Email(m) = msg {
+                    email.bcc.extend(m.recipients.iter().cloned());
+                }
+            }
+        }
+        Sms(sms) => {
+            for msg in msgs {
+                if let Sms(m) = msg {
+                    sms.recipients.extend(m.recipients.iter().cloned());
+                }
+            }
+        }
+        InApp(_) => {}
+    }
+    Some(merged)
+}
+
+// every merged message gets the result of the provider call, a failure is retried
+// for each message on its own
+fn share(res: &anyhow::Result<Delivery>) -> anyhow::Result<Delivery> {
+    match res {
+        Ok(delivery) => Ok(delivery.clone()),
+        Err(e) => Err(anyhow!("{:#}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::pb::{EmailMessage, SmsMessage};
+
+    fn new_sms(recipients: &[&str], body: &str) -> Msg {
+        SmsMessage {
+            sender: "10086".to_string(),
+            recipients: recipients.iter().map(|r| r.to_string()).collect(),
+            body: body.to_string(),
+        }
+        .into()
+    }
+
+    #[test]
+    fn merge_should_collect_recipients() {
+        let msgs = [new_sms(&["1", "2"], "Hi"), new_sms(&["3"], "Hi")];
+        assert_eq!(Some(new_sms(&["1", "2", "3"], "Hi")), merge(msgs.iter()));
+        assert_eq!(without_recipients(&msgs[0]), without_recipients(&msgs[1]));
+        assert_ne!(
+            without_recipients(&msgs[0]),
+            without_recipients(&new_sms(&["1"], "Hello"))
+        );
+
+        let email = |to: &str| -> Msg {
+            EmailMessage {
+                subject: "Welcome".to_string(),
+                sender: "admin@crm.org".to_string(),
+                recipients: vec![to.to_string()],
+                body: "Hi".to_string(),
+                ..Default::default()
+            }
+            .into()
+        };
+        let msgs = [email("tyr@acme.org"), email("alice@acme.org")];
+        let Some(Email(merged)) = merge(msgs.iter()) else {
+            panic!("expect an email");
+        };
+        assert!(merged.recipients.is_empty());
+        assert_eq!(vec!["tyr@acme.org", "alice@acme.org"], merged.bcc);
+    }
+}
@@ -1,25 +1,16 @@
-use tracing::warn;
-
-use crate::abi::{to_ts, Sender};
+use crate::abi::Sender;
 use crate::pb::send_request::Msg;
-use crate::pb::{EmailMessage, SendResponse};
+use crate::pb::EmailMessage;
+use crate::provider::{Delivery, Rejected};
 use crate::NotificationService;
 
 impl Sender for EmailMessage {
-    async fn send(
-        self,
-        msg_id: String,
-        by: &NotificationService,
-    ) -> Result<SendResponse, tonic::Status> {
-        let snd = by.sender_svc.clone();
-        if let Err(e) = snd.send(self.into()).await {
-            warn!("Failed to send email: {}", e);
-        };
-
-        Ok(SendResponse {
-            id: msg_id,
-            created_at: Some(to_ts()),
-        })
+    async fn send(&self, by: &NotificationService) -> anyhow::Result<Delivery> {
+        // a batched email only has bcc recipients
+        if self.recipients.is_empty() && self.cc.is_empty() && self.bcc.is_empty() {
+            return Err(Rejected("No recipients".to_string()).into());
+        }
+        by.providers.email.send(self).await
     }
 }
 
@@ -34,7 +25,6 @@ impl EmailMessage {
     pub fn new() -> Self {
         use fake::faker::internet::en::SafeEmail;
         use fake::faker::lorem::zh_cn::Sentence;
-        use fake::faker::name::en::Name;
         use fake::Fake;
         use rand::{thread_rng, Rng};
 
@@ -42,9 +32,10 @@ impl EmailMessage {
         let recipients = (1..count).map(|_| SafeEmail().fake()).collect::<Vec<_>>();
         Self {
             subject: Sentence(1..2).fake(),
-            sender: Name().fake(),
+            sender: SafeEmail().fake(),
             recipients,
             body: Sentence(2..6).fake(),
+            ..Default::default()
         }
     }
 }
@@ -4,23 +4,31 @@ use std::sync::Arc;
 use chrono::Utc;
 use futures::{Stream, StreamExt};
 use prost_types::Timestamp;
+use sqlx::PgPool;
 use tokio::sync::mpsc;
 use tonic::codegen::tokio_stream::wrappers::ReceiverStream;
-use tonic::{Response, Status};
-use tracing::{info, warn};
+use tonic::Response;
+use tracing::warn;
 use uuid::Uuid;
 
+use crate::abi::outbox::Enqueued;
 use crate::pb::notification_server::NotificationServer;
 use crate::pb::send_request::Msg;
 use crate::pb::send_request::Msg::{Email, InApp, Sms};
-use crate::pb::{EmailMessage, InAppMessage, SendRequest, SendResponse, SmsMessage};
+use crate::pb::{EmailMessage, InAppMessage, SendRequest, SendResponse, SendStatus, SmsMessage};
+use crate::provider::{Delivery, Providers};
 use crate::{
     AppConfig, NotificationService, NotificationServiceInner, ResponseStream, ServiceResult,
 };
 
+mod batch;
 mod email;
 mod in_app;
+mod outbox;
+mod schedule;
 mod sms;
+mod status;
+pub mod webhook;
 
 const CHANNEL_SIZE: usize = 1024;
 
@@ -31,38 +39,63 @@ impl Deref for NotificationService {
     }
 }
 impl NotificationService {
-    pub fn new(config: AppConfig) -> Self {
-        let sender_svc = dummy_sender();
-        Self {
-            inner: Arc::new(NotificationServiceInner { config, sender_svc }),
-        }
+    pub async fn new(config: AppConfig) -> Self {
+        let providers =
+            Providers::try_new(&config.providers).expect("Failed to create delivery providers");
+        Self::with_providers(config, providers).await
+    }
+
+    /// create the service with custom delivery providers, rate limits of the config
+    /// are applied to them
+    pub async fn with_providers(config: AppConfig, providers: Providers) -> Self {
+        let providers = providers.with_rate_limits(&config.providers.rate_limits);
+        let pool = PgPool::connect(&config.db_url)
+            .await
+            .expect("Failed to connect to db");
+        let svc = Self {
+            inner: Arc::new(NotificationServiceInner {
+                config,
+                pool,
+                providers,
+            }),
+        };
+        svc.spawn_workers();
+        svc
     }
 
     pub fn into_server(self) -> NotificationServer<Self> {
         NotificationServer::new(self)
     }
 
-    pub async fn send<S>(&self, mut stream: S) -> ServiceResult<ResponseStream>
+    pub async fn send<S>(&self, stream: S) -> ServiceResult<ResponseStream>
     where
         S: Stream<Item = Result<SendRequest, tonic::Status>> + Send + 'static + Unpin,
     {
         let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
         let svc = self.clone();
         tokio::spawn(async move {
-            while let Some(Ok(req)) = stream.next().await {
-                let svc_clone = svc.clone();
-                let id = req.id;
-                let resp = match req.msg {
-                    Some(Email(email)) => email.send(id, &svc_clone).await,
-                    Some(Sms(sms)) => sms.send(id, &svc_clone).await,
-                    Some(InApp(in_app)) => in_app.send(id, &svc_clone).await,
-                    None => {
-                        warn!("Empty request");
-                        Err(Status::invalid_argument("Empty request"))
+            // requests which are ready at the same time may be batched
+            let batch = &svc.config.providers.batch;
+            let size = batch.email.max(batch.sms).max(1) as usize;
+            let mut chunks = stream.ready_chunks(size);
+            while let Some(chunk) = chunks.next().await {
+                // the client has gone, e.g. a cancelled campaign, the rest isn't sent
+                if tx.is_closed() {
+                    warn!("Client disconnected, stop sending");
+                    break;
+                }
+                let len = chunk.len();
+                let reqs = chunk.into_iter().map_while(Result::ok).collect::<Vec<_>>();
+                let mut stop = reqs.len() < len;
+                for resp in svc.send_batch(reqs).await {
+                    if let Err(e) = tx.send(Ok(resp)).await {
+                        warn!("Failed to send response, stop sending: {}", e);
+                        stop = true;
+                        break;
                     }
-                };
-                if let Err(e) = tx.send(resp).await {
-                    warn!("Failed to send response: {}", e);
+                }
+                if stop {
+                    break;
                 }
             }
         });
@@ -70,16 +103,63 @@ impl NotificationService {
         let resp = Box::pin(ReceiverStream::new(rx));
         Ok(Response::new(resp))
     }
-}
 
-fn dummy_sender() -> mpsc::Sender<Msg> {
-    let (tx, mut rx) = mpsc::channel(CHANNEL_SIZE * 100);
-    tokio::spawn(async move {
-        while let Some(msg) = rx.recv().await {
-            info!("Send message: {:?}", msg);
+    // persist the request in the outbox, then make the first attempt
+    async fn send_one(&self, req: SendRequest) -> SendResponse {
+        let res = match self.accept(&req).await {
+            Ok(msg) => msg.send(self).await,
+            Err(resp) => return resp,
+        };
+        self.finish(req.id, res).await
+    }
+
+    // persist a valid request, which is sent now if it isn't scheduled. A rejected,
+    // scheduled or duplicate request gets its response
+    async fn accept<'a>(&self, req: &'a SendRequest) -> Result<&'a Msg, SendResponse> {
+        let Some(msg) = req.msg.as_ref() else {
+            return Err(SendResponse::rejected(req.id.clone(), "Empty request"));
+        };
+        if req.id.is_empty() {
+            return Err(SendResponse::rejected(req.id.clone(), "Empty id"));
         }
-    });
-    tx
+        let schedule = req
+            .schedule()
+            .map_err(|e| SendResponse::rejected(req.id.clone(), e.to_string()))?;
+        if schedule.is_expired() {
+            let e = "Message expired".to_string();
+            return Err(SendResponse::with_error(
+                req.id.clone(),
+                SendStatus::Expired,
+                e,
+            ));
+        }
+        match self.enqueue(req, msg.channel(), &schedule).await {
+            Ok(Enqueued::New) => Ok(msg),
+            Ok(Enqueued::Scheduled) => {
+                let resp =
+                    SendResponse::with_error(req.id.clone(), SendStatus::Scheduled, String::new());
+                if let Err(e) = self.save_response(&resp).await {
+                    warn!("Failed to save response of message {}: {:#}", resp.id, e);
+                }
+                Err(resp)
+            }
+            Ok(Enqueued::Duplicate(resp)) => Err(resp),
+            Err(e) => {
+                warn!("Failed to persist message {}: {:#}", req.id, e);
+                let e = format!("Failed to persist message: {:#}", e);
+                Err(SendResponse::with_error(
+                    req.id.clone(),
+                    SendStatus::Failed,
+                    e,
+                ))
+            }
+        }
+    }
+
+    // record the result of the first attempt
+    async fn finish(&self, id: String, res: anyhow::Result<Delivery>) -> SendResponse {
+        self.complete(&id, 1, res).await
+    }
 }
 
 pub fn to_ts() -> Timestamp {
@@ -91,11 +171,46 @@ pub fn to_ts() -> Timestamp {
 }
 
 trait Sender {
-    async fn send(
-        self,
-        msg_id: String,
-        by: &NotificationService,
-    ) -> Result<SendResponse, tonic::Status>;
+    /// validate the message and deliver it through the provider of its channel
+    async fn send(&self, by: &NotificationService) -> anyhow::Result<Delivery>;
+}
+
+impl Sender for Msg {
+    async fn send(&self, by: &NotificationService) -> anyhow::Result<Delivery> {
+        match self {
+            Email(email) => email.send(by).await,
+            Sms(sms) => sms.send(by).await,
+            InApp(in_app) => in_app.send(by).await,
+        }
+    }
+}
+
+impl Msg {
+    fn channel(&self) -> &'static str {
+        match self {
+            Email(_) => "email",
+            Sms(_) => "sms",
+            InApp(_) => "in_app",
+        }
+    }
+}
+
+impl SendResponse {
+    fn rejected(id: String, reason: impl Into<String>) -> Self {
+        let reason = reason.into();
+        warn!("Message {} is rejected: {}", id, reason);
+        Self::with_error(id, SendStatus::Rejected, reason)
+    }
+
+    fn with_error(id: String, status: SendStatus, error: String) -> Self {
+        Self {
+            id,
+            created_at: Some(to_ts()),
+            status: status as i32,
+            error,
+            provider_message_id: String::new(),
+        }
+    }
 }
 
 impl SendRequest {
@@ -110,10 +225,12 @@ impl SendRequest {
             sender: sender.into(),
             recipients: recipients.to_vec(),
             body: body.into(),
+            ..Default::default()
         };
         Self {
             id: Uuid::new_v4().to_string(),
             msg: Some(msg.into()),
+            ..Default::default()
         }
     }
 
@@ -130,6 +247,7 @@ impl SendRequest {
         Self {
             id: Uuid::new_v4().to_string(),
             msg: Some(msg.into()),
+            ..Default::default()
         }
     }
 
@@ -146,6 +264,7 @@ impl SendRequest {
         Self {
             id: Uuid::new_v4().to_string(),
             msg: Some(msg.into()),
+            ..Default::default()
         }
     }
 }
@@ -156,25 +275,29 @@ mod tests {
     use tonic::codegen::tokio_stream;
     use uuid::Uuid;
 
-    use crate::pb::{EmailMessage, InAppMessage, SendRequest, SmsMessage};
+    use crate::abi::outbox::OutboxStatus;
+    use crate::pb::{EmailMessage, InAppMessage, SendRequest, SendStatus, SmsMessage};
     use crate::{AppConfig, NotificationService};
 
     #[tokio::test]
     async fn test_send_message() -> anyhow::Result<()> {
-        let svc = NotificationService::new(AppConfig::load().unwrap());
+        let svc = NotificationService::new(AppConfig::load().unwrap()).await;
         let stream = tokio_stream::iter(
             vec![
                 Ok(SendRequest {
                     id: Uuid::new_v4().to_string(),
                     msg: Some(EmailMessage::new().into()),
+                    ..Default::default()
                 }),
                 Ok(SendRequest {
                     id: Uuid::new_v4().to_string(),
                     msg: Some(SmsMessage::new().into()),
+                    ..Default::default()
                 }),
                 Ok(SendRequest {
                     id: Uuid::new_v4().to_string(),
                     msg: Some(InAppMessage::new().into()),
+                    ..Default::default()
                 }),
             ]
             .into_iter(),
@@ -186,4 +309,74 @@ mod tests {
         }
         Ok(())
     }
+
+    #[tokio::test]
+    async fn send_should_report_status() -> anyhow::Result<()> {
+        let svc = NotificationService::new(AppConfig::load().unwrap()).await;
+        let stream = tokio_stream::iter(vec![
+            Ok(SendRequest::new_email(
+                "Welcome",
+                "admin@crm.org",
+                &["tyr@acme.org".to_string()],
+                "Hi",
+            )),
+            Ok(SendRequest::new_sms("10086", &[], "Hi")),
+            Ok(SendRequest {
+                id: Uuid::new_v4().to_string(),
+                msg: None,
+                ..Default::default()
+            }),
+        ]);
+        let res = svc
+            .send(stream)
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(3, res.len());
+        // the dummy provider only queues messages for logging
+        assert_eq!(SendStatus::Queued as i32, res[0].status);
+        assert!(res[0].error.is_empty());
+        assert_eq!(SendStatus::Rejected as i32, res[1].status);
+        assert_eq!("No recipients", res[1].error);
+        assert_eq!(SendStatus::Rejected as i32, res[2].status);
+        assert_eq!("Empty request", res[2].error);
+
+        // accepted messages are recorded in the outbox
+        let status: Vec<(String, OutboxStatus)> =
+            sqlx::query_as("SELECT id, status FROM outbox WHERE id = ANY($1) ORDER BY status")
+                .bind(res.iter().map(|r| r.id.clone()).collect::<Vec<_>>())
+                .fetch_all(&svc.pool)
+                .await?;
+        assert_eq!(
+            vec![
+                (res[0].id.clone(), OutboxStatus::Sent),
+                (res[1].id.clone(), OutboxStatus::Rejected)
+            ],
+            status
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn send_duplicate_id_should_return_original_response() -> anyhow::Result<()> {
+        let svc = NotificationService::new(AppConfig::load().unwrap()).await;
+        let req = SendRequest::new_in_app("device-1", "Hi", "Hi");
+        let stream = tokio_stream::iter(vec![Ok(req.clone()), Ok(req)]);
+        let res = svc
+            .send(stream)
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>()?;
+
+        assert_eq!(SendStatus::Queued as i32, res[0].status);
+        assert_eq!(res[0], res[1]);
+        Ok(())
+    }
 }
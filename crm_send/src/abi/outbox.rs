@@ -0,0 +1,313 @@
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use prost::Message;
+use prost_types::Timestamp;
+use tokio::time::sleep;
+use tracing::warn;
+
+use crate::abi::schedule::Schedule;
+use crate::abi::Sender;
+use crate::pb::{SendRequest, SendResponse, SendStatus};
+use crate::provider::{Delivery, Rejected};
+use crate::{NotificationService, OutboxConfig};
+
+#[derive(Debug, Clone, Copy, PartialEq, sqlx::Type)]
+#[sqlx(type_name = "outbox_status", rename_all = "lowercase")]
+pub(crate) enum OutboxStatus {
+    // waiting for a retry
+    Pending,
+    // locked by a worker until next_attempt_at
+    Sending,
+    Sent,
+    Rejected,
+    // failed max_attempts times
+    Dead,
+    // reported by provider callbacks
+    Delivered,
+    Bounced,
+    // held until next_attempt_at
+    Scheduled,
+    // not sent before expires_at
+    Expired,
+    Cancelled,
+}
+
+/// a message locked by a worker
+#[derive(Debug, sqlx::FromRow)]
+struct Claimed {
+    id: String,
+    payload: Vec<u8>,
+    attempts: i32,
+    expired: bool,
+}
+
+pub(crate) enum Enqueued {
+    New,
+    // held until send_at, it's delivered by a worker
+    Scheduled,
+    // a message with the same id was sent within the idempotency window
+    Duplicate(SendResponse),
+}
+
+/// result of a delivery attempt
+#[derive(Debug)]
+pub(crate) enum Outcome {
+    Delivered(Delivery),
+    Rejected(String),
+    // failed, a retry is scheduled
+    Retry(String),
+    // failed too many times
+    Dead(String),
+}
+
+impl NotificationService {
+    /// persist a request before it's acknowledged, the message is locked for the caller
+    /// which makes the first attempt, or held until send_at. A finished message sent
+    /// before the idempotency window is replaced, so its id can be reused.
+    pub(crate) async fn enqueue(
+        &self,
+        req: &SendRequest,
+        channel: &str,
+        schedule: &Schedule,
+    ) -> anyhow::Result<Enqueued> {
+        let (status, attempts) = match schedule.send_at {
+            Some(_) => (OutboxStatus::Scheduled, 0),
+            None => (OutboxStatus::Sending, 1),
+        };
+        let ret = sqlx::query(
+            "INSERT INTO outbox(id, channel, payload, status, attempts, next_attempt_at, expires_at) \
+             VALUES ($1, $2, $3, $4, $5, COALESCE($6, now() + make_interval(secs => $7)), $8) \
+             ON CONFLICT (id) DO UPDATE SET \
+               channel = EXCLUDED.channel, payload = EXCLUDED.payload, status = EXCLUDED.status, \
+               attempts = EXCLUDED.attempts, next_attempt_at = EXCLUDED.next_attempt_at, \
+               expires_at = EXCLUDED.expires_at, last_error = NULL, provider_message_id = NULL, \
+               response = NULL, created_at = now(), updated_at = now() \
+             WHERE outbox.created_at < now() - make_interval(secs => $9) \
+               AND outbox.status IN \
+                 ('sent', 'delivered', 'bounced', 'rejected', 'dead', 'expired', 'cancelled')",
+        )
+        .bind(&req.id)
+        .bind(channel)
+        .bind(req.encode_to_vec())
+        .bind(status)
+        .bind(attempts)
+        .bind(schedule.send_at)
+        .bind(to_secs(self.config.outbox.lease))
+        .bind(schedule.expires_at)
+        .bind(to_secs(self.config.outbox.idempotency_window))
+        .execute(&self.pool)
+        .await?;
+        if ret.rows_affected() == 1 {
+            return match status {
+                OutboxStatus::Scheduled => Ok(Enqueued::Scheduled),
+                _ => Ok(Enqueued::New),
+            };
+        }
+
+        let (response, created_at): (Option<Vec<u8>>, DateTime<Utc>) =
+            sqlx::query_as("SELECT response, created_at FROM outbox WHERE id = $1")
+                .bind(&req.id)
+                .fetch_one(&self.pool)
+                .await?;
+        let response = match response {
+            Some(response) => SendResponse::decode(response.as_slice())?,
+            // the first attempt is still in progress
+            None => SendResponse {
+                id: req.id.clone(),
+                created_at: Some(Timestamp {
+                    seconds: created_at.timestamp(),
+                    nanos: created_at.timestamp_subsec_nanos() as i32,
+                }),
+                status: SendStatus::Queued as i32,
+                error: String::new(),
+                provider_message_id: String::new(),
+            },
+        };
+        Ok(Enqueued::Duplicate(response))
+    }
+
+    /// keep the response for duplicate requests unless an attempt recorded one
+    pub(crate) async fn save_response(&self, resp: &SendResponse) -> anyhow::Result<()> {
+        sqlx::query("UPDATE outbox SET response = $2 WHERE id = $1 AND response IS NULL")
+            .bind(&resp.id)
+            .bind(resp.encode_to_vec())
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    /// record the result of an attempt and its response for duplicate requests, a failed
+    /// message is retried with exponential backoff until it fails max_attempts times
+    pub(crate) async fn complete(
+        &self,
+        id: &str,
+        attempts: i32,
+        res: anyhow::Result<Delivery>,
+    ) -> SendResponse {
+        let config = &self.config.outbox;
+        let (outcome, status, next_attempt) = match res {
+            Ok(delivery) => (Outcome::Delivered(delivery), OutboxStatus::Sent, None),
+            Err(e) => match e.downcast_ref::<Rejected>() {
+                Some(Rejected(reason)) => (
+                    Outcome::Rejected(reason.clone()),
+                    OutboxStatus::Rejected,
+                    None,
+                ),
+                None if attempts >= config.max_attempts => {
+                    (Outcome::Dead(format!("{:#}", e)), OutboxStatus::Dead, None)
+                }
+                None => (
+                    Outcome::Retry(format!("{:#}", e)),
+                    OutboxStatus::Pending,
+                    Some(backoff(config, attempts)),
+                ),
+            },
+        };
+
+        if let Outcome::Rejected(e) | Outcome::Retry(e) | Outcome::Dead(e) = &outcome {
+            warn!("Failed to send message {} ({:?}): {}", id, status, e);
+        }
+        let resp = SendResponse::new(id.to_string(), outcome);
+        let error = Some(resp.error.as_str()).filter(|e| !e.is_empty());
+        let provider_message_id =
+            Some(resp.provider_message_id.as_str()).filter(|id| !id.is_empty());
+        // only the attempt holding the lock is recorded. A delivery callback may arrive
+        // before it, and a later attempt claims the message once the lease expired
+        let ret = sqlx::query(
+            "UPDATE outbox SET status = $2, last_error = $3, provider_message_id = $4, \
+             next_attempt_at = now() + make_interval(secs => $5), response = $6, \
+             updated_at = now() WHERE id = $1 AND status = $7 AND attempts = $8",
+        )
+        .bind(id)
+        .bind(status)
+        .bind(error)
+        .bind(provider_message_id)
+        .bind(next_attempt.map(|d| d.as_secs_f64()).unwrap_or_default())
+        .bind(resp.encode_to_vec())
+        .bind(OutboxStatus::Sending)
+        .bind(attempts)
+        .execute(&self.pool)
+        .await;
+        match ret {
+            Ok(ret) if ret.rows_affected() == 0 => {
+                warn!(
+                    "Attempt {} of message {} ({:?}) isn't recorded, it changed after the claim",
+                    attempts, id, status
+                );
+                // a delivery callback may arrive before the first attempt is recorded
+                if let Err(e) = self.save_response(&resp).await {
+                    warn!("Failed to save response of message {}: {:#}", id, e);
+                }
+            }
+            Ok(_) => {}
+            // the message stays locked and is delivered again once the lease expires
+            Err(e) => warn!("Failed to update outbox message {}: {}", id, e),
+        }
+
+        resp
+    }
+
+    pub(crate) fn spawn_workers(&self) {
+        for _ in 0..self.config.outbox.workers {
+            let svc = self.clone();
+            tokio::spawn(async move { svc.run_worker().await });
+        }
+    }
+
+    async fn run_worker(self) {
+        let poll_interval = Duration::from_millis(self.config.outbox.poll_interval);
+        loop {
+            match self.claim().await {
+                Ok(Some(claimed)) => self.retry(claimed).await,
+                Ok(None) => sleep(poll_interval).await,
+                Err(e) => {
+                    warn!("Failed to claim outbox message: {}", e);
+                    sleep(poll_interval).await;
+                }
+            }
+        }
+    }
+
+    // lock a due message, a sending message whose lease expired is claimed again.
+    // scheduled messages are released here once send_at is reached
+    async fn claim(&self) -> anyhow::Result<Option<Claimed>> {
+        let claimed = sqlx::query_as(
+            "UPDATE outbox SET status = $1, attempts = attempts + 1, \
+             next_attempt_at = now() + make_interval(secs => $2), updated_at = now() \
+             WHERE id = (\
+               SELECT id FROM outbox \
+               WHERE status IN ($3, $1, $4) AND next_attempt_at <= now() \
+               ORDER BY next_attempt_at LIMIT 1 FOR UPDATE SKIP LOCKED\
+             ) \
+             RETURNING id, payload, attempts, COALESCE(expires_at <= now(), FALSE) AS expired",
+        )
+        .bind(OutboxStatus::Sending)
+        .bind(to_secs(self.config.outbox.lease))
+        .bind(OutboxStatus::Pending)
+        .bind(OutboxStatus::Scheduled)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(claimed)
+    }
+
+    async fn retry(&self, claimed: Claimed) {
+        if claimed.expired {
+            return self.expire(&claimed.id).await;
+        }
+        let res = match SendRequest::decode(claimed.payload.as_slice()) {
+            Ok(SendRequest { msg: Some(msg), .. }) => msg.send(self).await,
+            Ok(_) => Err(Rejected("Empty request".to_string()).into()),
+            Err(e) => Err(Rejected(format!("Invalid payload: {}", e)).into()),
+        };
+        self.complete(&claimed.id, claimed.attempts, res).await;
+    }
+}
+
+impl SendResponse {
+    pub(crate) fn new(id: String, outcome: Outcome) -> Self {
+        match outcome {
+            Outcome::Delivered(delivery) => Self {
+                id,
+                created_at: Some(super::to_ts()),
+                status: delivery.status as i32,
+                error: String::new(),
+                provider_message_id: delivery.provider_message_id.unwrap_or_default(),
+            },
+            Outcome::Rejected(e) => Self::with_error(id, SendStatus::Rejected, e),
+            // the message is persisted, it'll be delivered by a retry
+            Outcome::Retry(e) => Self::with_error(id, SendStatus::Queued, e),
+            Outcome::Dead(e) => Self::with_error(id, SendStatus::Failed, e),
+        }
+    }
+}
+
+// delay before the next attempt, `attempts` attempts failed so far
+fn backoff(config: &OutboxConfig, attempts: i32) -> Duration {
+    let exp = attempts.saturating_sub(1).clamp(0, 31) as u32;
+    let delay = config.backoff.saturating_mul(1 << exp);
+    Duration::from_millis(delay.min(config.max_backoff))
+}
+
+fn to_secs(millis: u64) -> f64 {
+    millis as f64 / 1000.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_should_double_until_max() {
+        let config = OutboxConfig {
+            backoff: 1000,
+            max_backoff: 5000,
+            ..Default::default()
+        };
+        assert_eq!(Duration::from_millis(1000), backoff(&config, 1));
+        assert_eq!(Duration::from_millis(2000), backoff(&config, 2));
+        assert_eq!(Duration::from_millis(4000), backoff(&config, 3));
+        assert_eq!(Duration::from_millis(5000), backoff(&config, 4));
+        assert_eq!(Duration::from_millis(5000), backoff(&config, 100));
+    }
+}
@@ -0,0 +1,146 @@
+use anyhow::bail;
+use chrono::{DateTime, Utc};
+use prost::Message;
+use prost_types::Timestamp;
+use tonic::{Response, Status};
+use tracing::warn;
+
+use crate::abi::outbox::OutboxStatus;
+use crate::abi::status::{to_datetime, MAX_IDS};
+use crate::pb::{CancelRequest, CancelResponse, SendRequest, SendResponse, SendStatus};
+use crate::{NotificationService, ServiceResult};
+
+/// when a request is delivered
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub(crate) struct Schedule {
+    // set if it's in the future
+    pub send_at: Option<DateTime<Utc>>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl NotificationService {
+    pub async fn cancel(&self, req: CancelRequest) -> ServiceResult<CancelResponse> {
+        if req.ids.len() > MAX_IDS {
+            return Err(Status::invalid_argument(format!(
+                "Too many ids, max is {}",
+                MAX_IDS
+            )));
+        }
+        // a message being sent is locked by a worker, it can't be cancelled
+        let ids: Vec<String> = sqlx::query_scalar(
+            "UPDATE outbox SET status = $2, updated_at = now() \
+             WHERE id = ANY($1) AND status IN ($3, $4) RETURNING id",
+        )
+        .bind(&req.ids)
+        .bind(OutboxStatus::Cancelled)
+        .bind(OutboxStatus::Scheduled)
+        .bind(OutboxStatus::Pending)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        let cancelled = req.ids.into_iter().filter(|id| ids.contains(id)).collect();
+        Ok(Response::new(CancelResponse { cancelled }))
+    }
+
+    /// drop a message claimed after it expired, if it still holds the lock of the claim
+    pub(crate) async fn expire(&self, id: &str) {
+        let e = "Message expired".to_string();
+        let resp = SendResponse::with_error(id.to_string(), SendStatus::Expired, e);
+        let ret = sqlx::query(
+            "UPDATE outbox SET status = $2, last_error = $3, response = $4, updated_at = now() \
+             WHERE id = $1 AND status = $5",
+        )
+        .bind(id)
+        .bind(OutboxStatus::Expired)
+        .bind(&resp.error)
+        .bind(resp.encode_to_vec())
+        .bind(OutboxStatus::Sending)
+        .execute(&self.pool)
+        .await;
+        if let Err(e) = ret {
+            warn!("Failed to expire outbox message {}: {}", id, e);
+        }
+    }
+}
+
+impl Schedule {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at.is_some_and(|t| t <= Utc::now())
+    }
+}
+
+impl SendRequest {
+    /// hold the message until send_at
+    pub fn with_send_at(mut self, send_at: Timestamp) -> Self {
+        self.send_at = Some(send_at);
+        self
+    }
+
+    /// drop the message if it isn't sent before expires_at
+    pub fn with_expires_at(mut self, expires_at: Timestamp) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+
+    pub(crate) fn schedule(&self) -> anyhow::Result<Schedule> {
+        let now = Utc::now();
+        let send_at = self
+            .send_at
+            .map(to_datetime)
+            .transpose()?
+            .filter(|t| *t > now);
+        let expires_at = self.expires_at.map(to_datetime).transpose()?;
+        if let (Some(send_at), Some(expires_at)) = (send_at, expires_at) {
+            if expires_at <= send_at {
+                bail!("Message expires before send_at");
+            }
+        }
+        Ok(Schedule {
+            send_at,
+            expires_at,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Duration;
+
+    use super::*;
+    use crate::abi::to_ts;
+
+    fn ts(t: DateTime<Utc>) -> Timestamp {
+        Timestamp {
+            seconds: t.timestamp(),
+            nanos: 0,
+        }
+    }
+
+    #[test]
+    fn schedule_should_work() -> anyhow::Result<()> {
+        let req = SendRequest::new_in_app("device-1", "Hi", "Hi");
+        assert_eq!(Schedule::default(), req.schedule()?);
+
+        // send_at in the past is ignored
+        let req = req.with_send_at(to_ts());
+        assert_eq!(None, req.schedule()?.send_at);
+
+        let tomorrow = Utc::now() + Duration::days(1);
+        let req = req.with_send_at(ts(tomorrow));
+        let schedule = req.schedule()?;
+        assert_eq!(
+            Some(tomorrow.timestamp()),
+            schedule.send_at.map(|t| t.timestamp())
+        );
+        assert!(!schedule.is_expired());
+
+        let req = req.with_expires_at(ts(tomorrow - Duration::hours(1)));
+        assert!(req.schedule().is_err());
+
+        let req = SendRequest::new_in_app("device-1", "Hi", "Hi")
+            .with_expires_at(ts(Utc::now() - Duration::hours(1)));
+        assert!(req.schedule()?.is_expired());
+        Ok(())
+    }
+}
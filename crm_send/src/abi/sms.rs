@@ -1,25 +1,15 @@
-use tracing::warn;
-
-use crate::abi::{to_ts, Sender};
+use crate::abi::Sender;
 use crate::pb::send_request::Msg;
-use crate::pb::{SendResponse, SmsMessage};
+use crate::pb::SmsMessage;
+use crate::provider::{Delivery, Rejected};
 use crate::NotificationService;
 
 impl Sender for SmsMessage {
-    async fn send(
-        self,
-        msg_id: String,
-        by: &NotificationService,
-    ) -> Result<SendResponse, tonic::Status> {
-        let snd = by.sender_svc.clone();
-        if let Err(e) = snd.send(self.into()).await {
-            warn!("Failed to send email: {}", e);
-        };
-
-        Ok(SendResponse {
-            id: msg_id,
-            created_at: Some(to_ts()),
-        })
+    async fn send(&self, by: &NotificationService) -> anyhow::Result<Delivery> {
+        if self.recipients.is_empty() {
+            return Err(Rejected("No recipients".to_string()).into());
+        }
+        by.providers.sms.send(self).await
     }
 }
 
@@ -0,0 +1,270 @@
+use anyhow::{anyhow, bail};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, TimeZone, Utc};
+use prost_types::Timestamp;
+use sqlx::{Postgres, QueryBuilder};
+use tonic::{Response, Status};
+
+use crate::abi::outbox::OutboxStatus;
+use crate::pb::{
+    GetStatusRequest, GetStatusResponse, ListMessagesRequest, ListMessagesResponse, MessageFilter,
+    MessageInfo, MessageState,
+};
+use crate::{NotificationService, ServiceResult};
+
+pub(crate) const MAX_IDS: usize = 1000;
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 1000;
+const COLUMNS: &str =
+    "id, channel, status, attempts, last_error, provider_message_id, created_at, updated_at";
+
+#[derive(Debug, sqlx::FromRow)]
+struct MessageRow {
+    id: String,
+    channel: String,
+    status: OutboxStatus,
+    attempts: i32,
+    last_error: Option<String>,
+    provider_message_id: Option<String>,
+    created_at: DateTime<Utc>,
+    updated_at: DateTime<Utc>,
+}
+
+impl NotificationService {
+    pub async fn get_status(&self, req: GetStatusRequest) -> ServiceResult<GetStatusResponse> {
+        if req.ids.len() > MAX_IDS {
+            return Err(Status::invalid_argument(format!(
+                "Too many ids, max is {}",
+                MAX_IDS
+            )));
+        }
+        let rows: Vec<MessageRow> = sqlx::query_as(&format!(
+            "SELECT {} FROM outbox WHERE id = ANY($1) ORDER BY created_at DESC, id DESC",
+            COLUMNS
+        ))
+        .bind(&req.ids)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(|e| Status::internal(e.to_string()))?;
+
+        let messages = rows.into_iter().map(MessageInfo::from).collect();
+        Ok(Response::new(GetStatusResponse { messages }))
+    }
+
+    pub async fn list_messages(
+        &self,
+        req: ListMessagesRequest,
+    ) -> ServiceResult<ListMessagesResponse> {
+        let page_size = match req.page_size {
+            0 => DEFAULT_PAGE_SIZE,
+            size => size.min(MAX_PAGE_SIZE),
+        };
+        let mut builder =
+            list_sql(&req, page_size).map_err(|e| Status::invalid_argument(format!("{:#}", e)))?;
+        let mut rows: Vec<MessageRow> = builder
+            .build_query_as()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Status::internal(e.to_string()))?;
+
+        // one more row is fetched to tell whether there is a next page
+        let next_page_token = if rows.len() > page_size as usize {
+            rows.truncate(page_size as usize);
+            rows.last()
+                .map(|row| encode_token(row.created_at, &row.id))
+                .unwrap_or_default()
+        } else {
+            String::new()
+        };
+        let messages = rows.into_iter().map(MessageInfo::from).collect();
+        Ok(Response::new(ListMessagesResponse {
+            messages,
+            next_page_token,
+        }))
+    }
+}
+
+fn list_sql(
+    req: &ListMessagesRequest,
+    page_size: u32,
+) -> anyhow::Result<QueryBuilder<'static, Postgres>> {
+    let mut builder = QueryBuilder::new(format!("SELECT {} FROM outbox WHERE TRUE", COLUMNS));
+    if let Some(filter) = req.filter.as_ref() {
+        push_filter(&mut builder, filter)?;
+    }
+    if !req.page_token.is_empty() {
+        let (created_at, id) = decode_token(&req.page_token)?;
+        builder
+            .push(" AND (created_at, id) < (")
+            .push_bind(created_at)
+            .push(", ")
+            .push_bind(id)
+            .push(")");
+    }
+    builder
+        .push(" ORDER BY created_at DESC, id DESC LIMIT ")
+        .push_bind(page_size as i64 + 1);
+    Ok(builder)
+}
+
+fn push_filter(
+    builder: &mut QueryBuilder<'static, Postgres>,
+    filter: &MessageFilter,
+) -> anyhow::Result<()> {
+    if !filter.channel.is_empty() {
+        builder
+            .push(" AND channel = ")
+            .push_bind(filter.channel.clone());
+    }
+    if !filter.states.is_empty() {
+        let states = filter
+            .states
+            .iter()
+            .map(|&state| state_name(state))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        builder
+            .push(" AND status = ANY(")
+            .push_bind(states)
+            .push("::outbox_status[])");
+    }
+    if let Some(ts) = filter.created_after {
+        builder
+            .push(" AND created_at >= ")
+            .push_bind(to_datetime(ts)?);
+    }
+    if let Some(ts) = filter.created_before {
+        builder
+            .push(" AND created_at < ")
+            .push_bind(to_datetime(ts)?);
+    }
+    Ok(())
+}
+
+fn state_name(state: i32) -> anyhow::Result<&'static str> {
+    let state = MessageState::try_from(state).map_err(|_| anyhow!("Unknown state: {}", state))?;
+    let name = match state {
+        MessageState::Unspecified => bail!("State is unspecified"),
+        MessageState::Pending => "pending",
+        MessageState::Sending => "sending",
+        MessageState::Sent => "sent",
+        MessageState::Rejected => "rejected",
+        MessageState::Dead => "dead",
+        MessageState::Delivered => "delivered",
+        MessageState::Bounced => "bounced",
+        MessageState::Scheduled => "scheduled",
+        MessageState::Expired => "expired",
+        MessageState::Cancelled => "cancelled",
+    };
+    Ok(name)
+}
+
+impl From<OutboxStatus> for MessageState {
+    fn from(status: OutboxStatus) -> Self {
+        match status {
+            OutboxStatus::Pending => MessageState::Pending,
+            OutboxStatus::Sending => MessageState::Sending,
+            OutboxStatus::Sent => MessageState::Sent,
+            OutboxStatus::Rejected => MessageState::Rejected,
+            OutboxStatus::Dead => MessageState::Dead,
+            OutboxStatus::Delivered => MessageState::Delivered,
+            OutboxStatus::Bounced => MessageState::Bounced,
+            OutboxStatus::Scheduled => MessageState::Scheduled,
+            OutboxStatus::Expired => MessageState::Expired,
+            OutboxStatus::Cancelled => MessageState::Cancelled,
+        }
+    }
+}
+
+impl From<MessageRow> for MessageInfo {
+    fn from(row: MessageRow) -> Self {
+        Self {
+            id: row.id,
+            channel: row.channel,
+            state: MessageState::from(row.status) as i32,
+            attempts: row.attempts,
+            last_error: row.last_error.unwrap_or_default(),
+            provider_message_id: row.provider_message_id.unwrap_or_default(),
+            created_at: Some(to_ts(row.created_at)),
+            updated_at: Some(to_ts(row.updated_at)),
+        }
+    }
+}
+
+// position of the last message of a page
+fn encode_token(created_at: DateTime<Utc>, id: &str) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}|{}", created_at.timestamp_micros(), id))
+}
+
+fn decode_token(token: &str) -> anyhow::Result<(DateTime<Utc>, String)> {
+    let invalid = || anyhow!("Invalid page token");
+    let bytes = URL_SAFE_NO_PAD.decode(token).map_err(|_| invalid())?;
+    let token = String::from_utf8(bytes).map_err(|_| invalid())?;
+    let (micros, id) = token.split_once('|').ok_or_else(invalid)?;
+    let micros = micros.parse::<i64>().map_err(|_| invalid())?;
+    let created_at = DateTime::from_timestamp_micros(micros).ok_or_else(invalid)?;
+    Ok((created_at, id.to_string()))
+}
+
+pub(crate) fn to_datetime(ts: Timestamp) -> anyhow::Result<DateTime<Utc>> {
+    Utc.timestamp_opt(ts.seconds, ts.nanos as u32)
+        .single()
+        .ok_or_else(|| anyhow!("Invalid timestamp: {}", ts))
+}
+
+fn to_ts(t: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: t.timestamp(),
+        nanos: t.timestamp_subsec_nanos() as i32,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn page_token_should_round_trip() -> anyhow::Result<()> {
+        let created_at = DateTime::from_timestamp_micros(1_725_000_000_123_456).unwrap();
+        let token = encode_token(created_at, "a|b");
+        assert_eq!((created_at, "a|b".to_string()), decode_token(&token)?);
+        assert!(decode_token("not a token").is_err());
+        // "abc" without a separator
+        assert!(decode_token("YWJj").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn list_sql_should_work() -> anyhow::Result<()> {
+        let req = ListMessagesRequest {
+            filter: Some(MessageFilter {
+                channel: "email".to_string(),
+                states: vec![MessageState::Sent as i32, MessageState::Bounced as i32],
+                created_after: Some(Timestamp::default()),
+                created_before: None,
+            }),
+            page_size: 10,
+            page_token: encode_token(Utc::now(), "id"),
+        };
+        let sql = list_sql(&req, 10)?.into_sql();
+        assert_eq!(
+            format!(
+                "SELECT {} FROM outbox WHERE TRUE AND channel = $1 \
+                 AND status = ANY($2::outbox_status[]) AND created_at >= $3 \
+                 AND (created_at, id) < ($4, $5) ORDER BY created_at DESC, id DESC LIMIT $6",
+                COLUMNS
+            ),
+            sql
+        );
+
+        let req = ListMessagesRequest {
+            filter: Some(MessageFilter {
+                states: vec![MessageState::Unspecified as i32],
+                ..Default::default()
+            }),
+            ..Default::default()
+        };
+        assert!(list_sql(&req, 10).is_err());
+        Ok(())
+    }
+}
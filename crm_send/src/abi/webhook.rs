@@ -0,0 +1,102 @@
+use axum::extract::State;
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::routing::post;
+use axum::{Json, Router};
+use serde::{Deserialize, Serialize};
+use subtle::ConstantTimeEq;
+use tracing::warn;
+
+use crate::abi::outbox::OutboxStatus;
+use crate::NotificationService;
+
+// max events of a callback
+const MAX_EVENTS: usize = 1000;
+
+/// a delivery or bounce callback of a provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeliveryEvent {
+    // SendRequest.id of the message
+    pub id: String,
+    pub status: DeliveryEventStatus,
+    // why the message is bounced
+    #[serde(default)]
+    pub reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryEventStatus {
+    Delivered,
+    Bounced,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DeliveryEventsResponse {
+    // events of unknown messages are ignored
+    pub updated: u64,
+}
+
+impl NotificationService {
+    /// http routes accepting provider callbacks, `POST /webhooks/delivery` takes
+    /// a json array of at most 1000 DeliveryEvent
+    pub fn webhook_router(&self) -> Router {
+        Router::new()
+            .route("/webhooks/delivery", post(delivery_events))
+            .with_state(self.clone())
+    }
+
+    async fn update_delivery(&self, events: &[DeliveryEvent]) -> anyhow::Result<u64> {
+        let mut tx = self.pool.begin().await?;
+        let mut updated = 0;
+        for event in events {
+            let status = match event.status {
+                DeliveryEventStatus::Delivered => OutboxStatus::Delivered,
+                DeliveryEventStatus::Bounced => OutboxStatus::Bounced,
+            };
+            // only a message handed to the provider can be delivered or bounced,
+            // the first attempt may still be recording it as sent
+            let ret = sqlx::query(
+                "UPDATE outbox SET status = $2, last_error = COALESCE($3, last_error), \
+                 updated_at = now() WHERE id = $1 AND status IN ($4, $5)",
+            )
+            .bind(&event.id)
+            .bind(status)
+            .bind(&event.reason)
+            .bind(OutboxStatus::Sending)
+            .bind(OutboxStatus::Sent)
+            .execute(&mut *tx)
+            .await?;
+            updated += ret.rows_affected();
+        }
+        tx.commit().await?;
+        Ok(updated)
+    }
+}
+
+async fn delivery_events(
+    State(svc): State<NotificationService>,
+    headers: HeaderMap,
+    Json(events): Json<Vec<DeliveryEvent>>,
+) -> Result<Json<DeliveryEventsResponse>, StatusCode> {
+    // callbacks are refused if the webhook isn't configured
+    let token = svc.config.webhook.as_ref().map(|w| w.token.as_str());
+    let auth = headers
+        .get(header::AUTHORIZATION)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.strip_prefix("Bearer "));
+    match (token, auth) {
+        (Some(token), Some(auth)) if bool::from(token.as_bytes().ct_eq(auth.as_bytes())) => {}
+        _ => return Err(StatusCode::UNAUTHORIZED),
+    }
+    if events.len() > MAX_EVENTS {
+        return Err(StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    match svc.update_delivery(&events).await {
+        Ok(updated) => Ok(Json(DeliveryEventsResponse { updated })),
+        Err(e) => {
+            warn!("Failed to update delivery status: {:#}", e);
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
@@ -1,4 +1,6 @@
+use std::collections::HashMap;
 use std::fs::File;
+use std::path::PathBuf;
 
 use anyhow::bail;
 use serde::{Deserialize, Serialize};
@@ -8,6 +10,13 @@ pub struct AppConfig {
     pub server: ServerConfig,
     pub db_url: String,
     pub auth: AuthConfig,
+    #[serde(default)]
+    pub providers: ProvidersConfig,
+    #[serde(default)]
+    pub outbox: OutboxConfig,
+    // http server for provider callbacks, disabled if not set
+    #[serde(default)]
+    pub webhook: Option<WebhookConfig>,
 }
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
@@ -19,17 +28,308 @@ pub struct AuthConfig {
     pub sk: String,
 }
 
+/// delivery provider of each channel, a channel without config only logs messages
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProvidersConfig {
+    #[serde(default)]
+    pub email: EmailProviderConfig,
+    #[serde(default)]
+    pub sms: SmsProviderConfig,
+    #[serde(default)]
+    pub in_app: InAppProviderConfig,
+    #[serde(default)]
+    pub rate_limits: RateLimitsConfig,
+    #[serde(default)]
+    pub batch: BatchConfig,
+    #[serde(default)]
+    pub attachments: AttachmentsConfig,
+}
+
+/// url attachments of emails are fetched by the email provider
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AttachmentsConfig {
+    // hosts which may be fetched even if they are loopback, link-local or private
+    pub allowed_hosts: Vec<String>,
+    // a larger attachment is rejected
+    pub max_attachment_bytes: u64,
+    // in milliseconds
+    pub connect_timeout: u64,
+    // timeout of the whole download in milliseconds
+    pub timeout: u64,
+}
+
+/// token bucket of provider calls per channel and provider name (`smtp`, `http`, `file`,
+/// `dummy`), a provider without config isn't limited
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RateLimitsConfig {
+    #[serde(default)]
+    pub email: HashMap<String, RateLimitConfig>,
+    #[serde(default)]
+    pub sms: HashMap<String, RateLimitConfig>,
+    #[serde(default)]
+    pub in_app: HashMap<String, RateLimitConfig>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RateLimitConfig {
+    // provider calls per second
+    pub rate: f64,
+    // calls allowed at once, rate rounded up if not set
+    #[serde(default)]
+    pub burst: Option<u32>,
+}
+
+/// max recipients merged into one provider call, 0 or 1 disables batching.
+/// requests of a Send stream which are ready at the same time and only differ in
+/// recipients are merged, batched emails get the recipients as bcc.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BatchConfig {
+    pub email: u32,
+    pub sms: u32,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum EmailProviderConfig {
+    #[default]
+    Dummy,
+    Smtp(SmtpConfig),
+    File(FileProviderConfig),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum SmsProviderConfig {
+    #[default]
+    Dummy,
+    Http(HttpSmsConfig),
+    File(FileProviderConfig),
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum InAppProviderConfig {
+    #[default]
+    Dummy,
+    File(FileProviderConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SmtpConfig {
+    pub host: String,
+    pub port: u16,
+    #[serde(default)]
+    pub tls: SmtpTls,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    // timeout of a smtp command in milliseconds
+    #[serde(default = "default_smtp_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SmtpTls {
+    // plain text, only for local test servers
+    None,
+    #[default]
+    StartTls,
+    Tls,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HttpSmsConfig {
+    pub url: String,
+    // sent as a bearer token
+    pub token: Option<String>,
+    // request timeout in milliseconds
+    #[serde(default = "default_http_timeout")]
+    pub timeout: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileProviderConfig {
+    pub path: PathBuf,
+}
+
+/// messages are persisted in the outbox table and retried by a pool of workers
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutboxConfig {
+    pub workers: usize,
+    // a message failed this many times is moved to the dead-letter state
+    pub max_attempts: i32,
+    // delay before the first retry in milliseconds, doubled for every retry
+    pub backoff: u64,
+    // max delay between retries in milliseconds
+    pub max_backoff: u64,
+    // how often an idle worker checks for due messages in milliseconds
+    pub poll_interval: u64,
+    // how long a message is locked by a worker in milliseconds, it's retried after
+    // that if the worker died. It must outlast a provider call and its rate-limit wait
+    pub lease: u64,
+    // a request with the id of a message sent within this many milliseconds is a duplicate,
+    // it gets the original response and isn't delivered again
+    pub idempotency_window: u64,
+}
+
+impl Default for OutboxConfig {
+    fn default() -> Self {
+        Self {
+            workers: 4,
+            max_attempts: 5,
+            backoff: 1000,
+            max_backoff: 600_000,
+            poll_interval: 1000,
+            lease: 300_000,
+            idempotency_window: 86_400_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookConfig {
+    pub port: u16,
+    // callbacks must carry it as a bearer token
+    pub token: String,
+}
+
+impl Default for AttachmentsConfig {
+    fn default() -> Self {
+        Self {
+            allowed_hosts: vec![],
+            max_attachment_bytes: 10 * 1024 * 1024,
+            connect_timeout: 5000,
+            timeout: 30_000,
+        }
+    }
+}
+
+fn default_http_timeout() -> u64 {
+    5000
+}
+
+fn default_smtp_timeout() -> u64 {
+    60_000
+}
+
+impl ProvidersConfig {
+    // max duration of a provider call of each channel in milliseconds, with the wait
+    // for a rate-limit token. Emails fetch their attachments first
+    fn call_durations(&self) -> [(&'static str, &'static str, u64); 3] {
+        let fetch = self
+            .attachments
+            .connect_timeout
+            .max(self.attachments.timeout);
+        let email = match &self.email {
+            EmailProviderConfig::Dummy => ("dummy", fetch),
+            EmailProviderConfig::Smtp(smtp) => ("smtp", fetch.saturating_add(smtp.timeout)),
+            EmailProviderConfig::File(_) => ("file", fetch),
+        };
+        let sms = match &self.sms {
+            SmsProviderConfig::Dummy => ("dummy", 0),
+            SmsProviderConfig::Http(http) => ("http", http.timeout),
+            SmsProviderConfig::File(_) => ("file", 0),
+        };
+        let in_app = match &self.in_app {
+            InAppProviderConfig::Dummy => ("dummy", 0),
+            InAppProviderConfig::File(_) => ("file", 0),
+        };
+        let limits = &self.rate_limits;
+        [
+            ("email", email, &limits.email),
+            ("sms", sms, &limits.sms),
+            ("in_app", in_app, &limits.in_app),
+        ]
+        .map(|(channel, (provider, timeout), limits)| {
+            // a token is available after 1 / rate seconds
+            let wait = limits
+                .get(provider)
+                .map(|limit| (1000.0 / limit.rate).ceil() as u64)
+                .unwrap_or_default();
+            (channel, provider, timeout.saturating_add(wait))
+        })
+    }
+}
+
 impl AppConfig {
     pub fn load() -> anyhow::Result<Self> {
-        match (
+        let config: Self = match (
             File::open("send.yml"),
             File::open("/etc/config/send.yml"),
             std::env::var("SEND_CONFIG"),
         ) {
-            (Ok(file), _, _) => Ok(serde_yaml::from_reader(file)?),
-            (_, Ok(file), _) => Ok(serde_yaml::from_reader(file)?),
-            (_, _, Ok(path)) => Ok(serde_yaml::from_str(&path)?),
+            (Ok(file), _, _) => serde_yaml::from_reader(file)?,
+            (_, Ok(file), _) => serde_yaml::from_reader(file)?,
+            (_, _, Ok(path)) => serde_yaml::from_str(&path)?,
             _ => bail!("no config file found"),
+        };
+        config.validate()?;
+        Ok(config)
+    }
+
+    fn validate(&self) -> anyhow::Result<()> {
+        let rate_limits = &self.providers.rate_limits;
+        for (channel, limits) in [
+            ("email", &rate_limits.email),
+            ("sms", &rate_limits.sms),
+            ("in_app", &rate_limits.in_app),
+        ] {
+            for (provider, limit) in limits {
+                if !limit.rate.is_finite() || limit.rate <= 0.0 {
+                    bail!(
+                        "Invalid rate limit of {}/{}: {}",
+                        channel,
+                        provider,
+                        limit.rate
+                    );
+                }
+            }
+        }
+        let outbox = &self.outbox;
+        if outbox.workers == 0 {
+            bail!("outbox.workers must be at least 1, failed messages are never retried");
+        }
+        if outbox.max_attempts <= 0 {
+            bail!(
+                "outbox.max_attempts must be positive: {}",
+                outbox.max_attempts
+            );
+        }
+        if outbox.backoff == 0 {
+            bail!("outbox.backoff must be positive");
+        }
+        if outbox.backoff > outbox.max_backoff {
+            bail!(
+                "outbox.backoff {} is longer than outbox.max_backoff {}",
+                outbox.backoff,
+                outbox.max_backoff
+            );
+        }
+        if outbox.poll_interval == 0 {
+            bail!("outbox.poll_interval must be positive");
+        }
+        if outbox.lease == 0 {
+            bail!("outbox.lease must be positive");
+        }
+        // an attempt outlasting the lease is claimed and sent again by another worker
+        for (channel, provider, duration) in self.providers.call_durations() {
+            if outbox.lease <= duration {
+                bail!(
+                    "outbox.lease must be longer than {}ms, the max duration of a {}/{} call",
+                    duration,
+                    channel,
+                    provider
+                );
+            }
+        }
+        if let Some(webhook) = &self.webhook {
+            if webhook.token.is_empty() {
+                bail!("webhook.token is required when the webhook is enabled");
+            }
         }
+        Ok(())
     }
 }
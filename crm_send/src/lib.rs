@@ -2,18 +2,26 @@ use std::pin::Pin;
 use std::sync::Arc;
 
 use futures::Stream;
-use tokio::sync::mpsc::Sender;
+use sqlx::PgPool;
 use tonic::{async_trait, Request, Response, Status, Streaming};
 
-pub use config::AppConfig;
+pub use config::{
+    AppConfig, AttachmentsConfig, BatchConfig, EmailProviderConfig, FileProviderConfig,
+    HttpSmsConfig, InAppProviderConfig, OutboxConfig, ProvidersConfig, RateLimitConfig,
+    RateLimitsConfig, SmsProviderConfig, SmtpConfig, SmtpTls, WebhookConfig,
+};
 
 use crate::pb::notification_server::Notification;
-use crate::pb::send_request::Msg;
-use crate::pb::{SendRequest, SendResponse};
+use crate::pb::{
+    CancelRequest, CancelResponse, GetStatusRequest, GetStatusResponse, ListMessagesRequest,
+    ListMessagesResponse, SendRequest, SendResponse,
+};
+use crate::provider::Providers;
 
 pub mod abi;
 mod config;
 pub mod pb;
+pub mod provider;
 
 #[derive(Clone)]
 pub struct NotificationService {
@@ -23,7 +31,8 @@ pub struct NotificationService {
 #[allow(unused)]
 pub struct NotificationServiceInner {
     config: AppConfig,
-    sender_svc: Sender<Msg>,
+    pool: PgPool,
+    providers: Providers,
 }
 
 type ServiceResult<T> = Result<Response<T>, Status>;
@@ -40,4 +49,25 @@ impl Notification for NotificationService {
         let stream = request.into_inner();
         self.send(stream).await
     }
+
+    async fn get_status(
+        &self,
+        request: Request<GetStatusRequest>,
+    ) -> ServiceResult<GetStatusResponse> {
+        let request = request.into_inner();
+        self.get_status(request).await
+    }
+
+    async fn list_messages(
+        &self,
+        request: Request<ListMessagesRequest>,
+    ) -> ServiceResult<ListMessagesResponse> {
+        let request = request.into_inner();
+        self.list_messages(request).await
+    }
+
+    async fn cancel(&self, request: Request<CancelRequest>) -> ServiceResult<CancelResponse> {
+        let request = request.into_inner();
+        self.cancel(request).await
+    }
 }
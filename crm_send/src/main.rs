@@ -1,3 +1,4 @@
+use tokio::net::TcpListener;
 use tracing::info;
 use tracing::level_filters::LevelFilter;
 use tracing_subscriber::fmt::Layer;
@@ -16,7 +17,17 @@ async fn main() -> anyhow::Result<()> {
     let addr = format!("[::1]:{}", config.server.port)
         .parse()
         .expect("Failed to parse address ()");
-    let svc = NotificationService::new(config).into_server();
+    let webhook_port = config.webhook.as_ref().map(|w| w.port);
+    let svc = NotificationService::new(config).await;
+
+    if let Some(port) = webhook_port {
+        let listener = TcpListener::bind(format!("[::1]:{}", port)).await?;
+        info!("Webhook server listening on {}", listener.local_addr()?);
+        let router = svc.webhook_router();
+        tokio::spawn(async move { axum::serve(listener, router).await });
+    }
+
+    let svc = svc.into_server();
     info!("gRPC server listening on {}", addr);
 
     tonic::transport::Server::builder()
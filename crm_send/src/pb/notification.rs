@@ -5,6 +5,12 @@
 pub struct SendRequest {
     #[prost(string, tag = "1")]
     pub id: ::prost::alloc::string::String,
+    /// the message is held until send_at if it's in the future
+    #[prost(message, optional, tag = "5")]
+    pub send_at: ::core::option::Option<::prost_types::Timestamp>,
+    /// the message is dropped if it isn't sent before expires_at
+    #[prost(message, optional, tag = "6")]
+    pub expires_at: ::core::option::Option<::prost_types::Timestamp>,
     #[prost(oneof = "send_request::Msg", tags = "2, 3, 4")]
     pub msg: ::core::option::Option<send_request::Msg>,
 }
@@ -22,6 +28,7 @@ pub mod send_request {
     }
 }
 /// email message
+#[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct EmailMessage {
@@ -31,9 +38,50 @@ pub struct EmailMessage {
     pub sender: ::prost::alloc::string::String,
     #[prost(string, repeated, tag = "3")]
     pub recipients: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// plain text body, it's the text alternative if html_body is set
     #[prost(string, tag = "4")]
     pub body: ::prost::alloc::string::String,
+    #[prost(string, tag = "5")]
+    pub html_body: ::prost::alloc::string::String,
+    #[prost(string, tag = "6")]
+    pub reply_to: ::prost::alloc::string::String,
+    #[prost(string, repeated, tag = "7")]
+    pub cc: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    #[prost(string, repeated, tag = "8")]
+    pub bcc: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// extra headers, e.g. List-Unsubscribe
+    #[prost(map = "string, string", tag = "9")]
+    pub headers:
+        ::std::collections::HashMap<::prost::alloc::string::String, ::prost::alloc::string::String>,
+    #[prost(message, repeated, tag = "10")]
+    pub attachments: ::prost::alloc::vec::Vec<Attachment>,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Attachment {
+    #[prost(string, tag = "1")]
+    pub filename: ::prost::alloc::string::String,
+    /// e.g. application/pdf, guessed from the response for url attachments if empty
+    #[prost(string, tag = "2")]
+    pub content_type: ::prost::alloc::string::String,
+    #[prost(oneof = "attachment::Source", tags = "3, 4")]
+    pub source: ::core::option::Option<attachment::Source>,
+}
+/// Nested message and enum types in `Attachment`.
+pub mod attachment {
+    #[derive(serde::Serialize, serde::Deserialize)]
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Source {
+        /// fetched when the email is sent
+        #[prost(string, tag = "3")]
+        Url(::prost::alloc::string::String),
+        #[prost(bytes, tag = "4")]
+        Content(::prost::alloc::vec::Vec<u8>),
+    }
+}
+#[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct SmsMessage {
@@ -44,6 +92,7 @@ pub struct SmsMessage {
     #[prost(string, tag = "3")]
     pub body: ::prost::alloc::string::String,
 }
+#[derive(serde::Serialize, serde::Deserialize)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
 pub struct InAppMessage {
@@ -61,6 +110,208 @@ pub struct SendResponse {
     pub id: ::prost::alloc::string::String,
     #[prost(message, optional, tag = "2")]
     pub created_at: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(enumeration = "SendStatus", tag = "3")]
+    pub status: i32,
+    /// why the message is failed or rejected
+    #[prost(string, tag = "4")]
+    pub error: ::prost::alloc::string::String,
+    /// id assigned by the provider, e.g. the Message-ID of an email
+    #[prost(string, tag = "5")]
+    pub provider_message_id: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MessageInfo {
+    #[prost(string, tag = "1")]
+    pub id: ::prost::alloc::string::String,
+    /// email, sms or in_app
+    #[prost(string, tag = "2")]
+    pub channel: ::prost::alloc::string::String,
+    #[prost(enumeration = "MessageState", tag = "3")]
+    pub state: i32,
+    #[prost(int32, tag = "4")]
+    pub attempts: i32,
+    #[prost(string, tag = "5")]
+    pub last_error: ::prost::alloc::string::String,
+    #[prost(string, tag = "6")]
+    pub provider_message_id: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "7")]
+    pub created_at: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "8")]
+    pub updated_at: ::core::option::Option<::prost_types::Timestamp>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetStatusRequest {
+    #[prost(string, repeated, tag = "1")]
+    pub ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetStatusResponse {
+    /// unknown ids are omitted
+    #[prost(message, repeated, tag = "1")]
+    pub messages: ::prost::alloc::vec::Vec<MessageInfo>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MessageFilter {
+    /// any channel if empty
+    #[prost(string, tag = "1")]
+    pub channel: ::prost::alloc::string::String,
+    /// any state if empty
+    #[prost(enumeration = "MessageState", repeated, tag = "2")]
+    pub states: ::prost::alloc::vec::Vec<i32>,
+    #[prost(message, optional, tag = "3")]
+    pub created_after: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "4")]
+    pub created_before: ::core::option::Option<::prost_types::Timestamp>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListMessagesRequest {
+    #[prost(message, optional, tag = "1")]
+    pub filter: ::core::option::Option<MessageFilter>,
+    /// 100 if 0
+    #[prost(uint32, tag = "2")]
+    pub page_size: u32,
+    /// next_page_token of the previous page
+    #[prost(string, tag = "3")]
+    pub page_token: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ListMessagesResponse {
+    /// newest first
+    #[prost(message, repeated, tag = "1")]
+    pub messages: ::prost::alloc::vec::Vec<MessageInfo>,
+    /// empty for the last page
+    #[prost(string, tag = "2")]
+    pub next_page_token: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelRequest {
+    #[prost(string, repeated, tag = "1")]
+    pub ids: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CancelResponse {
+    /// messages which are scheduled or waiting for a retry are cancelled,
+    /// others are omitted
+    #[prost(string, repeated, tag = "1")]
+    pub cancelled: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum SendStatus {
+    Unspecified = 0,
+    /// accepted but not delivered yet
+    Queued = 1,
+    /// delivered to the provider
+    Sent = 2,
+    /// delivery failed, retrying may succeed
+    Failed = 3,
+    /// invalid message or refused by the provider, retrying won't help
+    Rejected = 4,
+    /// held until send_at
+    Scheduled = 5,
+    /// expires_at passed before the message is sent
+    Expired = 6,
+}
+impl SendStatus {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            SendStatus::Unspecified => "SEND_STATUS_UNSPECIFIED",
+            SendStatus::Queued => "SEND_STATUS_QUEUED",
+            SendStatus::Sent => "SEND_STATUS_SENT",
+            SendStatus::Failed => "SEND_STATUS_FAILED",
+            SendStatus::Rejected => "SEND_STATUS_REJECTED",
+            SendStatus::Scheduled => "SEND_STATUS_SCHEDULED",
+            SendStatus::Expired => "SEND_STATUS_EXPIRED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "SEND_STATUS_UNSPECIFIED" => Some(Self::Unspecified),
+            "SEND_STATUS_QUEUED" => Some(Self::Queued),
+            "SEND_STATUS_SENT" => Some(Self::Sent),
+            "SEND_STATUS_FAILED" => Some(Self::Failed),
+            "SEND_STATUS_REJECTED" => Some(Self::Rejected),
+            "SEND_STATUS_SCHEDULED" => Some(Self::Scheduled),
+            "SEND_STATUS_EXPIRED" => Some(Self::Expired),
+            _ => None,
+        }
+    }
+}
+/// state of a message in the outbox
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum MessageState {
+    Unspecified = 0,
+    /// waiting for a retry
+    Pending = 1,
+    /// being delivered
+    Sending = 2,
+    /// accepted by the provider
+    Sent = 3,
+    Rejected = 4,
+    /// failed too many times
+    Dead = 5,
+    /// the provider reported the message is delivered to the recipient
+    Delivered = 6,
+    /// the provider reported the message is bounced
+    Bounced = 7,
+    /// held until send_at
+    Scheduled = 8,
+    /// expires_at passed before the message is sent
+    Expired = 9,
+    /// cancelled before it's sent
+    Cancelled = 10,
+}
+impl MessageState {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            MessageState::Unspecified => "MESSAGE_STATE_UNSPECIFIED",
+            MessageState::Pending => "MESSAGE_STATE_PENDING",
+            MessageState::Sending => "MESSAGE_STATE_SENDING",
+            MessageState::Sent => "MESSAGE_STATE_SENT",
+            MessageState::Rejected => "MESSAGE_STATE_REJECTED",
+            MessageState::Dead => "MESSAGE_STATE_DEAD",
+            MessageState::Delivered => "MESSAGE_STATE_DELIVERED",
+            MessageState::Bounced => "MESSAGE_STATE_BOUNCED",
+            MessageState::Scheduled => "MESSAGE_STATE_SCHEDULED",
+            MessageState::Expired => "MESSAGE_STATE_EXPIRED",
+            MessageState::Cancelled => "MESSAGE_STATE_CANCELLED",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "MESSAGE_STATE_UNSPECIFIED" => Some(Self::Unspecified),
+            "MESSAGE_STATE_PENDING" => Some(Self::Pending),
+            "MESSAGE_STATE_SENDING" => Some(Self::Sending),
+            "MESSAGE_STATE_SENT" => Some(Self::Sent),
+            "MESSAGE_STATE_REJECTED" => Some(Self::Rejected),
+            "MESSAGE_STATE_DEAD" => Some(Self::Dead),
+            "MESSAGE_STATE_DELIVERED" => Some(Self::Delivered),
+            "MESSAGE_STATE_BOUNCED" => Some(Self::Bounced),
+            "MESSAGE_STATE_SCHEDULED" => Some(Self::Scheduled),
+            "MESSAGE_STATE_EXPIRED" => Some(Self::Expired),
+            "MESSAGE_STATE_CANCELLED" => Some(Self::Cancelled),
+            _ => None,
+        }
+    }
 }
 /// Generated client implementations.
 pub mod notification_client {
@@ -166,6 +417,61 @@ pub mod notification_client {
                 .insert(GrpcMethod::new("notification.Notification", "Send"));
             self.inner.streaming(req, path, codec).await
         }
+        /// current state of the messages
+        pub async fn get_status(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetStatusRequest>,
+        ) -> std::result::Result<tonic::Response<super::GetStatusResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/notification.Notification/GetStatus");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("notification.Notification", "GetStatus"));
+            self.inner.unary(req, path, codec).await
+        }
+        pub async fn list_messages(
+            &mut self,
+            request: impl tonic::IntoRequest<super::ListMessagesRequest>,
+        ) -> std::result::Result<tonic::Response<super::ListMessagesResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/notification.Notification/ListMessages");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("notification.Notification", "ListMessages"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// cancel messages which aren't sent yet
+        pub async fn cancel(
+            &mut self,
+            request: impl tonic::IntoRequest<super::CancelRequest>,
+        ) -> std::result::Result<tonic::Response<super::CancelResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/notification.Notification/Cancel");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("notification.Notification", "Cancel"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -184,6 +490,20 @@ pub mod notification_server {
             &self,
             request: tonic::Request<tonic::Streaming<super::SendRequest>>,
         ) -> std::result::Result<tonic::Response<Self::SendStream>, tonic::Status>;
+        /// current state of the messages
+        async fn get_status(
+            &self,
+            request: tonic::Request<super::GetStatusRequest>,
+        ) -> std::result::Result<tonic::Response<super::GetStatusResponse>, tonic::Status>;
+        async fn list_messages(
+            &self,
+            request: tonic::Request<super::ListMessagesRequest>,
+        ) -> std::result::Result<tonic::Response<super::ListMessagesResponse>, tonic::Status>;
+        /// cancel messages which aren't sent yet
+        async fn cancel(
+            &self,
+            request: tonic::Request<super::CancelRequest>,
+        ) -> std::result::Result<tonic::Response<super::CancelResponse>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct NotificationServer<T: Notification> {
@@ -302,6 +622,127 @@ pub mod notification_server {
                     };
                     Box::pin(fut)
                 }
+                "/notification.Notification/GetStatus" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetStatusSvc<T: Notification>(pub Arc<T>);
+                    impl<T: Notification> tonic::server::UnaryService<super::GetStatusRequest> for GetStatusSvc<T> {
+                        type Response = super::GetStatusResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetStatusRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Notification>::get_status(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetStatusSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/notification.Notification/ListMessages" => {
+                    #[allow(non_camel_case_types)]
+                    struct ListMessagesSvc<T: Notification>(pub Arc<T>);
+                    impl<T: Notification> tonic::server::UnaryService<super::ListMessagesRequest>
+                        for ListMessagesSvc<T>
+                    {
+                        type Response = super::ListMessagesResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::ListMessagesRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as Notification>::list_messages(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ListMessagesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/notification.Notification/Cancel" => {
+                    #[allow(non_camel_case_types)]
+                    struct CancelSvc<T: Notification>(pub Arc<T>);
+                    impl<T: Notification> tonic::server::UnaryService<super::CancelRequest> for CancelSvc<T> {
+                        type Response = super::CancelResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::CancelRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as Notification>::cancel(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CancelSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
@@ -0,0 +1,73 @@
+use tokio::sync::mpsc;
+use tonic::async_trait;
+use tracing::info;
+
+use crate::pb::{EmailMessage, InAppMessage, SmsMessage};
+use crate::provider::{Delivery, EmailProvider, InAppProvider, SmsProvider};
+
+const CHANNEL_SIZE: usize = 1024 * 100;
+
+/// log messages instead of delivering them, emails are logged as MIME messages
+/// with the urls of url attachments
+#[derive(Clone)]
+pub struct DummyProvider {
+    tx: mpsc::Sender<String>,
+}
+
+impl DummyProvider {
+    pub fn new() -> Self {
+        let (tx, mut rx) = mpsc::channel(CHANNEL_SIZE);
+        tokio::spawn(async move {
+            while let Some(msg) = rx.recv().await {
+                info!("Send message: {}", msg);
+            }
+        });
+        Self { tx }
+    }
+
+    async fn send(&self, msg: String) -> anyhow::Result<Delivery> {
+        self.tx.send(msg).await?;
+        Ok(Delivery::queued())
+    }
+}
+
+impl Default for DummyProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl EmailProvider for DummyProvider {
+    async fn send(&self, msg: &EmailMessage) -> anyhow::Result<Delivery> {
+        let message = msg.to_message(None).await?;
+        let mime = String::from_utf8_lossy(&message.formatted()).into_owned();
+        DummyProvider::send(self, mime).await
+    }
+
+    fn name(&self) -> &'static str {
+        "dummy"
+    }
+}
+
+#[async_trait]
+impl SmsProvider for DummyProvider {
+    async fn send(&self, msg: &SmsMessage) -> anyhow::Result<Delivery> {
+        DummyProvider::send(self, format!("{:?}", msg)).await
+    }
+
+    fn name(&self) -> &'static str {
+        "dummy"
+    }
+}
+
+#[async_trait]
+impl InAppProvider for DummyProvider {
+    async fn send(&self, msg: &InAppMessage) -> anyhow::Result<Delivery> {
+        DummyProvider::send(self, format!("{:?}", msg)).await
+    }
+
+    fn name(&self) -> &'static str {
+        "dummy"
+    }
+}
@@ -0,0 +1,307 @@
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::Context;
+use reqwest::dns::{Addrs, Name, Resolve, Resolving};
+use reqwest::redirect::Policy;
+use reqwest::{header, Client};
+use url::{Host, Url};
+
+use crate::provider::Rejected;
+use crate::AttachmentsConfig;
+
+const MAX_REDIRECTS: usize = 5;
+
+/// fetch url attachments of emails. Only http(s) urls are fetched, and hosts which
+/// resolve to loopback, link-local or private addresses are blocked unless allowed
+#[derive(Clone)]
+pub struct AttachmentFetcher {
+    client: Client,
+    allowed_hosts: Arc<HashSet<String>>,
+    max_bytes: u64,
+}
+
+/// an attachment url which must not be fetched
+#[derive(Debug)]
+struct Blocked(String);
+
+// resolve hosts and block internal addresses, so a public name can't point to them
+struct PublicResolver {
+    allowed_hosts: Arc<HashSet<String>>,
+}
+
+impl AttachmentFetcher {
+    pub fn try_new(config: &AttachmentsConfig) -> anyhow::Result<Self> {
+        let allowed_hosts = Arc::new(
+            config
+                .allowed_hosts
+                .iter()
+                .map(|host| host.to_ascii_lowercase())
+                .collect::<HashSet<_>>(),
+        );
+        let resolver = PublicResolver {
+            allowed_hosts: allowed_hosts.clone(),
+        };
+        // redirects are checked like the original url
+        let redirect_hosts = allowed_hosts.clone();
+        let redirect = Policy::custom(move |attempt| {
+            if attempt.previous().len() >= MAX_REDIRECTS {
+                return attempt.error(Blocked("Too many redirects".to_string()));
+            }
+            match check_url(attempt.url(), &redirect_hosts) {
+                Ok(()) => attempt.follow(),
+                Err(e) => attempt.error(e),
+            }
+        });
+        let client = Client::builder()
+            .connect_timeout(Duration::from_millis(config.connect_timeout))
+            .timeout(Duration::from_millis(config.timeout))
+            .dns_resolver(Arc::new(resolver))
+            .redirect(redirect)
+            .no_proxy()
+            .build()?;
+        Ok(Self {
+            client,
+            allowed_hosts,
+            max_bytes: config.max_attachment_bytes,
+        })
+    }
+
+    /// the content of the url and its content type. A blocked url, a missing file
+    /// or a body larger than the limit is Rejected
+    pub async fn fetch(&self, url: &str) -> anyhow::Result<(Vec<u8>, String)> {
+        let invalid = |e: String| anyhow::Error::from(Rejected(e));
+        let parsed = Url::parse(url)
+            .map_err(|e| invalid(format!("Invalid attachment url {}: {}", url, e)))?;
+        check_url(&parsed, &self.allowed_hosts)
+            .map_err(|e| invalid(format!("Failed to fetch attachment {}: {}", url, e)))?;
+
+        let res = match self.client.get(parsed).send().await {
+            Ok(res) => res,
+            Err(e) => match blocked(&e) {
+                Some(blocked) => {
+                    return Err(invalid(format!(
+                        "Failed to fetch attachment {}: {}",
+                        url, blocked
+                    )))
+                }
+                None => {
+                    return Err(e).with_context(|| format!("Failed to fetch attachment {}", url))
+                }
+            },
+        };
+        // the attachment is gone, retrying won't help
+        if res.status().is_client_error() {
+            return Err(invalid(format!(
+                "Failed to fetch attachment {}: {}",
+                url,
+                res.status()
+            )));
+        }
+        let mut res = res.error_for_status()?;
+        let too_large = || {
+            invalid(format!(
+                "Attachment {} is larger than {} bytes",
+                url, self.max_bytes
+            ))
+        };
+        if res.content_length().is_some_and(|len| len > self.max_bytes) {
+            return Err(too_large());
+        }
+        let content_type = res
+            .headers()
+            .get(header::CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        let mut content = Vec::new();
+        while let Some(chunk) = res.chunk().await? {
+            if (content.len() + chunk.len()) as u64 > self.max_bytes {
+                return Err(too_large());
+            }
+            content.extend_from_slice(&chunk);
+        }
+        Ok((content, content_type))
+    }
+}
+
+impl Resolve for PublicResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        let host = name.as_str().to_ascii_lowercase();
+        let allowed = self.allowed_hosts.contains(&host);
+        Box::pin(async move {
+            let addrs = tokio::net::lookup_host((host.as_str(), 0))
+                .await?
+                .collect::<Vec<SocketAddr>>();
+            if !allowed {
+                if let Some(addr) = addrs.iter().find(|addr| is_internal(addr.ip())) {
+                    let e = Blocked(format!("{} resolves to {}", host, addr.ip()));
+                    return Err(Box::new(e) as Box<dyn Error + Send + Sync>);
+                }
+            }
+            Ok(Box::new(addrs.into_iter()) as Addrs)
+        })
+    }
+}
+
+// names are checked by the resolver, ip addresses are checked here
+fn check_url(url: &Url, allowed_hosts: &HashSet<String>) -> Result<(), Blocked> {
+    if !matches!(url.scheme(), "http" | "https") {
+        return Err(Blocked(format!("Unsupported scheme {}", url.scheme())));
+    }
+    let host = url.host_str().unwrap_or_default().to_ascii_lowercase();
+    if allowed_hosts.contains(&host) {
+        return Ok(());
+    }
+    let ip = match url.host() {
+        Some(Host::Ipv4(ip)) => IpAddr::V4(ip),
+        Some(Host::Ipv6(ip)) => IpAddr::V6(ip),
+        Some(Host::Domain(_)) => return Ok(()),
+        None => return Err(Blocked("No host".to_string())),
+    };
+    if is_internal(ip) {
+        return Err(Blocked(format!("Address {} isn't allowed", ip)));
+    }
+    Ok(())
+}
+
+fn is_internal(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            let [a, b, c, _] = ip.octets();
+            ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_multicast()
+                // "this network" 0.0.0.0/8
+                || a == 0
+                // shared address space of carrier-grade NAT 100.64.0.0/10
+                || (a == 100 && (64..128).contains(&b))
+                // IETF protocol assignments 192.0.0.0/24
+                || (a == 192 && b == 0 && c == 0)
+                // benchmarking 198.18.0.0/15
+                || (a == 198 && (b & 0xfe) == 18)
+                // reserved 240.0.0.0/4, broadcast included
+                || a >= 240
+        }
+        IpAddr::V6(ip) => {
+            // IPv4-mapped ::ffff:0:0/96
+            if let Some(ip) = ip.to_ipv4_mapped() {
+                return is_internal(IpAddr::V4(ip));
+            }
+            let segments = ip.segments();
+            ip.is_loopback()
+                || ip.is_unspecified()
+                || ip.is_multicast()
+                // unique local fc00::/7 and link-local fe80::/10
+                || (segments[0] & 0xfe00) == 0xfc00
+                || (segments[0] & 0xffc0) == 0xfe80
+                // NAT64 64:ff9b::/96 reaches any IPv4 address through the gateway
+                || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+        }
+    }
+}
+
+// the reason if the request was blocked by the resolver or the redirect policy
+fn blocked(e: &reqwest::Error) -> Option<&Blocked> {
+    let mut source = e.source();
+    while let Some(e) = source {
+        if let Some(blocked) = e.downcast_ref::<Blocked>() {
+            return Some(blocked);
+        }
+        source = e.source();
+    }
+    None
+}
+
+impl fmt::Display for Blocked {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+
+impl Error for Blocked {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn internal_addresses_should_be_blocked() {
+        let allowed = HashSet::from(["10.0.0.8".to_string()]);
+        let check = |url: &str| check_url(&Url::parse(url).unwrap(), &allowed).is_ok();
+        assert!(check("https://files.acme.org/report.csv"));
+        assert!(check("http://93.184.216.34/report.csv"));
+        assert!(check("http://10.0.0.8/report.csv"));
+        assert!(!check("ftp://files.acme.org/report.csv"));
+        assert!(!check("file:///etc/passwd"));
+        assert!(!check("http://127.0.0.1:50002/"));
+        assert!(!check("http://169.254.169.254/latest/meta-data"));
+        assert!(!check("http://192.168.1.1/"));
+        assert!(!check("http://[::1]/"));
+        assert!(!check("http://[fe80::1]/"));
+        assert!(!check("http://[::ffff:10.0.0.1]/"));
+    }
+
+    fn internal(ip: &str) -> bool {
+        is_internal(ip.parse().unwrap())
+    }
+
+    #[test]
+    fn this_network_should_be_internal() {
+        assert!(internal("0.0.0.0"));
+        assert!(internal("0.1.2.3"));
+        assert!(!internal("1.0.0.1"));
+    }
+
+    #[test]
+    fn carrier_grade_nat_should_be_internal() {
+        assert!(internal("100.64.0.1"));
+        assert!(internal("100.127.255.254"));
+        assert!(!internal("100.63.255.255"));
+        assert!(!internal("100.128.0.1"));
+    }
+
+    #[test]
+    fn ietf_protocol_assignments_should_be_internal() {
+        assert!(internal("192.0.0.8"));
+        assert!(!internal("192.0.1.1"));
+    }
+
+    #[test]
+    fn benchmarking_should_be_internal() {
+        assert!(internal("198.18.0.1"));
+        assert!(internal("198.19.255.254"));
+        assert!(!internal("198.17.0.1"));
+        assert!(!internal("198.20.0.1"));
+    }
+
+    #[test]
+    fn multicast_and_reserved_should_be_internal() {
+        assert!(internal("224.0.0.1"));
+        assert!(internal("239.255.255.250"));
+        assert!(internal("240.0.0.1"));
+        assert!(internal("255.255.255.255"));
+        assert!(internal("ff02::1"));
+        assert!(!internal("223.255.255.254"));
+    }
+
+    #[test]
+    fn nat64_should_be_internal() {
+        assert!(internal("64:ff9b::a00:1"));
+        assert!(internal("64:ff9b::5db8:d822"));
+        assert!(!internal("2606:2800:220:1::1"));
+    }
+
+    #[test]
+    fn ipv4_mapped_should_be_checked_as_ipv4() {
+        assert!(internal("::ffff:127.0.0.1"));
+        assert!(internal("::ffff:100.64.0.1"));
+        assert!(!internal("::ffff:93.184.216.34"));
+    }
+}
@@ -0,0 +1,87 @@
+use std::fs::OpenOptions;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::Context;
+use serde::Serialize;
+use tokio::fs::File;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Mutex;
+use tonic::async_trait;
+
+use crate::pb::{EmailMessage, InAppMessage, SmsMessage};
+use crate::provider::{Delivery, EmailProvider, InAppProvider, SmsProvider};
+
+/// append every message as a json line to a file, useful for tests
+#[derive(Clone)]
+pub struct FileProvider {
+    file: Arc<Mutex<File>>,
+}
+
+#[derive(Serialize)]
+struct Record<'a, T> {
+    channel: &'static str,
+    msg: &'a T,
+}
+
+impl FileProvider {
+    pub fn try_new(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+        Ok(Self {
+            file: Arc::new(Mutex::new(File::from_std(file))),
+        })
+    }
+
+    async fn write<T: Serialize>(
+        &self,
+        channel: &'static str,
+        msg: &T,
+    ) -> anyhow::Result<Delivery> {
+        let mut line = serde_json::to_vec(&Record { channel, msg })?;
+        line.push(b'\n');
+        let mut file = self.file.lock().await;
+        file.write_all(&line).await?;
+        file.flush().await?;
+        Ok(Delivery::sent(None))
+    }
+}
+
+#[async_trait]
+impl EmailProvider for FileProvider {
+    async fn send(&self, msg: &EmailMessage) -> anyhow::Result<Delivery> {
+        // an email the smtp provider would reject isn't written
+        msg.to_message(None).await?;
+        self.write("email", msg).await
+    }
+
+    fn name(&self) -> &'static str {
+        "file"
+    }
+}
+
+#[async_trait]
+impl SmsProvider for FileProvider {
+    async fn send(&self, msg: &SmsMessage) -> anyhow::Result<Delivery> {
+        self.write("sms", msg).await
+    }
+
+    fn name(&self) -> &'static str {
+        "file"
+    }
+}
+
+#[async_trait]
+impl InAppProvider for FileProvider {
+    async fn send(&self, msg: &InAppMessage) -> anyhow::Result<Delivery> {
+        self.write("in_app", msg).await
+    }
+
+    fn name(&self) -> &'static str {
+        "file"
+    }
+}
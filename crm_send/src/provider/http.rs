@@ -0,0 +1,61 @@
+use std::time::Duration;
+
+use reqwest::{Client, StatusCode};
+use tonic::async_trait;
+
+use crate::pb::SmsMessage;
+use crate::provider::{Delivery, Rejected, SmsProvider};
+use crate::HttpSmsConfig;
+
+/// post SmsMessage as json to a http sms gateway,
+/// the `id` field of a json response is used as the provider message id
+pub struct HttpSmsProvider {
+    client: Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl HttpSmsProvider {
+    pub fn try_new(config: &HttpSmsConfig) -> anyhow::Result<Self> {
+        let client = Client::builder()
+            .timeout(Duration::from_millis(config.timeout))
+            .build()?;
+        Ok(Self {
+            client,
+            url: config.url.clone(),
+            token: config.token.clone(),
+        })
+    }
+}
+
+#[async_trait]
+impl SmsProvider for HttpSmsProvider {
+    async fn send(&self, msg: &SmsMessage) -> anyhow::Result<Delivery> {
+        let mut req = self.client.post(&self.url).json(msg);
+        if let Some(token) = &self.token {
+            req = req.bearer_auth(token);
+        }
+        let res = req.send().await?;
+        let status = res.status();
+        // throttled or timed out requests may succeed later
+        let retryable = matches!(
+            status,
+            StatusCode::TOO_MANY_REQUESTS | StatusCode::REQUEST_TIMEOUT
+        );
+        if status.is_client_error() && !retryable {
+            let body = res.text().await.unwrap_or_default();
+            return Err(Rejected(format!("{}: {}", status, body)).into());
+        }
+        let res = res.error_for_status()?;
+        let id = res
+            .json::<serde_json::Value>()
+            .await
+            .ok()
+            .and_then(|v| v.get("id").and_then(|id| id.as_str()).map(String::from));
+        Ok(Delivery::sent(id))
+    }
+
+    fn name(&self) -> &'static str {
+        "http"
+    }
+}
@@ -0,0 +1,152 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::time::sleep;
+use tonic::async_trait;
+
+use crate::pb::{EmailMessage, InAppMessage, SmsMessage};
+use crate::provider::{Delivery, EmailProvider, InAppProvider, SmsProvider};
+use crate::RateLimitConfig;
+
+// the bucket is checked again after this long, so a tiny rate can't overflow the wait
+const MAX_WAIT: Duration = Duration::from_secs(1);
+
+/// a token bucket, every provider call takes a token
+#[derive(Debug)]
+pub struct TokenBucket {
+    // tokens added per second
+    rate: f64,
+    burst: f64,
+    state: Mutex<BucketState>,
+}
+
+#[derive(Debug)]
+struct BucketState {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/// a provider which waits for a token before every call
+pub struct RateLimited<P: ?Sized> {
+    inner: Arc<P>,
+    bucket: TokenBucket,
+}
+
+impl TokenBucket {
+    pub fn new(config: &RateLimitConfig) -> Self {
+        let rate = config.rate.max(f64::MIN_POSITIVE);
+        let burst = config
+            .burst
+            .map(|b| b as f64)
+            .unwrap_or(rate.ceil())
+            .max(1.0);
+        Self {
+            rate,
+            burst,
+            state: Mutex::new(BucketState {
+                tokens: burst,
+                updated_at: Instant::now(),
+            }),
+        }
+    }
+
+    /// wait until a token is taken, waiting callers are not served in any order
+    pub async fn acquire(&self) {
+        loop {
+            let wait = {
+                let mut state = self.state.lock().unwrap();
+                let now = Instant::now();
+                let elapsed = now.duration_since(state.updated_at).as_secs_f64();
+                state.tokens = (state.tokens + elapsed * self.rate).min(self.burst);
+                state.updated_at = now;
+                if state.tokens >= 1.0 {
+                    state.tokens -= 1.0;
+                    return;
+                }
+                (1.0 - state.tokens) / self.rate
+            };
+            let wait = Duration::try_from_secs_f64(wait).unwrap_or(MAX_WAIT);
+            sleep(wait.min(MAX_WAIT)).await;
+        }
+    }
+}
+
+impl<P: ?Sized> RateLimited<P> {
+    pub fn new(inner: Arc<P>, config: &RateLimitConfig) -> Self {
+        Self {
+            inner,
+            bucket: TokenBucket::new(config),
+        }
+    }
+}
+
+#[async_trait]
+impl EmailProvider for RateLimited<dyn EmailProvider> {
+    async fn send(&self, msg: &EmailMessage) -> anyhow::Result<Delivery> {
+        self.bucket.acquire().await;
+        self.inner.send(msg).await
+    }
+
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+}
+
+#[async_trait]
+impl SmsProvider for RateLimited<dyn SmsProvider> {
+    async fn send(&self, msg: &SmsMessage) -> anyhow::Result<Delivery> {
+        self.bucket.acquire().await;
+        self.inner.send(msg).await
+    }
+
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+}
+
+#[async_trait]
+impl InAppProvider for RateLimited<dyn InAppProvider> {
+    async fn send(&self, msg: &InAppMessage) -> anyhow::Result<Delivery> {
+        self.bucket.acquire().await;
+        self.inner.send(msg).await
+    }
+
+    fn name(&self) -> &'static str {
+        self.inner.name()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn tiny_rate_should_not_overflow() {
+        let bucket = TokenBucket::new(&RateLimitConfig {
+            rate: 0.0,
+            burst: Some(1),
+        });
+        bucket.acquire().await;
+        let waiting = tokio::time::timeout(Duration::from_millis(50), bucket.acquire()).await;
+        assert!(waiting.is_err());
+    }
+
+    #[tokio::test]
+    async fn token_bucket_should_limit_rate() {
+        let bucket = TokenBucket::new(&RateLimitConfig {
+            rate: 20.0,
+            burst: Some(2),
+        });
+        let start = Instant::now();
+        // the burst is taken at once
+        bucket.acquire().await;
+        bucket.acquire().await;
+        assert!(start.elapsed() < Duration::from_millis(20));
+        // then a token every 50ms
+        bucket.acquire().await;
+        bucket.acquire().await;
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(95), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_millis(300), "{:?}", elapsed);
+    }
+}
@@ -0,0 +1,325 @@
+use crate::pb::attachment::Source;
+use crate::pb::{Attachment, EmailMessage};
+use crate::provider::{AttachmentFetcher, Rejected};
+use anyhow::{anyhow, bail, Context};
+use lettre::message::header::{ContentType, HeaderName, HeaderValue};
+use lettre::message::{Attachment as AttachmentPart, Mailbox, MultiPart, SinglePart};
+use lettre::Message;
+
+// headers which are set from the fields of EmailMessage
+const RESERVED_HEADERS: [&str; 12] = [
+    "From",
+    "Sender",
+    "To",
+    "Cc",
+    "Bcc",
+    "Reply-To",
+    "Subject",
+    "Date",
+    "Message-ID",
+    "MIME-Version",
+    "Content-Type",
+    "Content-Transfer-Encoding",
+];
+
+const UNDISCLOSED_RECIPIENTS: &str = "undisclosed-recipients:;";
+
+impl EmailMessage {
+    /// build a RFC 5322 message, it's multipart/alternative if both text and html
+    /// bodies are set, and wrapped in multipart/mixed if there are attachments.
+    /// an invalid message is Rejected. url attachments are fetched by `fetcher`,
+    /// without it they are attached as text/uri-list parts of the urls.
+    pub async fn to_message(&self, fetcher: Option<&AttachmentFetcher>) -> anyhow::Result<Message> {
+        let mut parts = Vec::with_capacity(self.attachments.len());
+        for attachment in &self.attachments {
+            parts.push(attachment.to_part(fetcher).await?);
+        }
+        self.build(parts)
+            .map_err(|e| Rejected(format!("{:#}", e)).into())
+    }
+
+    fn build(&self, attachments: Vec<SinglePart>) -> anyhow::Result<Message> {
+        let mut builder = Message::builder()
+            .from(parse_mailbox("sender", &self.sender)?)
+            .subject(&self.subject)
+            .message_id(None);
+        if !self.reply_to.is_empty() {
+            builder = builder.reply_to(parse_mailbox("reply-to", &self.reply_to)?);
+        }
+        for recipient in &self.recipients {
+            builder = builder.to(parse_mailbox("recipient", recipient)?);
+        }
+        for cc in &self.cc {
+            builder = builder.cc(parse_mailbox("cc", cc)?);
+        }
+        for bcc in &self.bcc {
+            builder = builder.bcc(parse_mailbox("bcc", bcc)?);
+        }
+
+        let body = match (self.body.is_empty(), self.html_body.is_empty()) {
+            (false, false) => Body::Multi(MultiPart::alternative_plain_html(
+                self.body.clone(),
+                self.html_body.clone(),
+            )),
+            (true, false) => Body::Single(SinglePart::html(self.html_body.clone())),
+            _ => Body::Single(SinglePart::plain(self.body.clone())),
+        };
+        let mut message = if attachments.is_empty() {
+            match body {
+                Body::Single(part) => builder.singlepart(part)?,
+                Body::Multi(part) => builder.multipart(part)?,
+            }
+        } else {
+            let mixed = match body {
+                Body::Single(part) => MultiPart::mixed().singlepart(part),
+                Body::Multi(part) => MultiPart::mixed().multipart(part),
+            };
+            let mixed = attachments
+                .into_iter()
+                .fold(mixed, |mixed, part| mixed.singlepart(part));
+            builder.multipart(mixed)?
+        };
+
+        // a batched email only has bcc recipients, RFC 5322 suggests an empty group as To
+        if self.recipients.is_empty() && self.cc.is_empty() {
+            message.headers_mut().insert_raw(HeaderValue::new(
+                HeaderName::new_from_ascii_str("To"),
+                UNDISCLOSED_RECIPIENTS.to_string(),
+            ));
+        }
+
+        // sorted to keep the generated message stable
+        let mut headers = self.headers.iter().collect::<Vec<_>>();
+        headers.sort();
+        for (name, value) in headers {
+            if RESERVED_HEADERS
+                .iter()
+                .any(|h| h.eq_ignore_ascii_case(name))
+            {
+                bail!("Header {} can't be overridden", name);
+            }
+            if value.contains(['\r', '\n']) {
+                bail!("Invalid value of header {}", name);
+            }
+            let name = HeaderName::new_from_ascii(name.clone())
+                .map_err(|_| anyhow!("Invalid header name: {}", name))?;
+            message
+                .headers_mut()
+                .insert_raw(HeaderValue::new(name, value.clone()));
+        }
+        Ok(message)
+    }
+}
+
+enum Body {
+    Single(SinglePart),
+    Multi(MultiPart),
+}
+
+impl Attachment {
+    async fn to_part(&self, fetcher: Option<&AttachmentFetcher>) -> anyhow::Result<SinglePart> {
+        let invalid = |e: anyhow::Error| Rejected(format!("{:#}", e));
+        if self.filename.is_empty() {
+            return Err(invalid(anyhow!("Attachment has no filename")).into());
+        }
+        let (content, content_type) = match &self.source {
+            Some(Source::Content(content)) => (content.clone(), self.content_type.clone()),
+            Some(Source::Url(url)) => match fetcher {
+                Some(fetcher) => {
+                    let (content, content_type) = fetcher.fetch(url).await?;
+                    match self.content_type.is_empty() {
+                        true => (content, content_type),
+                        false => (content, self.content_type.clone()),
+                    }
+                }
+                None => (url.clone().into_bytes(), "text/uri-list".to_string()),
+            },
+            None => {
+                let e = anyhow!("Attachment {} has no content", self.filename);
+                return Err(invalid(e).into());
+            }
+        };
+        let content_type = match content_type.is_empty() {
+            true => "application/octet-stream",
+            false => content_type.as_str(),
+        };
+        let content_type = ContentType::parse(content_type)
+            .map_err(|_| invalid(anyhow!("Invalid content type: {}", content_type)))?;
+        Ok(AttachmentPart::new(self.filename.clone()).body(content, content_type))
+    }
+}
+
+fn parse_mailbox(field: &str, addr: &str) -> anyhow::Result<Mailbox> {
+    addr.parse()
+        .with_context(|| format!("Invalid {}: {}", field, addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    fn new_email() -> EmailMessage {
+        EmailMessage {
+            subject: "Welcome".to_string(),
+            sender: "admin@crm.org".to_string(),
+            recipients: vec!["tyr@acme.org".to_string()],
+            body: "Hi, Welcome to CRM!".to_string(),
+            ..Default::default()
+        }
+    }
+
+    async fn format(msg: &EmailMessage) -> anyhow::Result<String> {
+        let message = msg.to_message(None).await?;
+        Ok(String::from_utf8(message.formatted())?)
+    }
+
+    #[tokio::test]
+    async fn text_email_should_be_single_part() -> anyhow::Result<()> {
+        let mime = format(&new_email()).await?;
+        assert!(mime.contains("Content-Type: text/plain; charset=utf-8"));
+        assert!(!mime.contains("multipart"));
+        assert!(mime.contains("Hi, Welcome to CRM!"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn html_email_should_have_text_alternative() -> anyhow::Result<()> {
+        let msg = EmailMessage {
+            html_body: "<p>Hi, Welcome to CRM!</p>".to_string(),
+            ..new_email()
+        };
+        let mime = format(&msg).await?;
+        assert!(mime.contains("Content-Type: multipart/alternative"));
+        assert!(mime.contains("Content-Type: text/plain; charset=utf-8"));
+        assert!(mime.contains("Content-Type: text/html; charset=utf-8"));
+        assert!(mime.contains("<p>Hi, Welcome to CRM!</p>"));
+
+        let msg = EmailMessage {
+            body: String::new(),
+            ..msg
+        };
+        let mime = format(&msg).await?;
+        assert!(!mime.contains("multipart"));
+        assert!(!mime.contains("text/plain"));
+        assert!(mime.contains("Content-Type: text/html; charset=utf-8"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn email_with_attachments_should_be_mixed() -> anyhow::Result<()> {
+        let msg = EmailMessage {
+            html_body: "<p>Hi</p>".to_string(),
+            attachments: vec![Attachment {
+                filename: "hello.txt".to_string(),
+                content_type: "text/plain".to_string(),
+                source: Some(Source::Content(b"hello world".to_vec())),
+            }],
+            ..new_email()
+        };
+        let mime = format(&msg).await?;
+        let mixed = mime.find("Content-Type: multipart/mixed").unwrap();
+        let alternative = mime.find("Content-Type: multipart/alternative").unwrap();
+        assert!(mixed < alternative);
+        assert!(mime.contains("Content-Disposition: attachment; filename=\"hello.txt\""));
+        assert!(mime.contains("hello world"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn url_attachment_should_not_be_fetched_without_fetcher() -> anyhow::Result<()> {
+        let msg = EmailMessage {
+            attachments: vec![Attachment {
+                filename: "report.csv".to_string(),
+                content_type: String::new(),
+                source: Some(Source::Url("http://127.0.0.1:1/report.csv".to_string())),
+            }],
+            ..new_email()
+        };
+        let mime = format(&msg).await?;
+        assert!(mime.contains("Content-Type: text/uri-list"));
+        assert!(mime.contains("http://127.0.0.1:1/report.csv"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn email_headers_should_be_set() -> anyhow::Result<()> {
+        let msg = EmailMessage {
+            reply_to: "support@crm.org".to_string(),
+            cc: vec!["alice@acme.org".to_string()],
+            bcc: vec!["bob@acme.org".to_string()],
+            headers: HashMap::from([(
+                "List-Unsubscribe".to_string(),
+                "<https://crm.org/unsubscribe>".to_string(),
+            )]),
+            ..new_email()
+        };
+        let message = msg.to_message(None).await?;
+        let recipients = message
+            .envelope()
+            .to()
+            .iter()
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec!["tyr@acme.org", "alice@acme.org", "bob@acme.org"],
+            recipients
+        );
+
+        let mime = String::from_utf8(message.formatted())?;
+        assert!(mime.contains("Reply-To: support@crm.org"));
+        assert!(mime.contains("Cc: alice@acme.org"));
+        assert!(!mime.contains("bob@acme.org"));
+        assert!(mime.contains("List-Unsubscribe: <https://crm.org/unsubscribe>"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn bcc_only_email_should_have_undisclosed_recipients() -> anyhow::Result<()> {
+        let msg = EmailMessage {
+            recipients: vec![],
+            bcc: vec!["bob@acme.org".to_string(), "alice@acme.org".to_string()],
+            ..new_email()
+        };
+        let message = msg.to_message(None).await?;
+        assert_eq!(2, message.envelope().to().len());
+        let mime = String::from_utf8(message.formatted())?;
+        assert!(mime.contains("To: undisclosed-recipients:;"));
+        assert!(!mime.contains("bob@acme.org"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn invalid_email_should_be_rejected() {
+        let invalid = [
+            EmailMessage {
+                sender: "admin".to_string(),
+                ..new_email()
+            },
+            EmailMessage {
+                cc: vec!["alice".to_string()],
+                ..new_email()
+            },
+            EmailMessage {
+                headers: HashMap::from([("Subject".to_string(), "Hi".to_string())]),
+                ..new_email()
+            },
+            EmailMessage {
+                headers: HashMap::from([("X-Tag".to_string(), "a\r\nBcc: x".to_string())]),
+                ..new_email()
+            },
+            EmailMessage {
+                attachments: vec![Attachment {
+                    filename: "hello.txt".to_string(),
+                    ..Default::default()
+                }],
+                ..new_email()
+            },
+        ];
+        for msg in invalid {
+            let e = msg.to_message(None).await.unwrap_err();
+            assert!(e.is::<Rejected>(), "{:?}", msg);
+        }
+    }
+}
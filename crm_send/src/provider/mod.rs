@@ -0,0 +1,161 @@
+use std::fmt;
+use std::sync::Arc;
+
+use tonic::async_trait;
+
+pub use dummy::DummyProvider;
+pub use fetch::AttachmentFetcher;
+pub use file::FileProvider;
+pub use http::HttpSmsProvider;
+pub use limit::{RateLimited, TokenBucket};
+pub use smtp::SmtpProvider;
+
+use crate::pb::{EmailMessage, InAppMessage, SendStatus, SmsMessage};
+use crate::{
+    EmailProviderConfig, InAppProviderConfig, ProvidersConfig, RateLimitsConfig, SmsProviderConfig,
+};
+
+mod dummy;
+mod fetch;
+mod file;
+mod http;
+mod limit;
+mod mime;
+mod smtp;
+
+// name of a provider which doesn't have its own
+const CUSTOM_PROVIDER: &str = "custom";
+
+/// a message accepted by a provider
+#[derive(Debug, Clone, PartialEq)]
+pub struct Delivery {
+    pub status: SendStatus,
+    pub provider_message_id: Option<String>,
+}
+
+/// a message the provider will never accept, e.g. an invalid address,
+/// any other error of a provider is reported as a failed delivery
+#[derive(Debug)]
+pub struct Rejected(pub String);
+
+/// deliver an email, e.g. through a smtp server
+#[async_trait]
+pub trait EmailProvider: Send + Sync + 'static {
+    async fn send(&self, msg: &EmailMessage) -> anyhow::Result<Delivery>;
+
+    /// rate limits of the channel are configured by provider name
+    fn name(&self) -> &'static str {
+        CUSTOM_PROVIDER
+    }
+}
+
+/// deliver a sms, e.g. through a http sms gateway
+#[async_trait]
+pub trait SmsProvider: Send + Sync + 'static {
+    async fn send(&self, msg: &SmsMessage) -> anyhow::Result<Delivery>;
+
+    /// rate limits of the channel are configured by provider name
+    fn name(&self) -> &'static str {
+        CUSTOM_PROVIDER
+    }
+}
+
+/// deliver an in-app message to a device
+#[async_trait]
+pub trait InAppProvider: Send + Sync + 'static {
+    async fn send(&self, msg: &InAppMessage) -> anyhow::Result<Delivery>;
+
+    /// rate limits of the channel are configured by provider name
+    fn name(&self) -> &'static str {
+        CUSTOM_PROVIDER
+    }
+}
+
+/// delivery providers used by NotificationService, one per channel
+#[derive(Clone)]
+pub struct Providers {
+    pub email: Arc<dyn EmailProvider>,
+    pub sms: Arc<dyn SmsProvider>,
+    pub in_app: Arc<dyn InAppProvider>,
+}
+
+impl Providers {
+    /// build the providers from config, channels without a config only log messages
+    pub fn try_new(config: &ProvidersConfig) -> anyhow::Result<Self> {
+        let dummy = DummyProvider::new();
+
+        let email: Arc<dyn EmailProvider> = match &config.email {
+            EmailProviderConfig::Dummy => Arc::new(dummy.clone()),
+            EmailProviderConfig::Smtp(smtp) => {
+                Arc::new(SmtpProvider::try_new(smtp, &config.attachments)?)
+            }
+            EmailProviderConfig::File(config) => Arc::new(FileProvider::try_new(&config.path)?),
+        };
+        let sms: Arc<dyn SmsProvider> = match &config.sms {
+            SmsProviderConfig::Dummy => Arc::new(dummy.clone()),
+            SmsProviderConfig::Http(config) => Arc::new(HttpSmsProvider::try_new(config)?),
+            SmsProviderConfig::File(config) => Arc::new(FileProvider::try_new(&config.path)?),
+        };
+        let in_app: Arc<dyn InAppProvider> = match &config.in_app {
+            InAppProviderConfig::Dummy => Arc::new(dummy),
+            InAppProviderConfig::File(config) => Arc::new(FileProvider::try_new(&config.path)?),
+        };
+
+        Ok(Self { email, sms, in_app })
+    }
+
+    pub fn with_email(mut self, provider: impl EmailProvider) -> Self {
+        self.email = Arc::new(provider);
+        self
+    }
+
+    pub fn with_sms(mut self, provider: impl SmsProvider) -> Self {
+        self.sms = Arc::new(provider);
+        self
+    }
+
+    pub fn with_in_app(mut self, provider: impl InAppProvider) -> Self {
+        self.in_app = Arc::new(provider);
+        self
+    }
+
+    /// throttle the calls of each provider with the limit of its channel and name
+    pub fn with_rate_limits(mut self, config: &RateLimitsConfig) -> Self {
+        if let Some(limit) = config.email.get(self.email.name()) {
+            self.email = Arc::new(RateLimited::new(self.email, limit));
+        }
+        if let Some(limit) = config.sms.get(self.sms.name()) {
+            self.sms = Arc::new(RateLimited::new(self.sms, limit));
+        }
+        if let Some(limit) = config.in_app.get(self.in_app.name()) {
+            self.in_app = Arc::new(RateLimited::new(self.in_app, limit));
+        }
+        self
+    }
+}
+
+impl Delivery {
+    /// delivered to the provider
+    pub fn sent(provider_message_id: Option<String>) -> Self {
+        Self {
+            status: SendStatus::Sent,
+            provider_message_id,
+        }
+    }
+
+    /// accepted but not delivered yet
+    pub fn queued() -> Self {
+        Self {
+            status: SendStatus::Queued,
+            provider_message_id: None,
+        }
+    }
+}
+
+impl fmt::Display for Rejected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Rejected: {}", self.0)
+    }
+}
+
+impl std::error::Error for Rejected {}
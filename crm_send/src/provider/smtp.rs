@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Tokio1Executor};
+use tonic::async_trait;
+
+use crate::pb::EmailMessage;
+use crate::provider::{AttachmentFetcher, Delivery, EmailProvider, Rejected};
+use crate::{AttachmentsConfig, SmtpConfig, SmtpTls};
+
+/// deliver EmailMessage to a smtp server
+pub struct SmtpProvider {
+    transport: AsyncSmtpTransport<Tokio1Executor>,
+    fetcher: AttachmentFetcher,
+}
+
+impl SmtpProvider {
+    pub fn try_new(config: &SmtpConfig, attachments: &AttachmentsConfig) -> anyhow::Result<Self> {
+        let builder = match config.tls {
+            SmtpTls::None => AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.host),
+            SmtpTls::StartTls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.host)?
+            }
+            SmtpTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.host)?,
+        };
+        let builder = builder
+            .port(config.port)
+            .timeout(Some(Duration::from_millis(config.timeout)));
+        let builder = match (&config.username, &config.password) {
+            (Some(username), Some(password)) => {
+                builder.credentials(Credentials::new(username.clone(), password.clone()))
+            }
+            _ => builder,
+        };
+        Ok(Self {
+            transport: builder.build(),
+            fetcher: AttachmentFetcher::try_new(attachments)?,
+        })
+    }
+}
+
+#[async_trait]
+impl EmailProvider for SmtpProvider {
+    async fn send(&self, msg: &EmailMessage) -> anyhow::Result<Delivery> {
+        let message = msg.to_message(Some(&self.fetcher)).await?;
+        let message_id = message.headers().get_raw("Message-ID").map(String::from);
+        match self.transport.send(message).await {
+            Ok(_) => Ok(Delivery::sent(message_id)),
+            // 5xx reply, e.g. unknown recipient
+            Err(e) if e.is_permanent() => Err(Rejected(e.to_string()).into()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+}
@@ -0,0 +1,149 @@
+// each test crate only uses a part of the helpers
+#![allow(dead_code)]
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use futures::StreamExt;
+use rand::{thread_rng, Rng};
+use tokio::time::sleep;
+use tonic::codegen::tokio_stream;
+use tonic::transport::Channel;
+use tonic::Request;
+
+use crm_send::pb::notification_client::NotificationClient;
+use crm_send::pb::{SendRequest, SendResponse};
+use crm_send::provider::Providers;
+use crm_send::{
+    AppConfig, AttachmentsConfig, EmailProviderConfig, NotificationService, RateLimitConfig,
+    SmtpConfig, WebhookConfig,
+};
+
+/// config of send.yml with the overrides of a test
+pub struct ConfigBuilder {
+    config: AppConfig,
+}
+
+impl ConfigBuilder {
+    pub fn new() -> anyhow::Result<Self> {
+        Ok(Self {
+            config: AppConfig::load()?,
+        })
+    }
+
+    pub fn smtp(mut self, smtp: SmtpConfig) -> Self {
+        self.config.providers.email = EmailProviderConfig::Smtp(smtp);
+        self
+    }
+
+    /// milliseconds in which a request with a known id is a duplicate
+    pub fn idempotency_window(mut self, idempotency_window: u64) -> Self {
+        self.config.outbox.idempotency_window = idempotency_window;
+        self
+    }
+
+    /// url attachments of emails are only fetched as this config allows
+    pub fn attachments(mut self, attachments: AttachmentsConfig) -> Self {
+        self.config.providers.attachments = attachments;
+        self
+    }
+
+    /// max recipients merged into one sms provider call
+    pub fn sms_batch(mut self, batch: u32) -> Self {
+        self.config.providers.batch.sms = batch;
+        self
+    }
+
+    /// calls per second of the sms provider with the name
+    pub fn sms_rate_limit(mut self, provider: &str, rate: f64, burst: Option<u32>) -> Self {
+        self.config
+            .providers
+            .rate_limits
+            .sms
+            .insert(provider.to_string(), RateLimitConfig { rate, burst });
+        self
+    }
+
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.config.outbox.workers = workers;
+        self
+    }
+
+    /// 1 reports a failure right away instead of retrying it
+    pub fn max_attempts(mut self, max_attempts: i32) -> Self {
+        self.config.outbox.max_attempts = max_attempts;
+        self
+    }
+
+    /// delays of the outbox in milliseconds, short ones keep retry tests fast
+    pub fn retry(mut self, backoff: u64, max_backoff: u64, poll_interval: u64) -> Self {
+        self.config.outbox.backoff = backoff;
+        self.config.outbox.max_backoff = max_backoff;
+        self.config.outbox.poll_interval = poll_interval;
+        self
+    }
+
+    pub fn webhook(mut self, port: u16, token: &str) -> Self {
+        self.config.webhook = Some(WebhookConfig {
+            port,
+            token: token.to_string(),
+        });
+        self
+    }
+
+    pub fn db_url(&self) -> &str {
+        &self.config.db_url
+    }
+
+    pub fn build(self) -> AppConfig {
+        self.config
+    }
+
+    pub async fn service(self) -> NotificationService {
+        NotificationService::new(self.build()).await
+    }
+
+    /// replace the providers of the config, e.g. with an in memory provider
+    pub async fn service_with(
+        self,
+        f: impl FnOnce(Providers) -> Providers,
+    ) -> anyhow::Result<NotificationService> {
+        let config = self.build();
+        let providers = f(Providers::try_new(&config.providers)?);
+        Ok(NotificationService::with_providers(config, providers).await)
+    }
+}
+
+/// serve the service on a random port of localhost
+pub async fn start_server(svc: NotificationService) -> anyhow::Result<SocketAddr> {
+    let port = thread_rng().gen_range(51000..65500);
+    let addr = format!("[::1]:{}", port).parse()?;
+
+    tokio::spawn(async move {
+        tonic::transport::Server::builder()
+            .add_service(svc.into_server())
+            .serve(addr)
+            .await
+            .unwrap();
+    });
+
+    sleep(Duration::from_millis(10)).await;
+
+    Ok(addr)
+}
+
+/// send the requests in one stream, a failed request fails the call
+pub async fn send(
+    client: &mut NotificationClient<Channel>,
+    reqs: Vec<SendRequest>,
+) -> anyhow::Result<Vec<SendResponse>> {
+    let res = client
+        .send(Request::new(tokio_stream::iter(reqs)))
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(res)
+}
@@ -0,0 +1,126 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::time::sleep;
+use tonic::async_trait;
+
+use common::{send, start_server, ConfigBuilder};
+use crm_send::pb::notification_client::NotificationClient;
+use crm_send::pb::{EmailMessage, SendRequest, SendStatus};
+use crm_send::provider::{Delivery, EmailProvider};
+
+mod common;
+
+#[tokio::test]
+async fn replayed_stream_should_not_be_delivered_twice() -> anyhow::Result<()> {
+    let provider = CountingProvider::default();
+    let svc = ConfigBuilder::new()?
+        .idempotency_window(60_000)
+        .workers(0)
+        .service_with(|p| p.with_email(provider.clone()))
+        .await?;
+    let addr = start_server(svc).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let reqs = (0..3)
+        .map(|i| new_email(&format!("Welcome {}", i)))
+        .collect::<Vec<_>>();
+    let res = send(&mut client, reqs.clone()).await?;
+    assert_eq!(3, res.len());
+    assert_eq!(3, provider.count());
+
+    // the client retries the whole stream after a network error
+    let replayed = send(&mut client, reqs).await?;
+    assert_eq!(res, replayed);
+    assert_eq!(3, provider.count());
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_after_idempotency_window_should_be_delivered_again() -> anyhow::Result<()> {
+    let provider = CountingProvider::default();
+    let svc = ConfigBuilder::new()?
+        .idempotency_window(0)
+        .workers(0)
+        .service_with(|p| p.with_email(provider.clone()))
+        .await?;
+    let addr = start_server(svc).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let req = new_email("Welcome");
+    let res = send(&mut client, vec![req.clone()]).await?;
+    let replayed = send(&mut client, vec![req]).await?;
+
+    assert_eq!(res[0].id, replayed[0].id);
+    assert_eq!(2, provider.count());
+    Ok(())
+}
+
+#[tokio::test]
+async fn request_in_flight_should_not_be_replaced() -> anyhow::Result<()> {
+    let provider = CountingProvider::slow(Duration::from_millis(300));
+    let svc = ConfigBuilder::new()?
+        .idempotency_window(0)
+        .workers(0)
+        .service_with(|p| p.with_email(provider.clone()))
+        .await?;
+    let addr = start_server(svc).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    // the window passed, but the first attempt is still in progress
+    let req = new_email("Welcome");
+    let first = {
+        let mut client = client.clone();
+        let req = req.clone();
+        tokio::spawn(async move { send(&mut client, vec![req]).await })
+    };
+    sleep(Duration::from_millis(100)).await;
+    let replayed = send(&mut client, vec![req]).await?;
+    assert_eq!(SendStatus::Queued as i32, replayed[0].status);
+
+    let res = first.await??;
+    assert_eq!(SendStatus::Sent as i32, res[0].status);
+    assert_eq!(1, provider.count());
+    Ok(())
+}
+
+/// count delivered emails, each gets a new provider message id.
+/// services of these tests run without workers, which may pick up messages left by
+/// other tests, so only the first attempts are counted
+#[derive(Default, Clone)]
+struct CountingProvider {
+    count: Arc<AtomicUsize>,
+    delay: Duration,
+}
+
+impl CountingProvider {
+    fn slow(delay: Duration) -> Self {
+        Self {
+            delay,
+            ..Default::default()
+        }
+    }
+
+    fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl EmailProvider for CountingProvider {
+    async fn send(&self, _msg: &EmailMessage) -> anyhow::Result<Delivery> {
+        sleep(self.delay).await;
+        let n = self.count.fetch_add(1, Ordering::SeqCst);
+        Ok(Delivery::sent(Some(format!("email-{}", n))))
+    }
+}
+
+fn new_email(subject: &str) -> SendRequest {
+    SendRequest::new_email(
+        subject,
+        "admin@crm.org",
+        &["tyr@acme.org".to_string()],
+        "Hi, Welcome to CRM!",
+    )
+}
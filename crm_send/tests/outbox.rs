@@ -0,0 +1,295 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::Duration;
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use prost::Message;
+use prost_types::Timestamp;
+use sqlx::PgPool;
+use tokio::net::TcpListener;
+use tokio::time::sleep;
+use tonic::async_trait;
+use tonic::codegen::tokio_stream;
+
+use common::ConfigBuilder;
+use crm_send::abi::webhook::{DeliveryEvent, DeliveryEventStatus, DeliveryEventsResponse};
+use crm_send::pb::{CancelRequest, InAppMessage, SendRequest, SendStatus};
+use crm_send::provider::{Delivery, InAppProvider};
+use crm_send::NotificationService;
+
+mod common;
+
+#[tokio::test]
+async fn failed_message_should_be_retried() -> anyhow::Result<()> {
+    let (svc, pool) = start_service().await?;
+
+    let flaky = SendRequest::new_in_app("fail-2", "Hi", "Hi");
+    let broken = SendRequest::new_in_app("fail-always", "Hi", "Hi");
+    let stream = tokio_stream::iter(vec![Ok(flaky.clone()), Ok(broken.clone())]);
+    let res = svc
+        .send(stream)
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // the first attempt failed, both are persisted and retried later
+    assert_eq!(2, res.len());
+    assert_eq!(SendStatus::Queued as i32, res[0].status);
+    assert_eq!("device is offline", res[0].error);
+    assert_eq!(SendStatus::Queued as i32, res[1].status);
+
+    let (status, attempts, error) = wait_for(&pool, &flaky.id, "sent").await?;
+    assert_eq!("sent", status);
+    assert_eq!(3, attempts);
+    assert_eq!(None, error);
+
+    let (status, attempts, error) = wait_for(&pool, &broken.id, "dead").await?;
+    assert_eq!("dead", status);
+    assert_eq!(3, attempts);
+    assert_eq!(Some("device is offline".to_string()), error);
+
+    // a duplicate request gets the result of the last attempt
+    let stream = tokio_stream::iter(vec![Ok(flaky), Ok(broken)]);
+    let res = svc
+        .send(stream)
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(SendStatus::Sent as i32, res[0].status);
+    assert_eq!(SendStatus::Failed as i32, res[1].status);
+    assert_eq!("device is offline", res[1].error);
+    Ok(())
+}
+
+#[tokio::test]
+async fn message_locked_by_dead_worker_should_be_retried() -> anyhow::Result<()> {
+    let (_svc, pool) = start_service().await?;
+
+    // a worker claimed the message and died before it's delivered
+    let req = SendRequest::new_in_app("device-1", "Hi", "Hi");
+    sqlx::query(
+        "INSERT INTO outbox(id, channel, payload, status, attempts, next_attempt_at) \
+         VALUES ($1, 'in_app', $2, 'sending', 1, now() - interval '1 second')",
+    )
+    .bind(&req.id)
+    .bind(req.encode_to_vec())
+    .execute(&pool)
+    .await?;
+
+    let (status, attempts, _) = wait_for(&pool, &req.id, "sent").await?;
+    assert_eq!("sent", status);
+    assert_eq!(2, attempts);
+    Ok(())
+}
+
+#[tokio::test]
+async fn scheduled_message_should_be_sent_on_time() -> anyhow::Result<()> {
+    let (svc, pool) = start_service().await?;
+
+    let send_at = Utc::now() + chrono::Duration::milliseconds(500);
+    let scheduled = SendRequest::new_in_app("device-1", "Hi", "Hi").with_send_at(to_ts(send_at));
+    let cancelled = SendRequest::new_in_app("device-1", "Hi", "Hi")
+        .with_send_at(to_ts(Utc::now() + chrono::Duration::hours(1)));
+    let expired = SendRequest::new_in_app("device-1", "Hi", "Hi")
+        .with_expires_at(to_ts(Utc::now() - chrono::Duration::seconds(1)));
+    let stream = tokio_stream::iter(vec![
+        Ok(scheduled.clone()),
+        Ok(cancelled.clone()),
+        Ok(expired),
+    ]);
+    let res = svc
+        .send(stream)
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    assert_eq!(SendStatus::Scheduled as i32, res[0].status);
+    assert_eq!(SendStatus::Scheduled as i32, res[1].status);
+    assert_eq!(SendStatus::Expired as i32, res[2].status);
+
+    let req = CancelRequest {
+        ids: vec![cancelled.id.clone(), "unknown".to_string()],
+    };
+    let res = svc.cancel(req).await?.into_inner();
+    assert_eq!(vec![cancelled.id.clone()], res.cancelled);
+
+    let (status, attempts, _) = wait_for(&pool, &scheduled.id, "sent").await?;
+    assert!(Utc::now() >= send_at);
+    assert_eq!(("sent".to_string(), 1), (status, attempts));
+    let (status, attempts, _) = wait_for(&pool, &cancelled.id, "cancelled").await?;
+    assert_eq!(("cancelled".to_string(), 0), (status, attempts));
+
+    // a sent message can't be cancelled
+    let req = CancelRequest {
+        ids: vec![scheduled.id.clone()],
+    };
+    assert!(svc.cancel(req).await?.into_inner().cancelled.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn expired_message_should_be_dropped() -> anyhow::Result<()> {
+    let (_svc, pool) = start_service().await?;
+
+    // the message is released after it expired, e.g. no worker was running
+    let req = SendRequest::new_in_app("device-1", "Hi", "Hi");
+    sqlx::query(
+        "INSERT INTO outbox(id, channel, payload, status, attempts, next_attempt_at, expires_at) \
+         VALUES ($1, 'in_app', $2, 'scheduled', 0, now() - interval '2 seconds', \
+         now() - interval '1 second')",
+    )
+    .bind(&req.id)
+    .bind(req.encode_to_vec())
+    .execute(&pool)
+    .await?;
+
+    let (status, attempts, error) = wait_for(&pool, &req.id, "expired").await?;
+    assert_eq!("expired", status);
+    assert_eq!(1, attempts);
+    assert_eq!(Some("Message expired".to_string()), error);
+    Ok(())
+}
+
+#[tokio::test]
+async fn delivery_callback_should_not_be_overwritten() -> anyhow::Result<()> {
+    let (svc, pool) = start_service().await?;
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let url = format!("http://{}/webhooks/delivery", listener.local_addr()?);
+    let router = svc.webhook_router();
+    tokio::spawn(async move { axum::serve(listener, router).await });
+
+    // the callback arrives before the first attempt records the message as sent
+    let slow = SendRequest::new_in_app("slow-300", "Hi", "Hi");
+    let tomorrow = Utc::now() + chrono::Duration::days(1);
+    let scheduled = SendRequest::new_in_app("device-1", "Hi", "Hi").with_send_at(to_ts(tomorrow));
+    let stream = tokio_stream::iter(vec![Ok(scheduled.clone())]);
+    svc.send(stream)
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+    let sending = {
+        let svc = svc.clone();
+        let stream = tokio_stream::iter(vec![Ok(slow.clone())]);
+        tokio::spawn(async move { svc.send(stream).await.unwrap().into_inner().next().await })
+    };
+    wait_for(&pool, &slow.id, "sending").await?;
+
+    let events = [&slow.id, &scheduled.id]
+        .into_iter()
+        .map(|id| DeliveryEvent {
+            id: id.clone(),
+            status: DeliveryEventStatus::Delivered,
+            reason: None,
+        })
+        .collect::<Vec<_>>();
+    let res = reqwest::Client::new()
+        .post(&url)
+        .bearer_auth("token")
+        .json(&events)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<DeliveryEventsResponse>()
+        .await?;
+    // a scheduled message isn't handed to the provider yet
+    assert_eq!(1, res.updated);
+
+    let res = sending.await?.unwrap()?;
+    assert_eq!(SendStatus::Sent as i32, res.status);
+    let (status, _, _) = wait_for(&pool, &slow.id, "delivered").await?;
+    assert_eq!("delivered", status);
+    let (status, _, _) = wait_for(&pool, &scheduled.id, "scheduled").await?;
+    assert_eq!("scheduled", status);
+    Ok(())
+}
+
+/// fail messages sent to a `fail-<n>` device n times, `fail-always` never succeeds.
+/// `slow-<ms>` takes ms milliseconds to succeed
+#[derive(Default, Clone)]
+struct FlakyProvider {
+    attempts: Arc<Mutex<HashMap<String, usize>>>,
+}
+
+impl FlakyProvider {
+    // workers of every service in this test claim each other's messages,
+    // so they share the attempts
+    fn shared() -> Self {
+        static PROVIDER: OnceLock<FlakyProvider> = OnceLock::new();
+        PROVIDER.get_or_init(Default::default).clone()
+    }
+}
+
+#[async_trait]
+impl InAppProvider for FlakyProvider {
+    async fn send(&self, msg: &InAppMessage) -> anyhow::Result<Delivery> {
+        if let Some(ms) = msg.device_id.strip_prefix("slow-") {
+            sleep(Duration::from_millis(ms.parse()?)).await;
+            return Ok(Delivery::sent(None));
+        }
+        let mut attempts = self.attempts.lock().unwrap();
+        let attempt = attempts.entry(msg.device_id.clone()).or_default();
+        *attempt += 1;
+        let failures = match msg.device_id.strip_prefix("fail-") {
+            Some("always") => usize::MAX,
+            Some(n) => n.parse()?,
+            None => 0,
+        };
+        if *attempt <= failures {
+            return Err(anyhow!("device is offline"));
+        }
+        Ok(Delivery::sent(None))
+    }
+}
+
+async fn start_service() -> anyhow::Result<(NotificationService, PgPool)> {
+    let builder = ConfigBuilder::new()?
+        .workers(2)
+        .max_attempts(3)
+        .retry(10, 100, 10)
+        .webhook(0, "token");
+    let pool = PgPool::connect(builder.db_url()).await?;
+    let svc = builder
+        .service_with(|p| p.with_in_app(FlakyProvider::shared()))
+        .await?;
+    Ok((svc, pool))
+}
+
+// poll the outbox until the message reaches the status, or 5 seconds passed
+async fn wait_for(
+    pool: &PgPool,
+    id: &str,
+    status: &str,
+) -> anyhow::Result<(String, i32, Option<String>)> {
+    for _ in 0..500 {
+        // the message may not be persisted yet
+        let row: Option<(String, i32, Option<String>)> =
+            sqlx::query_as("SELECT status::text, attempts, last_error FROM outbox WHERE id = $1")
+                .bind(id)
+                .fetch_optional(pool)
+                .await?;
+        if let Some(row) = row.filter(|row| row.0 == status) {
+            return Ok(row);
+        }
+        sleep(Duration::from_millis(10)).await;
+    }
+    Err(anyhow!("Message {} didn't become {}", id, status))
+}
+
+fn to_ts(t: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: t.timestamp(),
+        nanos: t.timestamp_subsec_nanos() as i32,
+    }
+}
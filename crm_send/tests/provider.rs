@@ -0,0 +1,246 @@
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use futures::StreamExt;
+use tonic::async_trait;
+use tonic::codegen::tokio_stream;
+use uuid::Uuid;
+
+use common::{send, start_server, ConfigBuilder};
+use crm_send::pb::notification_client::NotificationClient;
+use crm_send::pb::{SendRequest, SendStatus, SmsMessage};
+use crm_send::provider::{Delivery, Rejected, SmsProvider};
+use crm_send::{
+    AppConfig, EmailProviderConfig, FileProviderConfig, InAppProviderConfig, NotificationService,
+};
+
+mod common;
+
+#[tokio::test]
+async fn custom_provider_should_work() -> anyhow::Result<()> {
+    let sms = MemorySmsProvider::default();
+    let svc = ConfigBuilder::new()?
+        .service_with(|p| p.with_sms(sms.clone()))
+        .await?;
+    let addr = start_server(svc).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let req = SendRequest::new_sms("10086", &["13800000000".to_string()], "Hello");
+    let res = send(&mut client, vec![req]).await?;
+
+    assert_eq!(1, res.len());
+    assert_eq!(SendStatus::Sent as i32, res[0].status);
+    assert_eq!("sms-1", res[0].provider_message_id);
+    let msgs = sms.msgs.lock().unwrap();
+    assert_eq!(1, msgs.len());
+    assert_eq!("Hello", msgs[0].body);
+    Ok(())
+}
+
+#[tokio::test]
+async fn file_provider_should_work() -> anyhow::Result<()> {
+    let path = std::env::temp_dir().join(format!("crm_send_{}.jsonl", Uuid::new_v4()));
+    let mut config = AppConfig::load()?;
+    config.providers.email = EmailProviderConfig::File(FileProviderConfig { path: path.clone() });
+    config.providers.in_app = InAppProviderConfig::File(FileProviderConfig { path: path.clone() });
+    let addr = start_server(NotificationService::new(config).await).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let req = SendRequest::new_email(
+        "Welcome",
+        "admin@crm.org",
+        &["tyr@acme.org".to_string()],
+        "Hi, Welcome to CRM!",
+    );
+    let res = send(&mut client, vec![req]).await?;
+    assert_eq!(SendStatus::Sent as i32, res[0].status);
+    let req = SendRequest::new_in_app("device-1", "Welcome", "Hi, Welcome to CRM!");
+    let res = send(&mut client, vec![req]).await?;
+    assert_eq!(SendStatus::Sent as i32, res[0].status);
+    // an invalid email isn't written
+    let req = SendRequest::new_email(
+        "Welcome",
+        "admin",
+        &["tyr@acme.org".to_string()],
+        "Hi, Welcome to CRM!",
+    );
+    let res = send(&mut client, vec![req]).await?;
+    assert_eq!(SendStatus::Rejected as i32, res[0].status);
+
+    let content = tokio::fs::read_to_string(&path).await?;
+    tokio::fs::remove_file(&path).await?;
+    let lines = content
+        .lines()
+        .map(serde_json::from_str::<serde_json::Value>)
+        .collect::<Result<Vec<_>, _>>()?;
+    assert_eq!(2, lines.len());
+    assert_eq!("email", lines[0]["channel"]);
+    assert_eq!("Welcome", lines[0]["msg"]["subject"]);
+    assert_eq!("in_app", lines[1]["channel"]);
+    assert_eq!("device-1", lines[1]["msg"]["device_id"]);
+    Ok(())
+}
+
+#[tokio::test]
+async fn sms_should_be_batched() -> anyhow::Result<()> {
+    let sms = MemorySmsProvider::default();
+    let svc = ConfigBuilder::new()?
+        .workers(0)
+        .sms_batch(4)
+        .service_with(|p| p.with_sms(sms.clone()))
+        .await?;
+
+    let recipients = (0..10)
+        .map(|i| format!("138000000{:02}", i))
+        .collect::<Vec<_>>();
+    let mut reqs = recipients[..9]
+        .iter()
+        .map(|r| SendRequest::new_sms("10086", std::slice::from_ref(r), "Hello"))
+        .collect::<Vec<_>>();
+    // a different message isn't merged
+    reqs.insert(5, SendRequest::new_sms("10086", &recipients[9..], "Hi"));
+    let ids = reqs.iter().map(|r| r.id.clone()).collect::<Vec<_>>();
+    let res = svc
+        .send(tokio_stream::iter(reqs.into_iter().map(Ok)))
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    // responses are in the order of requests
+    assert_eq!(ids, res.iter().map(|r| r.id.clone()).collect::<Vec<_>>());
+    assert!(res.iter().all(|r| r.status == SendStatus::Sent as i32));
+    let msgs = sms.msgs.lock().unwrap();
+    let batches = msgs
+        .iter()
+        .map(|m| (m.body.as_str(), m.recipients.len()))
+        .collect::<Vec<_>>();
+    // up to 4 requests are taken at once, then split by message
+    assert_eq!(
+        vec![
+            ("Hello", 4),
+            ("Hello", 1),
+            ("Hi", 1),
+            ("Hello", 2),
+            ("Hello", 2)
+        ],
+        batches
+    );
+    // a batch shares the provider message id
+    assert_eq!(res[0].provider_message_id, res[3].provider_message_id);
+    assert_ne!(res[0].provider_message_id, res[4].provider_message_id);
+    Ok(())
+}
+
+#[tokio::test]
+async fn rejected_batch_should_be_sent_one_by_one() -> anyhow::Result<()> {
+    let sms = MemorySmsProvider::default();
+    let svc = ConfigBuilder::new()?
+        .workers(0)
+        .sms_batch(4)
+        .service_with(|p| p.with_sms(sms.clone()))
+        .await?;
+
+    let reqs = ["13800000000", "invalid", "13800000001"]
+        .into_iter()
+        .map(|r| SendRequest::new_sms("10086", &[r.to_string()], "Hello"))
+        .collect::<Vec<_>>();
+    let res = svc
+        .send(tokio_stream::iter(reqs.into_iter().map(Ok)))
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await
+        .into_iter()
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let status = res.iter().map(|r| r.status).collect::<Vec<_>>();
+    assert_eq!(
+        vec![
+            SendStatus::Sent as i32,
+            SendStatus::Rejected as i32,
+            SendStatus::Sent as i32
+        ],
+        status
+    );
+    assert_eq!("Invalid number", res[1].error);
+    // the valid ones are sent on their own
+    assert_eq!(2, sms.msgs.lock().unwrap().len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn provider_should_be_rate_limited() -> anyhow::Result<()> {
+    let sms = MemorySmsProvider::default();
+    let svc = ConfigBuilder::new()?
+        .workers(0)
+        .sms_rate_limit("memory", 10.0, Some(1))
+        .service_with(|p| p.with_sms(sms.clone()))
+        .await?;
+
+    let reqs = (0..4)
+        .map(|_| SendRequest::new_sms("10086", &["13800000000".to_string()], "Hi"))
+        .collect::<Vec<_>>();
+    let start = Instant::now();
+    let res = svc
+        .send(tokio_stream::iter(reqs.into_iter().map(Ok)))
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+
+    // the first call takes the burst, then a call every 100ms
+    assert!(start.elapsed() >= Duration::from_millis(290));
+    assert_eq!(4, res.len());
+    assert_eq!(4, sms.msgs.lock().unwrap().len());
+    Ok(())
+}
+
+#[tokio::test]
+async fn limit_of_another_provider_should_not_apply() -> anyhow::Result<()> {
+    let sms = MemorySmsProvider::default();
+    let svc = ConfigBuilder::new()?
+        .workers(0)
+        .sms_rate_limit("http", 1.0, Some(1))
+        .service_with(|p| p.with_sms(sms.clone()))
+        .await?;
+
+    let reqs = (0..3)
+        .map(|_| SendRequest::new_sms("10086", &["13800000000".to_string()], "Hi"))
+        .collect::<Vec<_>>();
+    let start = Instant::now();
+    let res = svc
+        .send(tokio_stream::iter(reqs.into_iter().map(Ok)))
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+
+    assert!(start.elapsed() < Duration::from_millis(900));
+    assert_eq!(3, res.len());
+    Ok(())
+}
+
+/// keep sms in memory, a sms to an `invalid` recipient is rejected
+#[derive(Default, Clone)]
+struct MemorySmsProvider {
+    msgs: Arc<Mutex<Vec<SmsMessage>>>,
+}
+
+#[async_trait]
+impl SmsProvider for MemorySmsProvider {
+    async fn send(&self, msg: &SmsMessage) -> anyhow::Result<Delivery> {
+        if msg.recipients.iter().any(|r| r == "invalid") {
+            return Err(Rejected("Invalid number".to_string()).into());
+        }
+        let mut msgs = self.msgs.lock().unwrap();
+        msgs.push(msg.clone());
+        Ok(Delivery::sent(Some(format!("sms-{}", msgs.len()))))
+    }
+
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+}
@@ -1,32 +1,33 @@
-use std::net::SocketAddr;
-use std::time::Duration;
-
+use common::{start_server, ConfigBuilder};
 use crm_send::pb::notification_client::NotificationClient;
 use crm_send::pb::{EmailMessage, InAppMessage, SendRequest, SmsMessage};
-use crm_send::{AppConfig, NotificationService};
 use futures::StreamExt;
-use tokio::time::sleep;
 use tonic::codegen::tokio_stream;
 use tonic::Request;
 use uuid::Uuid;
 
+mod common;
+
 #[tokio::test]
 async fn send_should_work() -> anyhow::Result<()> {
-    let addr = start_server().await?;
+    let addr = start_server(ConfigBuilder::new()?.service().await).await?;
     let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
     let stream = tokio_stream::iter(
         vec![
             SendRequest {
                 id: Uuid::new_v4().to_string(),
                 msg: Some(EmailMessage::new().into()),
+                ..Default::default()
             },
             SendRequest {
                 id: Uuid::new_v4().to_string(),
                 msg: Some(SmsMessage::new().into()),
+                ..Default::default()
             },
             SendRequest {
                 id: Uuid::new_v4().to_string(),
                 msg: Some(InAppMessage::new().into()),
+                ..Default::default()
             },
         ]
         .into_iter(),
@@ -39,21 +40,3 @@ async fn send_should_work() -> anyhow::Result<()> {
     assert_eq!(3, res.len());
     Ok(())
 }
-
-async fn start_server() -> anyhow::Result<SocketAddr> {
-    let config = AppConfig::load()?;
-    let addr = format!("[::1]:{}", config.server.port).parse()?;
-
-    let svc = NotificationService::new(config);
-    tokio::spawn(async move {
-        tonic::transport::Server::builder()
-            .add_service(svc.into_server())
-            .serve(addr)
-            .await
-            .unwrap();
-    });
-
-    sleep(Duration::from_micros(1)).await;
-
-    Ok(addr)
-}
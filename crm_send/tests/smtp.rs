@@ -0,0 +1,380 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use axum::http::header;
+use axum::routing::get;
+use axum::Router;
+use futures::StreamExt;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpListener;
+use tonic::codegen::tokio_stream;
+use tonic::Request;
+
+use common::{start_server, ConfigBuilder};
+use crm_send::pb::attachment::Source;
+use crm_send::pb::notification_client::NotificationClient;
+use crm_send::pb::send_request::Msg;
+use crm_send::pb::{Attachment, EmailMessage, SendRequest, SendStatus};
+use crm_send::{AttachmentsConfig, SmtpConfig, SmtpTls};
+
+mod common;
+
+#[tokio::test]
+async fn send_email_with_smtp_should_work() -> anyhow::Result<()> {
+    let smtp = FakeSmtpServer::start(false).await?;
+    let svc = ConfigBuilder::new()?
+        .smtp(smtp.config())
+        .attachments(local_attachments())
+        .max_attempts(1)
+        .service()
+        .await;
+    let addr = start_server(svc).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let req = SendRequest::new_email(
+        "Welcome",
+        "admin@crm.org",
+        &["tyr@acme.org".to_string(), "alice@acme.org".to_string()],
+        "Hi, Welcome to CRM!",
+    );
+    let id = req.id.clone();
+    let res = client
+        .send(Request::new(tokio_stream::iter(vec![req])))
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(1, res.len());
+    let res = res[0].as_ref().unwrap();
+    assert_eq!(id, res.id);
+    assert_eq!(SendStatus::Sent as i32, res.status);
+
+    let mails = smtp.mails();
+    assert_eq!(1, mails.len());
+    assert_eq!("<admin@crm.org>", mails[0].from);
+    assert_eq!(vec!["<tyr@acme.org>", "<alice@acme.org>"], mails[0].to);
+    assert!(mails[0].data.contains("Subject: Welcome"));
+    assert!(mails[0].data.contains("Hi, Welcome to CRM!"));
+    assert!(mails[0]
+        .data
+        .contains(&format!("Message-ID: {}", res.provider_message_id)));
+    Ok(())
+}
+
+#[tokio::test]
+async fn send_multipart_email_with_smtp_should_work() -> anyhow::Result<()> {
+    let smtp = FakeSmtpServer::start(false).await?;
+    let svc = ConfigBuilder::new()?
+        .smtp(smtp.config())
+        .attachments(local_attachments())
+        .max_attempts(1)
+        .service()
+        .await;
+    let addr = start_server(svc).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    // serve the url attachment
+    let files_addr = serve_files().await?;
+
+    let mut req = SendRequest::new_email(
+        "Welcome",
+        "admin@crm.org",
+        &["tyr@acme.org".to_string()],
+        "Hi, Welcome to CRM!",
+    );
+    if let Some(Msg::Email(email)) = req.msg.as_mut() {
+        *email = EmailMessage {
+            html_body: "<h1>Hi, Welcome to CRM!</h1>".to_string(),
+            reply_to: "support@crm.org".to_string(),
+            cc: vec!["alice@acme.org".to_string()],
+            bcc: vec!["bob@acme.org".to_string()],
+            headers: HashMap::from([("X-Campaign".to_string(), "welcome".to_string())]),
+            attachments: vec![
+                Attachment {
+                    filename: "hello.txt".to_string(),
+                    content_type: "text/plain".to_string(),
+                    source: Some(Source::Content(b"hello world".to_vec())),
+                },
+                Attachment {
+                    filename: "report.csv".to_string(),
+                    content_type: String::new(),
+                    source: Some(Source::Url(format!("http://{}/report.csv", files_addr))),
+                },
+            ],
+            ..email.clone()
+        };
+    }
+    let res = client
+        .send(Request::new(tokio_stream::iter(vec![req])))
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+    let res = res[0].as_ref().unwrap();
+    assert_eq!(SendStatus::Sent as i32, res.status, "{}", res.error);
+
+    let mails = smtp.mails();
+    assert_eq!(1, mails.len());
+    assert_eq!(
+        vec!["<tyr@acme.org>", "<alice@acme.org>", "<bob@acme.org>"],
+        mails[0].to
+    );
+    let data = &mails[0].data;
+    assert!(data.contains("Reply-To: support@crm.org"));
+    assert!(data.contains("Cc: alice@acme.org"));
+    assert!(!data.contains("Bcc:"));
+    assert!(data.contains("X-Campaign: welcome"));
+    assert!(data.contains("Content-Type: multipart/mixed"));
+    assert!(data.contains("Content-Type: multipart/alternative"));
+    assert!(data.contains("Content-Type: text/plain; charset=utf-8"));
+    assert!(data.contains("Content-Type: text/html; charset=utf-8"));
+    assert!(data.contains("<h1>Hi, Welcome to CRM!</h1>"));
+    assert!(data.contains("Content-Disposition: attachment; filename=\"hello.txt\""));
+    assert!(data.contains("Content-Disposition: attachment; filename=\"report.csv\""));
+    assert!(data.contains("Content-Type: text/csv"));
+    assert!(data.contains("1,100"));
+    Ok(())
+}
+
+#[tokio::test]
+async fn send_email_refused_by_smtp_should_be_rejected() -> anyhow::Result<()> {
+    let smtp = FakeSmtpServer::start(true).await?;
+    let svc = ConfigBuilder::new()?
+        .smtp(smtp.config())
+        .attachments(local_attachments())
+        .max_attempts(1)
+        .service()
+        .await;
+    let addr = start_server(svc).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let req = SendRequest::new_email(
+        "Welcome",
+        "admin@crm.org",
+        &["tyr@acme.org".to_string()],
+        "Hi, Welcome to CRM!",
+    );
+    let res = client
+        .send(Request::new(tokio_stream::iter(vec![req])))
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(1, res.len());
+    let res = res[0].as_ref().unwrap();
+    assert_eq!(SendStatus::Rejected as i32, res.status);
+    assert!(res.error.contains("No such user"));
+    assert!(smtp.mails().is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn send_email_with_smtp_down_should_fail() -> anyhow::Result<()> {
+    // nothing listens on the port once the listener is dropped
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let smtp_addr = listener.local_addr()?;
+    drop(listener);
+    let smtp = SmtpConfig {
+        host: smtp_addr.ip().to_string(),
+        port: smtp_addr.port(),
+        tls: SmtpTls::None,
+        username: None,
+        password: None,
+        timeout: 60_000,
+    };
+    let svc = ConfigBuilder::new()?
+        .smtp(smtp)
+        .attachments(local_attachments())
+        .max_attempts(1)
+        .service()
+        .await;
+    let addr = start_server(svc).await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let req = SendRequest::new_email(
+        "Welcome",
+        "admin@crm.org",
+        &["tyr@acme.org".to_string()],
+        "Hi, Welcome to CRM!",
+    );
+    let res = client
+        .send(Request::new(tokio_stream::iter(vec![req])))
+        .await?
+        .into_inner()
+        .collect::<Vec<_>>()
+        .await;
+
+    assert_eq!(1, res.len());
+    let res = res[0].as_ref().unwrap();
+    assert_eq!(SendStatus::Failed as i32, res.status);
+    assert!(!res.error.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn unsafe_attachment_should_be_rejected() -> anyhow::Result<()> {
+    let files_addr = serve_files().await?;
+    let url = format!("http://{}/report.csv", files_addr);
+    let cases = [
+        // loopback isn't allowed by default
+        (AttachmentsConfig::default(), url.clone()),
+        (
+            AttachmentsConfig::default(),
+            format!("http://localhost:{}/report.csv", files_addr.port()),
+        ),
+        (local_attachments(), "file:///etc/passwd".to_string()),
+        (
+            AttachmentsConfig {
+                max_attachment_bytes: 4,
+                ..local_attachments()
+            },
+            url,
+        ),
+    ];
+    for (attachments, url) in cases {
+        let smtp = FakeSmtpServer::start(false).await?;
+        let svc = ConfigBuilder::new()?
+            .smtp(smtp.config())
+            .attachments(attachments)
+            .max_attempts(1)
+            .service()
+            .await;
+        let addr = start_server(svc).await?;
+        let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+        let mut req = SendRequest::new_email(
+            "Welcome",
+            "admin@crm.org",
+            &["tyr@acme.org".to_string()],
+            "Hi, Welcome to CRM!",
+        );
+        if let Some(Msg::Email(email)) = req.msg.as_mut() {
+            email.attachments = vec![Attachment {
+                filename: "report.csv".to_string(),
+                content_type: String::new(),
+                source: Some(Source::Url(url.clone())),
+            }];
+        }
+        let res = client
+            .send(Request::new(tokio_stream::iter(vec![req])))
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await;
+        let res = res[0].as_ref().unwrap();
+        assert_eq!(SendStatus::Rejected as i32, res.status, "{}", url);
+        assert!(smtp.mails().is_empty());
+    }
+    Ok(())
+}
+
+// serve /report.csv on a local port
+async fn serve_files() -> anyhow::Result<SocketAddr> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let addr = listener.local_addr()?;
+    let router = Router::new().route(
+        "/report.csv",
+        get(|| async { ([(header::CONTENT_TYPE, "text/csv")], "id,views\n1,100\n") }),
+    );
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    Ok(addr)
+}
+
+// attachments served by serve_files are allowed
+fn local_attachments() -> AttachmentsConfig {
+    AttachmentsConfig {
+        allowed_hosts: vec!["127.0.0.1".to_string()],
+        ..Default::default()
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+struct Mail {
+    from: String,
+    to: Vec<String>,
+    data: String,
+}
+
+/// a minimal smtp server which keeps mails in memory
+struct FakeSmtpServer {
+    addr: SocketAddr,
+    mails: Arc<Mutex<Vec<Mail>>>,
+}
+
+impl FakeSmtpServer {
+    // reject all recipients if `reject` is true
+    async fn start(reject: bool) -> anyhow::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let mails = Arc::new(Mutex::new(Vec::new()));
+
+        let mails_clone = mails.clone();
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                let mails = mails_clone.clone();
+                tokio::spawn(async move {
+                    let (reader, mut writer) = stream.into_split();
+                    let mut lines = BufReader::new(reader).lines();
+                    let mut mail = Mail::default();
+                    writer.write_all(b"220 localhost ESMTP\r\n").await?;
+                    while let Some(line) = lines.next_line().await? {
+                        let cmd = line.to_uppercase();
+                        let reply: &[u8] = if cmd.starts_with("EHLO") || cmd.starts_with("HELO") {
+                            b"250 localhost\r\n"
+                        } else if cmd.starts_with("MAIL FROM:") {
+                            mail.from = line[10..].trim().to_string();
+                            b"250 OK\r\n"
+                        } else if cmd.starts_with("RCPT TO:") {
+                            if reject {
+                                b"550 No such user\r\n"
+                            } else {
+                                mail.to.push(line[8..].trim().to_string());
+                                b"250 OK\r\n"
+                            }
+                        } else if cmd == "DATA" {
+                            writer
+                                .write_all(b"354 End data with <CR><LF>.<CR><LF>\r\n")
+                                .await?;
+                            while let Some(line) = lines.next_line().await? {
+                                if line == "." {
+                                    break;
+                                }
+                                mail.data.push_str(&line);
+                                mail.data.push('\n');
+                            }
+                            mails.lock().unwrap().push(std::mem::take(&mut mail));
+                            b"250 OK\r\n"
+                        } else if cmd == "QUIT" {
+                            writer.write_all(b"221 Bye\r\n").await?;
+                            break;
+                        } else {
+                            b"250 OK\r\n"
+                        };
+                        writer.write_all(reply).await?;
+                    }
+                    Ok::<_, std::io::Error>(())
+                });
+            }
+        });
+
+        Ok(Self { addr, mails })
+    }
+
+    fn config(&self) -> SmtpConfig {
+        SmtpConfig {
+            host: self.addr.ip().to_string(),
+            port: self.addr.port(),
+            tls: SmtpTls::None,
+            username: None,
+            password: None,
+            timeout: 60_000,
+        }
+    }
+
+    fn mails(&self) -> Vec<Mail> {
+        self.mails.lock().unwrap().clone()
+    }
+}
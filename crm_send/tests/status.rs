@@ -0,0 +1,157 @@
+use std::net::SocketAddr;
+
+use chrono::Utc;
+use prost_types::Timestamp;
+use tokio::net::TcpListener;
+
+use common::{send, start_server, ConfigBuilder};
+use crm_send::abi::webhook::{DeliveryEvent, DeliveryEventStatus, DeliveryEventsResponse};
+use crm_send::pb::notification_client::NotificationClient;
+use crm_send::pb::{
+    GetStatusRequest, ListMessagesRequest, MessageFilter, MessageState, SendRequest,
+};
+
+mod common;
+
+#[tokio::test]
+async fn status_and_webhook_should_work() -> anyhow::Result<()> {
+    let (addr, webhook_addr) = start_servers().await?;
+    let mut client = NotificationClient::connect(format!("http://{}", addr)).await?;
+
+    let now = Utc::now();
+    let started_at = Timestamp {
+        seconds: now.timestamp(),
+        nanos: now.timestamp_subsec_nanos() as i32,
+    };
+    let reqs = vec![
+        SendRequest::new_email("Hi", "admin@crm.org", &["tyr@acme.org".to_string()], "Hi"),
+        SendRequest::new_sms("10086", &["13800000000".to_string()], "Hi"),
+        SendRequest::new_sms("10086", &["13900000000".to_string()], "Hi"),
+        SendRequest::new_sms("10086", &[], "Hi"),
+    ];
+    let ids = reqs.iter().map(|r| r.id.clone()).collect::<Vec<_>>();
+    // requests are handled in order, so they are created in order
+    send(&mut client, reqs).await?;
+
+    // get status
+    let res = client
+        .get_status(GetStatusRequest {
+            ids: vec![ids[0].clone(), ids[3].clone(), "unknown".to_string()],
+        })
+        .await?
+        .into_inner();
+    assert_eq!(2, res.messages.len());
+    let email = res.messages.iter().find(|m| m.id == ids[0]).unwrap();
+    assert_eq!("email", email.channel);
+    assert_eq!(MessageState::Sent as i32, email.state);
+    assert_eq!(1, email.attempts);
+    let rejected = res.messages.iter().find(|m| m.id == ids[3]).unwrap();
+    assert_eq!(MessageState::Rejected as i32, rejected.state);
+    assert_eq!("No recipients", rejected.last_error);
+
+    // list sent sms page by page, newest first
+    let filter = MessageFilter {
+        channel: "sms".to_string(),
+        states: vec![MessageState::Sent as i32],
+        created_after: Some(started_at),
+        created_before: None,
+    };
+    let page1 = client
+        .list_messages(ListMessagesRequest {
+            filter: Some(filter.clone()),
+            page_size: 1,
+            page_token: String::new(),
+        })
+        .await?
+        .into_inner();
+    assert_eq!(1, page1.messages.len());
+    assert_eq!(ids[2], page1.messages[0].id);
+    assert!(!page1.next_page_token.is_empty());
+    let page2 = client
+        .list_messages(ListMessagesRequest {
+            filter: Some(filter),
+            page_size: 1,
+            page_token: page1.next_page_token,
+        })
+        .await?
+        .into_inner();
+    assert_eq!(1, page2.messages.len());
+    assert_eq!(ids[1], page2.messages[0].id);
+    assert!(page2.next_page_token.is_empty());
+
+    // provider callbacks
+    let url = format!("http://{}/webhooks/delivery", webhook_addr);
+    let events = vec![
+        DeliveryEvent {
+            id: ids[0].clone(),
+            status: DeliveryEventStatus::Delivered,
+            reason: None,
+        },
+        DeliveryEvent {
+            id: ids[1].clone(),
+            status: DeliveryEventStatus::Bounced,
+            reason: Some("No such number".to_string()),
+        },
+        DeliveryEvent {
+            id: "unknown".to_string(),
+            status: DeliveryEventStatus::Delivered,
+            reason: None,
+        },
+    ];
+    let http = reqwest::Client::new();
+    let res = http.post(&url).json(&events).send().await?;
+    assert_eq!(reqwest::StatusCode::UNAUTHORIZED, res.status());
+    let res = http
+        .post(&url)
+        .bearer_auth("tokem")
+        .json(&events)
+        .send()
+        .await?;
+    assert_eq!(reqwest::StatusCode::UNAUTHORIZED, res.status());
+    let too_many = vec![events[2].clone(); 1001];
+    let res = http
+        .post(&url)
+        .bearer_auth("token")
+        .json(&too_many)
+        .send()
+        .await?;
+    assert_eq!(reqwest::StatusCode::PAYLOAD_TOO_LARGE, res.status());
+    let res = http
+        .post(&url)
+        .bearer_auth("token")
+        .json(&events)
+        .send()
+        .await?
+        .error_for_status()?
+        .json::<DeliveryEventsResponse>()
+        .await?;
+    assert_eq!(2, res.updated);
+
+    let res = client
+        .get_status(GetStatusRequest {
+            ids: ids[..2].to_vec(),
+        })
+        .await?
+        .into_inner();
+    let email = res.messages.iter().find(|m| m.id == ids[0]).unwrap();
+    assert_eq!(MessageState::Delivered as i32, email.state);
+    let sms = res.messages.iter().find(|m| m.id == ids[1]).unwrap();
+    assert_eq!(MessageState::Bounced as i32, sms.state);
+    assert_eq!("No such number", sms.last_error);
+    Ok(())
+}
+
+// the grpc server and the webhook server of one service
+async fn start_servers() -> anyhow::Result<(SocketAddr, SocketAddr)> {
+    let listener = TcpListener::bind("127.0.0.1:0").await?;
+    let webhook_addr = listener.local_addr()?;
+    let svc = ConfigBuilder::new()?
+        .webhook(webhook_addr.port(), "token")
+        .service()
+        .await;
+
+    let router = svc.webhook_router();
+    tokio::spawn(async move { axum::serve(listener, router).await });
+    let addr = start_server(svc).await?;
+    Ok((addr, webhook_addr))
+}
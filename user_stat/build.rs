@@ -21,7 +21,6 @@ fn main() -> anyhow::Result<()> {
             true,
             Some(&[r#"#[serde(rename_all = "camelCase")]"#]),
         )
-        .with_sqlx_from_row(&["User"], None)
         .with_derive_builder(
             &[
                 "User",
@@ -41,7 +40,27 @@ fn main() -> anyhow::Result<()> {
             &[r#"#[builder(setter(each(name = "id_builder",into)))]"#],
         )
         .with_field_attributes(
-            &["User.email", "User.name", "RawQueryRequest.query"],
+            &["QueryRequest.fields"],
+            &[r#"#[builder(setter(each(name = "field_builder", into)))]"#],
+        )
+        .with_field_attributes(
+            &[
+                "User.created_at",
+                "User.last_visited_at",
+                "User.last_watched_at",
+                "User.last_email_notification",
+                "User.last_in_app_notification",
+                "User.last_sms_notification",
+            ],
+            &[r#"#[serde(default, with = "crate::abi::serde_ts")]"#],
+        )
+        .with_field_attributes(
+            &[
+                "User.email",
+                "User.name",
+                "RawQueryRequest.query",
+                "QueryRequest.page_token",
+            ],
             &[r#"#[builder(setter(into))]"#],
         )
         .with_field_attributes(
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- preferred language of notifications, e.g. en or zh-CN
+ALTER TABLE user_stats ADD COLUMN locale varchar(16);
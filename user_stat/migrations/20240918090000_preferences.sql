@@ -0,0 +1,11 @@
+-- Add migration script here
+-- explicit notification choices of users, users are subscribed to everything by default.
+-- an empty category covers all categories of the channel, a row of the category overrides it
+CREATE TABLE user_preferences(
+  email varchar(128) NOT NULL REFERENCES user_stats(email) ON DELETE CASCADE,
+  channel varchar(16) NOT NULL CHECK (channel IN ('email', 'in_app', 'sms')),
+  category varchar(64) NOT NULL DEFAULT '',
+  subscribed boolean NOT NULL,
+  updated_at timestamptz NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (email, channel, category)
+);
@@ -0,0 +1,3 @@
+-- Add migration script here
+-- IANA time zone of the user, e.g. Asia/Shanghai
+ALTER TABLE user_stats ADD COLUMN time_zone varchar(64);
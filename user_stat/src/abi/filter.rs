@@ -0,0 +1,229 @@
+use anyhow::{anyhow, bail};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::abi::query::{id_column, null_column, push_time_query, time_column, to_sqlx_ids};
+use crate::pb::filter::Expr;
+use crate::pb::{
+    Filter, FilterGroup, Gender, GenderFilter, IdFilter, IdOp, NotificationChannel, NullFilter,
+    SubscribedFilter, TimeFilter, TimeQuery,
+};
+
+// protect the server from a malicious deep nested filter
+const MAX_FILTER_DEPTH: usize = 32;
+
+impl Filter {
+    pub fn and(filters: Vec<Filter>) -> Self {
+        Self::new(Expr::And(FilterGroup { filters }))
+    }
+
+    pub fn or(filters: Vec<Filter>) -> Self {
+        Self::new(Expr::Or(FilterGroup { filters }))
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn not(filter: Filter) -> Self {
+        Self::new(Expr::Not(Box::new(filter)))
+    }
+
+    pub fn time(column: impl Into<String>, range: TimeQuery) -> Self {
+        Self::new(Expr::Time(TimeFilter {
+            column: column.into(),
+            range: Some(range),
+        }))
+    }
+
+    pub fn ids(column: impl Into<String>, op: IdOp, ids: Vec<u32>) -> Self {
+        Self::new(Expr::Ids(IdFilter {
+            column: column.into(),
+            op: op as i32,
+            ids,
+        }))
+    }
+
+    pub fn gender(gender: Gender) -> Self {
+        Self::new(Expr::Gender(GenderFilter {
+            gender: gender as i32,
+        }))
+    }
+
+    pub fn is_null(column: impl Into<String>) -> Self {
+        Self::new(Expr::Null(NullFilter {
+            column: column.into(),
+            is_null: true,
+        }))
+    }
+
+    pub fn is_not_null(column: impl Into<String>) -> Self {
+        Self::new(Expr::Null(NullFilter {
+            column: column.into(),
+            is_null: false,
+        }))
+    }
+
+    pub fn subscribed(channel: NotificationChannel, category: impl Into<String>) -> Self {
+        Self::new(Expr::Subscribed(SubscribedFilter {
+            channel: channel as i32,
+            category: category.into(),
+        }))
+    }
+
+    fn new(expr: Expr) -> Self {
+        Self { expr: Some(expr) }
+    }
+}
+
+/// compile a filter into parameterized sql, every expression is wrapped with parentheses
+pub(crate) fn push_filter(
+    builder: &mut QueryBuilder<'static, Postgres>,
+    filter: &Filter,
+    depth: usize,
+) -> anyhow::Result<()> {
+    if depth > MAX_FILTER_DEPTH {
+        bail!(
+            "Filter is nested too deep, max depth is {}",
+            MAX_FILTER_DEPTH
+        );
+    }
+
+    let Some(expr) = filter.expr.as_ref() else {
+        builder.push("TRUE");
+        return Ok(());
+    };
+
+    builder.push("(");
+    match expr {
+        Expr::And(group) => push_group(builder, group, " AND ", "TRUE", depth)?,
+        Expr::Or(group) => push_group(builder, group, " OR ", "FALSE", depth)?,
+        Expr::Not(filter) => push_not(builder, filter, depth)?,
+        Expr::Time(v) => {
+            let col = time_column(&v.column)?;
+            match v.range.as_ref() {
+                Some(range) => push_time_query(builder, col, range)?,
+                None => {
+                    builder.push("TRUE");
+                }
+            }
+        }
+        Expr::Ids(v) => push_id_filter(builder, v)?,
+        Expr::Gender(v) => {
+            let gender =
+                Gender::try_from(v.gender).map_err(|_| anyhow!("Unknown gender: {}", v.gender))?;
+            builder
+                .push("gender = ")
+                .push_bind(gender_name(gender))
+                .push("::gender");
+        }
+        Expr::Null(v) => {
+            let col = null_column(&v.column)?;
+            builder.push(col).push(if v.is_null {
+                " IS NULL"
+            } else {
+                " IS NOT NULL"
+            });
+        }
+        Expr::Subscribed(v) => {
+            let channel = NotificationChannel::try_from(v.channel)
+                .map_err(|_| anyhow!("Unknown channel: {}", v.channel))?;
+            // the preference of the category overrides the one of the whole channel
+            builder
+                .push(
+                    "COALESCE((SELECT p.subscribed FROM user_preferences p \
+                     WHERE p.email = user_stats.email AND p.channel = ",
+                )
+                .push_bind(channel.name()?)
+                .push(" AND p.category IN ('', ")
+                .push_bind(v.category.clone())
+                .push(") ORDER BY p.category DESC LIMIT 1), TRUE)");
+        }
+    }
+    builder.push(")");
+
+    Ok(())
+}
+
+fn push_group(
+    builder: &mut QueryBuilder<'static, Postgres>,
+    group: &FilterGroup,
+    sep: &str,
+    empty: &str,
+    depth: usize,
+) -> anyhow::Result<()> {
+    if group.filters.is_empty() {
+        builder.push(empty);
+        return Ok(());
+    }
+    for (i, filter) in group.filters.iter().enumerate() {
+        if i > 0 {
+            builder.push(sep);
+        }
+        push_filter(builder, filter, depth + 1)?;
+    }
+    Ok(())
+}
+
+// NOT of a predicate on a NULL column is NULL too, which drops the row. Such rows
+// are kept, as NotContains does
+fn push_not(
+    builder: &mut QueryBuilder<'static, Postgres>,
+    filter: &Filter,
+    depth: usize,
+) -> anyhow::Result<()> {
+    match filter.expr.as_ref() {
+        // a time filter without a range is TRUE for every row, NULL or not
+        Some(Expr::Time(v))
+            if v.range
+                .as_ref()
+                .is_some_and(|r| r.lower.is_some() || r.upper.is_some()) =>
+        {
+            builder
+                .push(time_column(&v.column)?)
+                .push(" IS NULL OR NOT ");
+        }
+        Some(Expr::Ids(v)) if v.op != IdOp::NotContains as i32 => {
+            builder.push(id_column(&v.column)?).push(" IS NULL OR NOT ");
+        }
+        Some(Expr::Gender(_)) => {
+            builder.push("gender IS NULL OR NOT ");
+        }
+        // a group is NULL if one of its predicates is
+        Some(Expr::And(_) | Expr::Or(_)) => {
+            builder.push("NOT COALESCE(");
+            push_filter(builder, filter, depth + 1)?;
+            builder.push(", FALSE)");
+            return Ok(());
+        }
+        _ => {
+            builder.push("NOT ");
+        }
+    }
+    push_filter(builder, filter, depth + 1)
+}
+
+fn push_id_filter(
+    builder: &mut QueryBuilder<'static, Postgres>,
+    v: &IdFilter,
+) -> anyhow::Result<()> {
+    let col = id_column(&v.column)?;
+    let op = IdOp::try_from(v.op).map_err(|_| anyhow!("Unknown id op: {}", v.op))?;
+    let ids = to_sqlx_ids(&v.ids);
+    match op {
+        IdOp::Contains => builder.push(col).push(" @> ").push_bind(ids),
+        IdOp::Overlaps => builder.push(col).push(" && ").push_bind(ids),
+        // NULL column doesn't contain anything
+        IdOp::NotContains => builder
+            .push("NOT COALESCE(")
+            .push(col)
+            .push(" @> ")
+            .push_bind(ids)
+            .push(", FALSE)"),
+    };
+    Ok(())
+}
+
+fn gender_name(gender: Gender) -> &'static str {
+    match gender {
+        Gender::Unknown => "unknown",
+        Gender::Male => "male",
+        Gender::Female => "female",
+    }
+}
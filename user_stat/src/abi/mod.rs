@@ -1,77 +1,191 @@
 use std::ops::Deref;
 use std::sync::Arc;
 
-use chrono::{DateTime, TimeZone, Utc};
+use futures::{Stream, StreamExt};
 use itertools::Itertools;
-use prost_types::Timestamp;
-use sqlx::PgPool;
-use tonic::Response;
-
+use sqlx::{PgPool, Postgres, QueryBuilder, Transaction};
+use tokio::sync::mpsc;
+use tonic::codegen::tokio_stream::wrappers::ReceiverStream;
+use tonic::{Response, Status};
+use tracing::warn;
+
+use crate::abi::filter::push_filter;
+use crate::abi::page::{is_paginated, order_by, order_column, page_size, push_page, PageToken};
+use crate::abi::query::{
+    id_column, push_id_query, push_time_query, raw_query_sql, select_columns, time_column,
+};
 use crate::pb::user_stats_server::UserStatsServer;
 use crate::pb::{
-    IdQuery, QueryRequest, QueryRequestBuilder, RawQueryRequest, TimeQuery, TimeQueryBuilder, User,
+    CountResponse, ExplainResponse, Filter, QueryPageResponse, QueryRequest, QueryRequestBuilder,
+    RawQueryRequest, TimeQuery, TimeQueryBuilder, User,
 };
 use crate::test_util::to_ts;
 use crate::{AppConfig, ResponseStream, ServiceResult, UserStatsService, UserStatsServiceInner};
 
+mod filter;
+mod notify;
+mod page;
+mod preferences;
+mod query;
+mod user;
+
+pub(crate) use user::serde_ts;
+
+const CHANNEL_SIZE: usize = 1024;
+
 impl UserStatsService {
     pub async fn query(&self, req: QueryRequest) -> ServiceResult<ResponseStream> {
         // generate sql based on request
-        let sql = Self::query_sql(req);
-        let Ok(ret) = sqlx::query_as::<_, User>(&sql).fetch_all(&self.pool).await else {
-            return Err(tonic::Status::internal(format!(
-                "Failed to query with: {}",
-                sql
-            )));
-        };
+        let mut builder =
+            Self::query_sql(req).map_err(|e| Status::invalid_argument(e.to_string()))?;
 
-        let rep_stream = Box::pin(futures::stream::iter(ret.into_iter().map(Ok)));
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        let pool = self.pool.clone();
+        tokio::spawn(async move {
+            let rows = builder.build_query_as::<User>().fetch(&pool);
+            send_rows(rows, &tx).await;
+        });
+
+        let rep_stream = Box::pin(ReceiverStream::new(rx));
         Ok(Response::new(rep_stream))
     }
 
     pub async fn raw_query(&self, req: RawQueryRequest) -> ServiceResult<ResponseStream> {
-        let Ok(ret) = sqlx::query_as::<_, User>(&req.query)
-            .fetch_all(&self.pool)
+        let config = &self.config.raw_query;
+        if !config.enabled {
+            return Err(Status::permission_denied("RawQuery is disabled"));
+        }
+        let sql = raw_query_sql(&req.query, config.max_rows)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        // the query isn't inspected, the read-only transaction and the role of
+        // raw_query.db_url keep it from writing or calling privileged functions
+        let mut db_tx = begin_read_only(&self.raw_query_pool, config.timeout)
             .await
-        else {
-            return Err(tonic::Status::internal(format!(
-                "Failed to query with: {}",
-                req.query
-            )));
-        };
+            .map_err(|e| Status::internal(format!("Failed to start transaction: {}", e)))?;
+
+        let (tx, rx) = mpsc::channel(CHANNEL_SIZE);
+        tokio::spawn(async move {
+            let rows = sqlx::query_as::<_, User>(&sql).fetch(&mut *db_tx);
+            send_rows(rows, &tx).await;
+            if let Err(e) = db_tx.rollback().await {
+                warn!("Failed to rollback raw query transaction: {}", e);
+            }
+        });
+
+        let rep_stream = Box::pin(ReceiverStream::new(rx));
+        Ok(Response::new(rep_stream))
+    }
 
-        let rep_stream = Box::pin(futures::stream::iter(ret.into_iter().map(Ok)));
+    pub async fn count(&self, req: QueryRequest) -> ServiceResult<CountResponse> {
+        let mut builder =
+            Self::count_sql(&req).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let count: i64 = builder
+            .build_query_scalar()
+            .fetch_one(&self.pool)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to count: {}", e)))?;
 
-        Ok(Response::new(rep_stream))
+        Ok(Response::new(CountResponse {
+            count: count as u64,
+        }))
     }
 
-    pub fn query_sql(req: QueryRequest) -> String {
-        let mut sql = "SELECT email, name FROM user_stats WHERE ".to_string();
+    pub async fn explain(&self, req: QueryRequest) -> ServiceResult<ExplainResponse> {
+        let mut builder = Self::select_sql("EXPLAIN SELECT ", &req)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let plan: Vec<String> = builder
+            .build_query_scalar()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to explain: {}", e)))?;
+
+        let sql = builder.sql().trim_start_matches("EXPLAIN ").to_string();
+        Ok(Response::new(ExplainResponse {
+            sql,
+            plan: plan.join("\n"),
+        }))
+    }
 
-        let time_conds = req
-            .timestamps
-            .iter()
-            .map(|(k, v)| format!("{} {} ", k, time_query(v)))
-            .join(" AND ");
+    pub async fn query_page(&self, mut req: QueryRequest) -> ServiceResult<QueryPageResponse> {
+        req.page_size = page_size(&req);
+        let order_by = order_by(&req).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let mut builder =
+            Self::query_sql(req.clone()).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let users = builder
+            .build_query_as::<User>()
+            .fetch_all(&self.pool)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to query: {}", e)))?;
+
+        // a full page means there may be more users
+        let next_page_token = match users.last() {
+            Some(user) if users.len() == req.page_size as usize => {
+                PageToken::from_user(order_by, user)
+                    .map_err(|e| Status::invalid_argument(e.to_string()))?
+                    .encode()
+            }
+            _ => String::new(),
+        };
+        Ok(Response::new(QueryPageResponse {
+            users,
+            next_page_token,
+        }))
+    }
 
-        sql.push_str(&time_conds);
-        if req.ids.is_empty() {
-            return sql;
-        }
+    /// column names are checked against user_stats schema, all values are bound as parameters
+    pub fn query_sql(req: QueryRequest) -> anyhow::Result<QueryBuilder<'static, Postgres>> {
+        Self::select_sql("SELECT ", &req)
+    }
 
-        sql.push_str(" AND ");
-        let id_conds = req.ids.iter().map(|(k, v)| id_query(k, v)).join(" AND ");
+    pub fn count_sql(req: &QueryRequest) -> anyhow::Result<QueryBuilder<'static, Postgres>> {
+        let mut builder = QueryBuilder::new("SELECT COUNT(*) FROM user_stats WHERE ");
+        push_conditions(&mut builder, req)?;
+        Ok(builder)
+    }
 
-        sql.push_str(&id_conds);
+    fn select_sql(
+        select: &str,
+        req: &QueryRequest,
+    ) -> anyhow::Result<QueryBuilder<'static, Postgres>> {
+        let paginated = is_paginated(req);
+        let mut fields = req.fields.clone();
+        if paginated && !fields.is_empty() {
+            // page token is made of the order column of the last user
+            fields.push(order_column(order_by(req)?).to_string());
+        }
 
-        sql
+        let mut builder = QueryBuilder::new(select);
+        builder
+            .push(select_columns(&fields)?)
+            .push(" FROM user_stats WHERE ");
+        push_conditions(&mut builder, req)?;
+        if paginated {
+            push_page(&mut builder, req)?;
+        }
+        Ok(builder)
     }
 
     pub async fn new(config: AppConfig) -> Self {
+        config
+            .raw_query
+            .validate()
+            .expect("Invalid raw_query config");
         let pool = PgPool::connect(&config.db_url)
             .await
             .expect("Failed to connect to db");
-        let inner = UserStatsServiceInner { config, pool };
+        let raw_query_pool = match &config.raw_query.db_url {
+            Some(url) => PgPool::connect(url)
+                .await
+                .expect("Failed to connect to db for raw query"),
+            // RawQuery is disabled
+            None => pool.clone(),
+        };
+        let inner = UserStatsServiceInner {
+            config,
+            pool,
+            raw_query_pool,
+        };
         Self {
             inner: Arc::new(inner),
         }
@@ -82,46 +196,86 @@ impl UserStatsService {
     }
 }
 
-impl Deref for UserStatsService {
-    type Target = UserStatsServiceInner;
-    fn deref(&self) -> &Self::Target {
-        &self.inner
+// conditions of the WHERE clause, shared by query, count and explain
+fn push_conditions(
+    builder: &mut QueryBuilder<'static, Postgres>,
+    req: &QueryRequest,
+) -> anyhow::Result<()> {
+    let mut has_cond = false;
+
+    // sort by column name to generate the same sql for the same request
+    for (k, v) in req.timestamps.iter().sorted_by_key(|(k, _)| *k) {
+        push_and(builder, &mut has_cond);
+        push_time_query(builder, time_column(k)?, v)?;
     }
-}
 
-fn time_query(v: &TimeQuery) -> String {
-    let (lower, upper) = (v.lower, v.upper);
-    if lower.is_none() && upper.is_none() {
-        return "TRUE".to_string();
+    for (k, v) in req.ids.iter().sorted_by_key(|(k, _)| *k) {
+        push_and(builder, &mut has_cond);
+        push_id_query(builder, id_column(k)?, v);
     }
-    if upper.is_none() {
-        let t = to_sqlx_timestamp(lower.unwrap());
-        return format!(" >= '{}' ", t.to_rfc3339());
+
+    if let Some(filter) = req.filter.as_ref() {
+        push_and(builder, &mut has_cond);
+        push_filter(builder, filter, 0)?;
     }
 
-    if lower.is_none() {
-        let t = to_sqlx_timestamp(upper.unwrap());
-        return format!(" <= '{}' ", t.to_rfc3339());
+    if !has_cond {
+        builder.push("TRUE");
     }
 
-    let t_lower = to_sqlx_timestamp(lower.unwrap());
-    let t_upper = to_sqlx_timestamp(upper.unwrap());
-    format!(
-        " BETWEEN '{}' AND '{}' ",
-        t_lower.to_rfc3339(),
-        t_upper.to_rfc3339()
-    )
+    Ok(())
 }
 
-fn to_sqlx_timestamp(t: Timestamp) -> DateTime<Utc> {
-    Utc.timestamp_opt(t.seconds, t.nanos as u32).unwrap()
+fn push_and(builder: &mut QueryBuilder<'static, Postgres>, has_cond: &mut bool) {
+    if *has_cond {
+        builder.push(" AND ");
+    }
+    *has_cond = true;
+}
+
+async fn begin_read_only(
+    pool: &PgPool,
+    timeout: u64,
+) -> Result<Transaction<'static, Postgres>, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query("SET TRANSACTION READ ONLY")
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query(&format!("SET LOCAL statement_timeout = {}", timeout))
+        .execute(&mut *tx)
+        .await?;
+    Ok(tx)
 }
 
-fn id_query(k: &str, v: &IdQuery) -> String {
-    if v.ids.is_empty() {
-        return "TRUE".to_string();
+// rows are pulled from postgres only as fast as the client consumes them,
+// the bounded channel is the backpressure between db and grpc stream
+async fn send_rows<S>(mut rows: S, tx: &mpsc::Sender<Result<User, Status>>)
+where
+    S: Stream<Item = Result<User, sqlx::Error>> + Unpin,
+{
+    while let Some(row) = rows.next().await {
+        let (row, failed) = match row {
+            Ok(user) => (Ok(user), false),
+            Err(e) => (
+                Err(Status::internal(format!("Failed to query: {}", e))),
+                true,
+            ),
+        };
+        if tx.send(row).await.is_err() {
+            // client has gone, stop querying
+            break;
+        }
+        if failed {
+            break;
+        }
+    }
+}
+
+impl Deref for UserStatsService {
+    type Target = UserStatsServiceInner;
+    fn deref(&self) -> &Self::Target {
+        &self.inner
     }
-    format!("array{:?} <@ {}", v.ids, k)
 }
 
 impl QueryRequest {
@@ -129,22 +283,104 @@ impl QueryRequest {
         QueryRequestBuilder::default()
             .timestamp_builder((
                 "created_at".to_string(),
+                TimeQuery::new_with_interval(interval),
+            ))
+            .fields(vec![
+                "email".to_string(),
+                "name".to_string(),
+                "locale".to_string(),
+                "time_zone".to_string(),
+            ])
+            .build()
+            .expect("QueryRequest builder failed")
+    }
+
+    /// users last visited `last_visit_interval` days ago and
+    /// last watched `last_watched_interval` days ago, 0 means no limit
+    pub fn new_with_recall(last_visit_interval: u32, last_watched_interval: u32) -> Self {
+        let mut builder = QueryRequestBuilder::default();
+        builder.fields(vec![
+            "email".to_string(),
+            "name".to_string(),
+            "locale".to_string(),
+            "time_zone".to_string(),
+        ]);
+        if last_visit_interval > 0 {
+            builder.timestamp_builder((
+                "last_visited_at".to_string(),
+                TimeQuery::new_with_interval(last_visit_interval),
+            ));
+        }
+        if last_watched_interval > 0 {
+            builder.timestamp_builder((
+                "last_watched_at".to_string(),
+                TimeQuery::new_with_interval(last_watched_interval),
+            ));
+        }
+        builder.build().expect("QueryRequest builder failed")
+    }
+
+    /// users whose last visit is more than `last_visit_interval` days ago
+    pub fn new_with_remind(last_visit_interval: u32) -> Self {
+        QueryRequestBuilder::default()
+            .timestamp_builder((
+                "last_visited_at".to_string(),
                 TimeQueryBuilder::default()
-                    .lower(to_ts((interval + 1) as _))
-                    .upper(to_ts(interval as _))
+                    .upper(to_ts(last_visit_interval as _))
                     .build()
                     .expect("timestamp builder failed"),
             ))
+            .field_builder("started_but_not_finished")
+            .field_builder("locale")
+            .field_builder("time_zone")
             .build()
             .expect("QueryRequest builder failed")
     }
 }
 
+impl QueryRequest {
+    /// AND the filter with the existing one
+    pub fn and_filter(mut self, filter: Filter) -> Self {
+        self.filter = Some(match self.filter.take() {
+            Some(existing) => Filter::and(vec![existing, filter]),
+            None => filter,
+        });
+        self
+    }
+
+    /// select the fields as well, the default fields are kept
+    pub fn with_fields(mut self, fields: impl IntoIterator<Item = String>) -> Self {
+        for field in fields {
+            if !self.fields.contains(&field) {
+                self.fields.push(field);
+            }
+        }
+        self
+    }
+}
+
+impl TimeQuery {
+    /// the whole day which is `interval` days ago
+    pub fn new_with_interval(interval: u32) -> Self {
+        TimeQueryBuilder::default()
+            .lower(to_ts((interval + 1) as _))
+            .upper(to_ts(interval as _))
+            .build()
+            .expect("timestamp builder failed")
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use anyhow::Result;
-    use futures::StreamExt;
-
+    use chrono::{Days, Utc};
+    use futures::{Stream, StreamExt};
+    use rand::{thread_rng, Rng};
+
+    use crate::abi::begin_read_only;
+    use crate::abi::page::PageToken;
+    use crate::abi::query::raw_query_sql;
+    use crate::pb::{Filter, Gender, IdOp, NotificationChannel, OrderBy};
     use crate::test_util::to_ts;
     use crate::{pb, AppConfig, UserStatsService};
 
@@ -162,29 +398,475 @@ mod tests {
                 "viewed_but_not_started".to_string(),
                 pb::IdQueryBuilder::default().ids(vec![1, 2]).build()?,
             ))
+            .field_builder("started_but_not_finished")
+            .build()?;
+        let builder = UserStatsService::query_sql(req)?;
+        assert_eq!(
+            builder.sql(),
+            "SELECT email, name, started_but_not_finished FROM user_stats \
+            WHERE created_at BETWEEN $1 AND $2 AND $3 <@ viewed_but_not_started"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_sql_without_timestamps() -> Result<()> {
+        let req = pb::QueryRequestBuilder::default()
+            .id_builder((
+                "finished".to_string(),
+                pb::IdQueryBuilder::default().ids(vec![1]).build()?,
+            ))
+            .field_builder("started_but_not_finished")
+            .build()?;
+        let builder = UserStatsService::query_sql(req)?;
+        assert_eq!(
+            builder.sql(),
+            "SELECT email, name, started_but_not_finished FROM user_stats WHERE $1 <@ finished"
+        );
+
+        let builder = UserStatsService::query_sql(pb::QueryRequest::default())?;
+        assert_eq!(
+            builder.sql(),
+            "SELECT email, name, gender, created_at, last_visited_at, last_watched_at, \
+            recent_watched, viewed_but_not_started, started_but_not_finished, finished, \
+            last_email_notification, last_in_app_notification, last_sms_notification, locale, \
+            time_zone FROM user_stats WHERE TRUE"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_sql_with_filter() -> Result<()> {
+        let filter = Filter::and(vec![
+            Filter::or(vec![
+                Filter::time(
+                    "last_visited_at",
+                    pb::TimeQueryBuilder::default().lower(to_ts(10)).build()?,
+                ),
+                Filter::ids("recent_watched", IdOp::Overlaps, vec![1, 2]),
+            ]),
+            Filter::not(Filter::gender(Gender::Male)),
+            Filter::ids("finished", IdOp::NotContains, vec![3]),
+            Filter::is_null("last_email_notification"),
+            Filter::subscribed(NotificationChannel::Email, "marketing"),
+        ]);
+        let req = pb::QueryRequestBuilder::default()
+            .filter(filter)
+            .field_builder("started_but_not_finished")
+            .build()?;
+        let builder = UserStatsService::query_sql(req)?;
+        assert_eq!(
+            builder.sql(),
+            "SELECT email, name, started_but_not_finished FROM user_stats WHERE \
+            (((last_visited_at >= $1) OR (recent_watched && $2)) \
+            AND (gender IS NULL OR NOT (gender = $3::gender)) \
+            AND (NOT COALESCE(finished @> $4, FALSE)) \
+            AND (last_email_notification IS NULL) \
+            AND (COALESCE((SELECT p.subscribed FROM user_preferences p \
+            WHERE p.email = user_stats.email AND p.channel = $5 AND p.category IN ('', $6) \
+            ORDER BY p.category DESC LIMIT 1), TRUE)))"
+        );
+
+        let filter = Filter::not(Filter::or(vec![
+            Filter::not(Filter::time(
+                "last_visited_at",
+                pb::TimeQueryBuilder::default().upper(to_ts(10)).build()?,
+            )),
+            Filter::not(Filter::ids("finished", IdOp::NotContains, vec![3])),
+        ]));
+        let req = pb::QueryRequestBuilder::default()
+            .filter(filter)
+            .field_builder("email")
+            .build()?;
+        let builder = UserStatsService::query_sql(req)?;
+        assert_eq!(
+            builder.sql(),
+            "SELECT email, name FROM user_stats WHERE \
+            (NOT COALESCE(((last_visited_at IS NULL OR NOT (last_visited_at <= $1)) \
+            OR (NOT (NOT COALESCE(finished @> $2, FALSE)))), FALSE))"
+        );
+
+        let req = pb::QueryRequestBuilder::default()
+            .filter(Filter::subscribed(NotificationChannel::Unspecified, ""))
+            .build()?;
+        assert!(UserStatsService::query_sql(req).is_err());
+
+        let req = pb::QueryRequestBuilder::default()
+            .filter(Filter::is_null("email; DROP TABLE user_stats"))
+            .build()?;
+        assert!(UserStatsService::query_sql(req).is_err());
+
+        let mut filter = Filter::is_null("gender");
+        for _ in 0..64 {
+            filter = Filter::not(filter);
+        }
+        let req = pb::QueryRequestBuilder::default().filter(filter).build()?;
+        assert!(UserStatsService::query_sql(req).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_with_filter() -> Result<()> {
+        let config = AppConfig::load().expect("Failed to load config");
+        let svc = UserStatsService::new(config).await;
+
+        let filter = Filter::and(vec![
+            Filter::or(vec![
+                Filter::ids("viewed_but_not_started", IdOp::Overlaps, vec![270437, 1]),
+                Filter::is_null("last_visited_at"),
+            ]),
+            Filter::not(Filter::gender(Gender::Female)),
+        ]);
+        let req = pb::QueryRequestBuilder::default().filter(filter).build()?;
+        let users = svc.query(req).await?.into_inner().collect::<Vec<_>>().await;
+        assert!(!users.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_with_not_filter_keeps_null() -> Result<()> {
+        let config = AppConfig::load().expect("Failed to load config");
+        let svc = UserStatsService::new(config).await;
+
+        // a content id no generated user has
+        let id = 999_999_001;
+        let email = format!("{}@null-visit.test", nanoid::nanoid!());
+        sqlx::query(
+            "INSERT INTO user_stats (email, name, viewed_but_not_started) VALUES ($1, 'Null', $2)",
+        )
+        .bind(&email)
+        .bind(vec![id])
+        .execute(&svc.pool)
+        .await?;
+
+        let filter = Filter::and(vec![
+            Filter::ids("viewed_but_not_started", IdOp::Contains, vec![id as u32]),
+            Filter::not(Filter::time(
+                "last_visited_at",
+                pb::TimeQueryBuilder::default().upper(to_ts(10)).build()?,
+            )),
+        ]);
+        let req = pb::QueryRequestBuilder::default().filter(filter).build()?;
+        let users = svc
+            .query(req)
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>, _>>();
+        sqlx::query("DELETE FROM user_stats WHERE email = $1")
+            .bind(&email)
+            .execute(&svc.pool)
+            .await?;
+
+        let users = users?;
+        assert_eq!(1, users.len());
+        assert_eq!(email, users[0].email);
+        assert!(users[0].last_visited_at.is_none());
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_sql_with_fields() -> Result<()> {
+        let req = pb::QueryRequestBuilder::default()
+            .field_builder("last_visited_at")
+            .field_builder("gender")
+            .field_builder("email")
+            .build()?;
+        let builder = UserStatsService::query_sql(req)?;
+        assert_eq!(
+            builder.sql(),
+            "SELECT email, name, gender, last_visited_at FROM user_stats WHERE TRUE"
+        );
+
+        let req = pb::QueryRequestBuilder::default()
+            .field_builder("name, (SELECT 1) AS one")
+            .build()?;
+        assert!(UserStatsService::query_sql(req).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_with_fields() -> Result<()> {
+        let config = AppConfig::load().expect("Failed to load config");
+        let svc = UserStatsService::new(config).await;
+
+        let filter = Filter::ids("viewed_but_not_started", IdOp::Contains, vec![270437]);
+        let req = pb::QueryRequestBuilder::default()
+            .filter(filter.clone())
+            .build()?;
+        let mut ret = svc.query(req).await?.into_inner();
+        let user = ret.next().await.unwrap()?;
+        assert!(user.created_at.is_some());
+        assert!(user.viewed_but_not_started.contains(&270437));
+
+        let req = pb::QueryRequestBuilder::default()
+            .filter(filter)
+            .field_builder("last_visited_at")
+            .build()?;
+        let mut ret = svc.query(req).await?.into_inner();
+        let user = ret.next().await.unwrap()?;
+        assert!(user.created_at.is_none());
+        assert!(user.last_visited_at.is_some());
+        assert!(user.viewed_but_not_started.is_empty());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_count_and_explain() -> Result<()> {
+        let config = AppConfig::load().expect("Failed to load config");
+        let svc = UserStatsService::new(config).await;
+
+        let filter = Filter::ids("viewed_but_not_started", IdOp::Contains, vec![270437]);
+        let req = pb::QueryRequestBuilder::default()
+            .filter(filter)
+            .field_builder("email")
+            .build()?;
+
+        let count = svc.count(req.clone()).await?.into_inner().count;
+        let users = svc
+            .query(req.clone())
+            .await?
+            .into_inner()
+            .collect::<Vec<_>>()
+            .await;
+        assert_eq!(count, users.len() as u64);
+
+        let ret = svc.explain(req).await?.into_inner();
+        assert_eq!(
+            ret.sql,
+            "SELECT email, name FROM user_stats WHERE (viewed_but_not_started @> $1)"
+        );
+        assert!(ret.plan.contains("user_stats"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_page_token() -> Result<()> {
+        let user = pb::UserBuilder::default()
+            .email("a|b@acme.org")
+            .last_visited_at(to_ts(10))
+            .build()?;
+        // postgres timestamps are in microseconds, so is the token
+        let token = PageToken::from_user(OrderBy::LastVisitedAt, &user)?.encode();
+        assert_eq!(PageToken::decode(&token)?.encode(), token);
+
+        let token = PageToken::from_user(OrderBy::Email, &user)?;
+        assert_eq!(PageToken::decode(&token.encode())?, token);
+
+        assert!(PageToken::decode("not a token").is_err());
+        assert!(PageToken::decode("zz").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_sql_with_page() -> Result<()> {
+        let user = pb::UserBuilder::default()
+            .email("tyr@acme.org")
+            .created_at(to_ts(10))
+            .build()?;
+        let req = pb::QueryRequestBuilder::default()
+            .field_builder("email")
+            .page_size(10u32)
+            .order_by(OrderBy::CreatedAt)
+            .page_token(PageToken::from_user(OrderBy::CreatedAt, &user)?.encode())
+            .build()?;
+        let builder = UserStatsService::query_sql(req)?;
+        assert_eq!(
+            builder.sql(),
+            "SELECT email, name, created_at FROM user_stats WHERE TRUE \
+            AND ((created_at, email) > ($1, $2) OR created_at IS NULL) \
+            ORDER BY created_at ASC NULLS LAST, email ASC LIMIT $3"
+        );
+
+        // token of another order is rejected
+        let req = pb::QueryRequestBuilder::default()
+            .page_token(PageToken::from_user(OrderBy::CreatedAt, &user)?.encode())
+            .build()?;
+        assert!(UserStatsService::query_sql(req).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_page() -> Result<()> {
+        let config = AppConfig::load().expect("Failed to load config");
+        let svc = UserStatsService::new(config).await;
+
+        let filter = Filter::ids("viewed_but_not_started", IdOp::Contains, vec![270437]);
+        let req = pb::QueryRequestBuilder::default()
+            .filter(filter)
+            .field_builder("email")
+            .build()?;
+        let count = svc.count(req.clone()).await?.into_inner().count;
+
+        for order_by in [OrderBy::Email, OrderBy::LastVisitedAt] {
+            let mut req = req.clone();
+            req.page_size = 7;
+            req.order_by = order_by as i32;
+            let mut emails = Vec::new();
+            loop {
+                let page = svc.query_page(req.clone()).await?.into_inner();
+                assert!(page.users.len() <= 7);
+                emails.extend(page.users.into_iter().map(|u| u.email));
+                if page.next_page_token.is_empty() {
+                    break;
+                }
+                req.page_token = page.next_page_token;
+            }
+            let total = emails.len();
+            emails.sort();
+            emails.dedup();
+            assert_eq!(total, emails.len());
+            assert_eq!(count, total as u64);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_sql_with_unknown_column() -> Result<()> {
+        let req = pb::QueryRequestBuilder::default()
+            .timestamp_builder((
+                "created_at; DROP TABLE user_stats; --".to_string(),
+                pb::TimeQueryBuilder::default().lower(to_ts(10)).build()?,
+            ))
+            .build()?;
+        assert!(UserStatsService::query_sql(req).is_err());
+
+        let req = pb::QueryRequestBuilder::default()
+            .timestamp_builder((
+                "created_at".to_string(),
+                pb::TimeQueryBuilder::default().lower(to_ts(10)).build()?,
+            ))
+            .id_builder((
+                "email".to_string(),
+                pb::IdQueryBuilder::default().ids(vec![1]).build()?,
+            ))
+            .build()?;
+        assert!(UserStatsService::query_sql(req).is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_query_sql_with_invalid_timestamp() -> Result<()> {
+        let req = pb::QueryRequestBuilder::default()
+            .timestamp_builder((
+                "created_at".to_string(),
+                pb::TimeQueryBuilder::default()
+                    .lower(prost_types::Timestamp {
+                        seconds: i64::MAX,
+                        nanos: 0,
+                    })
+                    .build()?,
+            ))
             .build()?;
-        let sql = UserStatsService::query_sql(req);
-        println!("sql: {}", sql);
+        assert!(UserStatsService::query_sql(req).is_err());
         Ok(())
     }
 
     #[tokio::test]
     async fn test_raw_query() -> Result<()> {
+        let mut config = AppConfig::load().expect("Failed to load config");
+        config.raw_query.enabled = true;
+        config.raw_query.db_url = Some(config.db_url.clone());
+        let svc = UserStatsService::new(config).await;
+        let id = new_content_id();
+        let emails = insert_users(&svc, id, &[("a", 60), ("b", 90), ("c", 5)]).await?;
+
+        let raw_sql = format!(
+            "SELECT email, name FROM user_stats \
+            WHERE created_at BETWEEN now() - interval '120 days' AND now() - interval '20 days' \
+            AND array[{}] <@ viewed_but_not_started",
+            id
+        );
+        let req = pb::RawQueryRequestBuilder::default()
+            .query(raw_sql)
+            .build()?;
+        let users = collect_emails(svc.raw_query(req).await?.into_inner()).await;
+        delete_users(&svc, &emails).await?;
+
+        assert_eq!(emails[..2].to_vec(), users?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_raw_query_sql() -> Result<()> {
+        let sql = raw_query_sql(" select email, name from user_stats limit 5;", 10)?;
+        assert_eq!(
+            sql,
+            "SELECT * FROM (select email, name from user_stats limit 5\n) AS raw_query LIMIT 10"
+        );
+
+        for query in [
+            "\tSELECT\temail, name FROM user_stats WHERE name = 'a--b'",
+            "SELECT email, name FROM user_stats -- comment",
+            "WITH u AS (SELECT email, name FROM user_stats) SELECT * FROM u",
+            "SELECT(email), name FROM user_stats",
+        ] {
+            assert!(raw_query_sql(query, 10).is_ok(), "{}", query);
+        }
+        assert!(raw_query_sql("DELETE FROM user_stats", 10).is_err());
+        assert!(raw_query_sql("DROP TABLE user_stats", 10).is_err());
+        assert!(raw_query_sql("SELECTED", 10).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_raw_query_disabled() -> Result<()> {
+        let mut config = AppConfig::load().expect("Failed to load config");
+        config.raw_query.enabled = false;
+        let svc = UserStatsService::new(config).await;
+
+        let req = pb::RawQueryRequestBuilder::default()
+            .query("SELECT email, name FROM user_stats LIMIT 1")
+            .build()?;
+        let err = svc.raw_query(req).await.err().unwrap();
+        assert_eq!(err.code(), tonic::Code::PermissionDenied);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_raw_query_is_read_only() -> Result<()> {
         let config = AppConfig::load().expect("Failed to load config");
         let svc = UserStatsService::new(config).await;
 
-        let raw_sql = "SELECT email, name FROM user_stats \
-        WHERE created_at BETWEEN '2024-05-01 00:00:00' AND '2024-08-02 00:00:00' \
-        AND array[270437] <@ viewed_but_not_started limit 5";
+        // if a write ever slips through the check, the transaction still rejects it
+        let mut tx = begin_read_only(&svc.pool, 1000).await?;
+        let ret = sqlx::query("DELETE FROM user_stats WHERE FALSE")
+            .execute(&mut *tx)
+            .await;
+        assert!(ret.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_raw_query_with_another_statement() -> Result<()> {
+        let mut config = AppConfig::load().expect("Failed to load config");
+        config.raw_query.enabled = true;
+        config.raw_query.db_url = Some(config.db_url.clone());
+        let svc = UserStatsService::new(config).await;
 
         let req = pb::RawQueryRequestBuilder::default()
-            .query(raw_sql.to_string())
+            .query("SELECT email, name FROM user_stats LIMIT 1; DELETE FROM user_stats")
             .build()?;
         let mut ret = svc.raw_query(req).await?.into_inner();
-        while let Some(user) = ret.next().await {
-            println!("user: {:?}", user?);
-        }
+        assert!(ret.next().await.unwrap().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_raw_query_timeout() -> Result<()> {
+        let mut config = AppConfig::load().expect("Failed to load config");
+        config.raw_query.enabled = true;
+        config.raw_query.db_url = Some(config.db_url.clone());
+        config.raw_query.timeout = 100;
+        let svc = UserStatsService::new(config).await;
 
+        let req = pb::RawQueryRequestBuilder::default()
+            .query("SELECT email, name FROM user_stats, pg_sleep(1) LIMIT 1")
+            .build()?;
+        let mut ret = svc.raw_query(req).await?.into_inner();
+        assert!(ret.next().await.unwrap().is_err());
         Ok(())
     }
 
@@ -192,6 +874,8 @@ mod tests {
     async fn test_query() -> Result<()> {
         let config = AppConfig::load().expect("Failed to load config");
         let svc = UserStatsService::new(config).await;
+        let id = new_content_id();
+        let emails = insert_users(&svc, id, &[("a", 60), ("b", 90), ("c", 5)]).await?;
 
         let req = pb::QueryRequestBuilder::default()
             .timestamp_builder((
@@ -203,14 +887,81 @@ mod tests {
             ))
             .id_builder((
                 "viewed_but_not_started".to_string(),
-                pb::IdQueryBuilder::default().ids(vec![270437]).build()?,
+                pb::IdQueryBuilder::default().ids(vec![id]).build()?,
             ))
             .build()?;
-        let mut ret = svc.query(req).await?.into_inner();
-        while let Some(user) = ret.next().await {
-            println!("user: {:?}", user?);
+        let users = collect_emails(svc.query(req).await?.into_inner()).await;
+        delete_users(&svc, &emails).await?;
+
+        // the user created 5 days ago is out of range
+        assert_eq!(emails[..2].to_vec(), users?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_query_with_remind() -> Result<()> {
+        let config = AppConfig::load().expect("Failed to load config");
+        let svc = UserStatsService::new(config).await;
+
+        let req = pb::QueryRequest::new_with_remind(7);
+        let users = svc.query(req).await?.into_inner().collect::<Vec<_>>().await;
+        assert!(!users.is_empty());
+        assert!(users
+            .into_iter()
+            .any(|user| !user.unwrap().started_but_not_finished.is_empty()));
+
+        Ok(())
+    }
+
+    // a content id no generated user has, so only the inserted users match it
+    fn new_content_id() -> u32 {
+        thread_rng().gen_range(1_000_000_000..2_000_000_000)
+    }
+
+    // users who viewed the content, created the given days ago
+    async fn insert_users(
+        svc: &UserStatsService,
+        id: u32,
+        users: &[(&str, u64)],
+    ) -> Result<Vec<String>> {
+        let mut emails = Vec::with_capacity(users.len());
+        for (name, days) in users {
+            let email = format!("{}-{}@acme.test", name, nanoid::nanoid!());
+            let created_at = Utc::now().checked_sub_days(Days::new(*days)).unwrap();
+            sqlx::query(
+                "INSERT INTO user_stats (email, name, created_at, viewed_but_not_started) \
+                VALUES ($1, $2, $3, $4)",
+            )
+            .bind(&email)
+            .bind(*name)
+            .bind(created_at)
+            .bind(vec![id as i32])
+            .execute(&svc.pool)
+            .await?;
+            emails.push(email);
         }
+        Ok(emails)
+    }
 
+    async fn delete_users(svc: &UserStatsService, emails: &[String]) -> Result<()> {
+        sqlx::query("DELETE FROM user_stats WHERE email = ANY($1)")
+            .bind(emails)
+            .execute(&svc.pool)
+            .await?;
         Ok(())
     }
+
+    // sorted emails of the users
+    async fn collect_emails(
+        users: impl Stream<Item = Result<pb::User, tonic::Status>>,
+    ) -> Result<Vec<String>> {
+        let mut emails = users
+            .map(|user| Ok(user?.email))
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<Vec<_>>>()?;
+        emails.sort();
+        Ok(emails)
+    }
 }
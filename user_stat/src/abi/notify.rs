@@ -0,0 +1,122 @@
+use anyhow::bail;
+use chrono::Utc;
+use tonic::{Response, Status};
+
+use crate::abi::query::to_sqlx_timestamp;
+use crate::pb::{MarkNotifiedRequest, MarkNotifiedResponse, NotificationChannel};
+use crate::{ServiceResult, UserStatsService};
+
+// protect the db from a huge update in one statement
+const MAX_EMAILS: usize = 1000;
+
+impl UserStatsService {
+    pub async fn mark_notified(
+        &self,
+        req: MarkNotifiedRequest,
+    ) -> ServiceResult<MarkNotifiedResponse> {
+        let sql = mark_notified_sql(&req).map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let notified_at = req
+            .notified_at
+            .map(to_sqlx_timestamp)
+            .transpose()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?
+            .unwrap_or_else(Utc::now);
+        let ret = sqlx::query(&sql)
+            .bind(&req.emails)
+            .bind(notified_at)
+            .execute(&self.pool)
+            .await
+            .map_err(|e| Status::internal(format!("Failed to mark notified: {}", e)))?;
+
+        Ok(Response::new(MarkNotifiedResponse {
+            updated: ret.rows_affected(),
+        }))
+    }
+}
+
+impl NotificationChannel {
+    /// the user_stats column keeping the last notification time of the channel
+    pub fn column(&self) -> anyhow::Result<&'static str> {
+        match self {
+            NotificationChannel::Unspecified => bail!("Channel is unspecified"),
+            NotificationChannel::Email => Ok("last_email_notification"),
+            NotificationChannel::InApp => Ok("last_in_app_notification"),
+            NotificationChannel::Sms => Ok("last_sms_notification"),
+        }
+    }
+
+    /// the channel stored in user_preferences
+    pub fn name(&self) -> anyhow::Result<&'static str> {
+        match self {
+            NotificationChannel::Unspecified => bail!("Channel is unspecified"),
+            NotificationChannel::Email => Ok("email"),
+            NotificationChannel::InApp => Ok("in_app"),
+            NotificationChannel::Sms => Ok("sms"),
+        }
+    }
+
+    pub fn from_name(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "email" => Ok(NotificationChannel::Email),
+            "in_app" => Ok(NotificationChannel::InApp),
+            "sms" => Ok(NotificationChannel::Sms),
+            _ => bail!("Unknown channel: {}", name),
+        }
+    }
+}
+
+fn mark_notified_sql(req: &MarkNotifiedRequest) -> anyhow::Result<String> {
+    if req.emails.len() > MAX_EMAILS {
+        bail!("Too many emails, max is {}", MAX_EMAILS);
+    }
+    let col = NotificationChannel::try_from(req.channel)
+        .map_err(|_| anyhow::anyhow!("Unknown channel: {}", req.channel))?
+        .column()?;
+    // an older notification never overrides a newer one
+    Ok(format!(
+        "UPDATE user_stats SET {col} = GREATEST({col}, $2) WHERE email = ANY($1)",
+        col = col
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mark_notified_sql_should_work() -> anyhow::Result<()> {
+        let req = MarkNotifiedRequest {
+            channel: NotificationChannel::Sms as i32,
+            emails: vec!["tyr@acme.org".to_string()],
+            notified_at: None,
+        };
+        assert_eq!(
+            "UPDATE user_stats SET last_sms_notification = \
+             GREATEST(last_sms_notification, $2) WHERE email = ANY($1)",
+            mark_notified_sql(&req)?
+        );
+
+        let req = MarkNotifiedRequest {
+            channel: NotificationChannel::Unspecified as i32,
+            ..req
+        };
+        assert!(mark_notified_sql(&req).is_err());
+        Ok(())
+    }
+    #[tokio::test]
+    async fn mark_notified_with_invalid_time_should_fail() -> anyhow::Result<()> {
+        let config = crate::AppConfig::load()?;
+        let svc = UserStatsService::new(config).await;
+        let req = MarkNotifiedRequest {
+            channel: NotificationChannel::Sms as i32,
+            emails: vec!["tyr@acme.org".to_string()],
+            notified_at: Some(prost_types::Timestamp {
+                seconds: i64::MAX,
+                nanos: 0,
+            }),
+        };
+        let err = svc.mark_notified(req).await.unwrap_err();
+        assert_eq!(tonic::Code::InvalidArgument, err.code());
+        Ok(())
+    }
+}
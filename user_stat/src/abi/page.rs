@@ -0,0 +1,164 @@
+use anyhow::{anyhow, bail};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::abi::query::to_sqlx_timestamp;
+use crate::pb::{OrderBy, QueryRequest, User};
+
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 10_000;
+
+/// position of the last user in a page, encoded into an opaque page token
+#[derive(Debug, PartialEq)]
+pub(crate) struct PageToken {
+    order_by: OrderBy,
+    // value of the order column, None for email order or a NULL column
+    value: Option<DateTime<Utc>>,
+    email: String,
+}
+
+impl PageToken {
+    pub(crate) fn from_user(order_by: OrderBy, user: &User) -> anyhow::Result<Self> {
+        let value = match order_by {
+            OrderBy::Unspecified | OrderBy::Email => None,
+            OrderBy::CreatedAt => user.created_at,
+            OrderBy::LastVisitedAt => user.last_visited_at,
+            OrderBy::LastWatchedAt => user.last_watched_at,
+        };
+        Ok(Self {
+            order_by,
+            value: value.map(to_sqlx_timestamp).transpose()?,
+            email: user.email.clone(),
+        })
+    }
+
+    pub(crate) fn encode(&self) -> String {
+        let value = self
+            .value
+            .map(|v| v.timestamp_micros().to_string())
+            .unwrap_or_default();
+        let token = format!("{}|{}|{}", self.order_by as i32, value, self.email);
+        URL_SAFE_NO_PAD.encode(token)
+    }
+
+    pub(crate) fn decode(token: &str) -> anyhow::Result<Self> {
+        let invalid = || anyhow!("Invalid page token");
+        let bytes = URL_SAFE_NO_PAD.decode(token).map_err(|_| invalid())?;
+        let token = String::from_utf8(bytes).map_err(|_| invalid())?;
+
+        let mut parts = token.splitn(3, '|');
+        let (Some(order_by), Some(value), Some(email)) = (parts.next(), parts.next(), parts.next())
+        else {
+            bail!(invalid());
+        };
+        let order_by = order_by
+            .parse::<i32>()
+            .ok()
+            .and_then(|v| OrderBy::try_from(v).ok())
+            .ok_or_else(invalid)?;
+        let value = match value {
+            "" => None,
+            v => {
+                let micros = v.parse::<i64>().map_err(|_| invalid())?;
+                Some(DateTime::from_timestamp_micros(micros).ok_or_else(invalid)?)
+            }
+        };
+        Ok(Self {
+            order_by,
+            value,
+            email: email.to_string(),
+        })
+    }
+}
+
+pub(crate) fn is_paginated(req: &QueryRequest) -> bool {
+    req.page_size > 0 || !req.page_token.is_empty() || req.order_by != OrderBy::Unspecified as i32
+}
+
+pub(crate) fn order_by(req: &QueryRequest) -> anyhow::Result<OrderBy> {
+    let order_by =
+        OrderBy::try_from(req.order_by).map_err(|_| anyhow!("Unknown order: {}", req.order_by))?;
+    // keyset pagination needs a total order, email is the primary key
+    if order_by == OrderBy::Unspecified {
+        return Ok(OrderBy::Email);
+    }
+    Ok(order_by)
+}
+
+pub(crate) fn order_column(order_by: OrderBy) -> &'static str {
+    match order_by {
+        OrderBy::Unspecified | OrderBy::Email => "email",
+        OrderBy::CreatedAt => "created_at",
+        OrderBy::LastVisitedAt => "last_visited_at",
+        OrderBy::LastWatchedAt => "last_watched_at",
+    }
+}
+
+/// page size for QueryPage, which always returns a bounded page
+pub(crate) fn page_size(req: &QueryRequest) -> u32 {
+    match req.page_size {
+        0 => DEFAULT_PAGE_SIZE,
+        size => size.min(MAX_PAGE_SIZE),
+    }
+}
+
+/// push `AND <cursor> ORDER BY ... LIMIT ...` after the WHERE conditions
+pub(crate) fn push_page(
+    builder: &mut QueryBuilder<'static, Postgres>,
+    req: &QueryRequest,
+) -> anyhow::Result<()> {
+    let order_by = order_by(req)?;
+    let col = order_column(order_by);
+
+    if !req.page_token.is_empty() {
+        let token = PageToken::decode(&req.page_token)?;
+        if token.order_by != order_by {
+            bail!("Page token doesn't match the order");
+        }
+        builder.push(" AND ");
+        push_cursor(builder, col, token);
+    }
+
+    builder.push(" ORDER BY ");
+    if col != "email" {
+        builder.push(col).push(" ASC NULLS LAST, ");
+    }
+    builder.push("email ASC");
+
+    if req.page_size > 0 {
+        builder.push(" LIMIT ").push_bind(req.page_size as i64);
+    }
+    Ok(())
+}
+
+// rows after (value, email) in `ORDER BY col NULLS LAST, email`
+fn push_cursor(builder: &mut QueryBuilder<'static, Postgres>, col: &'static str, token: PageToken) {
+    if col == "email" {
+        builder.push("email > ").push_bind(token.email);
+        return;
+    }
+    match token.value {
+        Some(value) => {
+            builder
+                .push("((")
+                .push(col)
+                .push(", email) > (")
+                .push_bind(value)
+                .push(", ")
+                .push_bind(token.email)
+                .push(") OR ")
+                .push(col)
+                .push(" IS NULL)");
+        }
+        None => {
+            builder
+                .push("(")
+                .push(col)
+                .push(" IS NULL AND email > ")
+                .push_bind(token.email)
+                .push(")");
+        }
+    }
+}
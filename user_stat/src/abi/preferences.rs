@@ -0,0 +1,149 @@
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Utc};
+use prost_types::Timestamp;
+use tonic::{Response, Status};
+
+use crate::pb::{
+    GetPreferencesRequest, NotificationChannel, Preference, Preferences, UpdatePreferencesRequest,
+};
+use crate::{ServiceResult, UserStatsService};
+
+const MAX_CATEGORY_LEN: usize = 64;
+
+#[derive(Debug, sqlx::FromRow)]
+struct PreferenceRow {
+    channel: String,
+    category: String,
+    subscribed: bool,
+    updated_at: DateTime<Utc>,
+}
+
+impl UserStatsService {
+    pub async fn get_preferences(&self, req: GetPreferencesRequest) -> ServiceResult<Preferences> {
+        let preferences = self.load_preferences(&req.email).await?;
+        Ok(Response::new(preferences))
+    }
+
+    pub async fn update_preferences(
+        &self,
+        req: UpdatePreferencesRequest,
+    ) -> ServiceResult<Preferences> {
+        let preferences = req
+            .preferences
+            .iter()
+            .map(|p| {
+                Ok((
+                    channel_name(p.channel)?,
+                    check_category(&p.category)?,
+                    p.subscribed,
+                ))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let internal =
+            |e: sqlx::Error| Status::internal(format!("Failed to update preferences: {}", e));
+        let mut tx = self.pool.begin().await.map_err(internal)?;
+        if !user_exists(&mut *tx, &req.email).await.map_err(internal)? {
+            return Err(Status::not_found(format!("User {} not found", req.email)));
+        }
+        for (channel, category, subscribed) in preferences {
+            sqlx::query(
+                "INSERT INTO user_preferences (email, channel, category, subscribed) \
+                 VALUES ($1, $2, $3, $4) ON CONFLICT (email, channel, category) \
+                 DO UPDATE SET subscribed = $4, updated_at = now()",
+            )
+            .bind(&req.email)
+            .bind(channel)
+            .bind(category)
+            .bind(subscribed)
+            .execute(&mut *tx)
+            .await
+            .map_err(internal)?;
+        }
+        tx.commit().await.map_err(internal)?;
+
+        let preferences = self.load_preferences(&req.email).await?;
+        Ok(Response::new(preferences))
+    }
+
+    async fn load_preferences(&self, email: &str) -> Result<Preferences, Status> {
+        let internal =
+            |e: sqlx::Error| Status::internal(format!("Failed to get preferences: {}", e));
+        let rows: Vec<PreferenceRow> = sqlx::query_as(
+            "SELECT channel, category, subscribed, updated_at FROM user_preferences \
+             WHERE email = $1 ORDER BY channel, category",
+        )
+        .bind(email)
+        .fetch_all(&self.pool)
+        .await
+        .map_err(internal)?;
+        if rows.is_empty() && !user_exists(&self.pool, email).await.map_err(internal)? {
+            return Err(Status::not_found(format!("User {} not found", email)));
+        }
+
+        let preferences = rows
+            .into_iter()
+            .map(Preference::try_from)
+            .collect::<anyhow::Result<Vec<_>>>()
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Preferences {
+            email: email.to_string(),
+            preferences,
+        })
+    }
+}
+
+impl Preference {
+    pub fn new(
+        channel: NotificationChannel,
+        category: impl Into<String>,
+        subscribed: bool,
+    ) -> Self {
+        Self {
+            channel: channel as i32,
+            category: category.into(),
+            subscribed,
+            updated_at: None,
+        }
+    }
+}
+
+impl TryFrom<PreferenceRow> for Preference {
+    type Error = anyhow::Error;
+
+    fn try_from(row: PreferenceRow) -> Result<Self, Self::Error> {
+        Ok(Self {
+            channel: NotificationChannel::from_name(&row.channel)? as i32,
+            category: row.category,
+            subscribed: row.subscribed,
+            updated_at: Some(Timestamp {
+                seconds: row.updated_at.timestamp(),
+                nanos: row.updated_at.timestamp_subsec_nanos() as i32,
+            }),
+        })
+    }
+}
+
+async fn user_exists<'e, E>(executor: E, email: &str) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::Postgres>,
+{
+    sqlx::query_scalar("SELECT EXISTS(SELECT 1 FROM user_stats WHERE email = $1)")
+        .bind(email)
+        .fetch_one(executor)
+        .await
+}
+
+fn channel_name(channel: i32) -> anyhow::Result<&'static str> {
+    NotificationChannel::try_from(channel)
+        .map_err(|_| anyhow!("Unknown channel: {}", channel))?
+        .name()
+}
+
+fn check_category(category: &str) -> anyhow::Result<&str> {
+    if category.len() > MAX_CATEGORY_LEN {
+        bail!("Category is too long, max length is {}", MAX_CATEGORY_LEN);
+    }
+    Ok(category)
+}
@@ -0,0 +1,163 @@
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, TimeZone, Utc};
+use prost_types::Timestamp;
+use sqlx::{Postgres, QueryBuilder};
+
+use crate::pb::{IdQuery, TimeQuery};
+
+// only these columns of user_stats could be used in a query,
+// column names are pushed into sql as is, so never take them from request directly
+const TIME_COLUMNS: &[&str] = &[
+    "created_at",
+    "last_visited_at",
+    "last_watched_at",
+    "last_email_notification",
+    "last_in_app_notification",
+    "last_sms_notification",
+];
+
+const ID_COLUMNS: &[&str] = &[
+    "recent_watched",
+    "viewed_but_not_started",
+    "started_but_not_finished",
+    "finished",
+];
+
+// all columns of user_stats in schema order, email and name are always selected
+const USER_COLUMNS: &[&str] = &[
+    "email",
+    "name",
+    "gender",
+    "created_at",
+    "last_visited_at",
+    "last_watched_at",
+    "recent_watched",
+    "viewed_but_not_started",
+    "started_but_not_finished",
+    "finished",
+    "last_email_notification",
+    "last_in_app_notification",
+    "last_sms_notification",
+    "locale",
+    "time_zone",
+];
+
+/// columns to select for the field mask, empty mask means all columns
+pub(crate) fn select_columns(fields: &[String]) -> anyhow::Result<String> {
+    if fields.is_empty() {
+        return Ok(USER_COLUMNS.join(", "));
+    }
+    if let Some(field) = fields.iter().find(|f| !USER_COLUMNS.contains(&f.as_str())) {
+        bail!("Unknown field: {}", field);
+    }
+    let columns = USER_COLUMNS
+        .iter()
+        .enumerate()
+        .filter(|(i, col)| *i < 2 || fields.iter().any(|f| f == *col))
+        .map(|(_, col)| *col)
+        .collect::<Vec<_>>();
+    Ok(columns.join(", "))
+}
+
+pub(crate) fn time_column(name: &str) -> anyhow::Result<&'static str> {
+    find_column(TIME_COLUMNS, name).ok_or_else(|| anyhow!("Unknown timestamp column: {}", name))
+}
+
+pub(crate) fn id_column(name: &str) -> anyhow::Result<&'static str> {
+    find_column(ID_COLUMNS, name).ok_or_else(|| anyhow!("Unknown ids column: {}", name))
+}
+
+/// columns could be NULL, gender is included
+pub(crate) fn null_column(name: &str) -> anyhow::Result<&'static str> {
+    if name == "gender" {
+        return Ok("gender");
+    }
+    time_column(name)
+        .or_else(|_| id_column(name))
+        .map_err(|_| anyhow!("Unknown column: {}", name))
+}
+
+fn find_column(columns: &[&'static str], name: &str) -> Option<&'static str> {
+    columns.iter().find(|col| **col == name).copied()
+}
+
+pub(crate) fn push_time_query(
+    builder: &mut QueryBuilder<'static, Postgres>,
+    col: &'static str,
+    v: &TimeQuery,
+) -> anyhow::Result<()> {
+    match (v.lower, v.upper) {
+        (None, None) => {
+            builder.push("TRUE");
+        }
+        (Some(lower), None) => {
+            builder
+                .push(col)
+                .push(" >= ")
+                .push_bind(to_sqlx_timestamp(lower)?);
+        }
+        (None, Some(upper)) => {
+            builder
+                .push(col)
+                .push(" <= ")
+                .push_bind(to_sqlx_timestamp(upper)?);
+        }
+        (Some(lower), Some(upper)) => {
+            builder
+                .push(col)
+                .push(" BETWEEN ")
+                .push_bind(to_sqlx_timestamp(lower)?)
+                .push(" AND ")
+                .push_bind(to_sqlx_timestamp(upper)?);
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn push_id_query(
+    builder: &mut QueryBuilder<'static, Postgres>,
+    col: &'static str,
+    v: &IdQuery,
+) {
+    if v.ids.is_empty() {
+        builder.push("TRUE");
+        return;
+    }
+    builder
+        .push_bind(to_sqlx_ids(&v.ids))
+        .push(" <@ ")
+        .push(col);
+}
+
+pub(crate) fn to_sqlx_timestamp(t: Timestamp) -> anyhow::Result<DateTime<Utc>> {
+    Utc.timestamp_opt(t.seconds, t.nanos as u32)
+        .single()
+        .ok_or_else(|| anyhow!("Invalid timestamp: {}", t))
+}
+
+// postgres has no unsigned int, content ids are stored as int[]
+pub(crate) fn to_sqlx_ids(ids: &[u32]) -> Vec<i32> {
+    ids.iter().map(|id| *id as i32).collect()
+}
+
+/// wrap the raw query in a subquery to cap the rows it returns. It has to be a
+/// SELECT, possibly with a WITH clause. A prepared statement is a single statement,
+/// so nothing could be appended to it
+pub(crate) fn raw_query_sql(query: &str, max_rows: u32) -> anyhow::Result<String> {
+    let query = query.trim().trim_end_matches(';').trim_end();
+    let keyword = query
+        .split(|c: char| c.is_whitespace() || c == '(')
+        .next()
+        .unwrap_or_default();
+    if !["SELECT", "WITH"]
+        .iter()
+        .any(|k| k.eq_ignore_ascii_case(keyword))
+    {
+        bail!("Only SELECT statement is allowed");
+    }
+    // a trailing line comment would swallow the closing parenthesis
+    Ok(format!(
+        "SELECT * FROM ({}\n) AS raw_query LIMIT {}",
+        query, max_rows
+    ))
+}
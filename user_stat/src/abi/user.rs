@@ -0,0 +1,109 @@
+use chrono::{DateTime, Utc};
+use prost_types::Timestamp;
+use sqlx::postgres::PgRow;
+use sqlx::{Decode, FromRow, Postgres, Row, Type};
+
+use crate::pb::{Gender, User};
+
+#[derive(Debug, sqlx::Type)]
+#[sqlx(type_name = "gender", rename_all = "lowercase")]
+enum DbGender {
+    Male,
+    Female,
+    Unknown,
+}
+
+// a query may only select a subset of the columns (field mask or raw query),
+// so every column except email and name is optional
+impl FromRow<'_, PgRow> for User {
+    fn from_row(row: &PgRow) -> Result<Self, sqlx::Error> {
+        let gender: Option<DbGender> = try_get_optional(row, "gender")?.flatten();
+
+        Ok(Self {
+            email: row.try_get("email")?,
+            name: row.try_get("name")?,
+            gender: gender.map(Gender::from).unwrap_or(Gender::Unknown) as i32,
+            created_at: try_get_ts(row, "created_at")?,
+            last_visited_at: try_get_ts(row, "last_visited_at")?,
+            last_watched_at: try_get_ts(row, "last_watched_at")?,
+            recent_watched: try_get_ids(row, "recent_watched")?,
+            viewed_but_not_started: try_get_ids(row, "viewed_but_not_started")?,
+            started_but_not_finished: try_get_ids(row, "started_but_not_finished")?,
+            finished: try_get_ids(row, "finished")?,
+            last_email_notification: try_get_ts(row, "last_email_notification")?,
+            last_in_app_notification: try_get_ts(row, "last_in_app_notification")?,
+            last_sms_notification: try_get_ts(row, "last_sms_notification")?,
+            locale: try_get_optional(row, "locale")?
+                .flatten()
+                .unwrap_or_default(),
+            time_zone: try_get_optional(row, "time_zone")?
+                .flatten()
+                .unwrap_or_default(),
+        })
+    }
+}
+
+impl From<DbGender> for Gender {
+    fn from(gender: DbGender) -> Self {
+        match gender {
+            DbGender::Male => Gender::Male,
+            DbGender::Female => Gender::Female,
+            DbGender::Unknown => Gender::Unknown,
+        }
+    }
+}
+
+fn try_get_optional<'r, T>(row: &'r PgRow, col: &str) -> Result<Option<T>, sqlx::Error>
+where
+    T: Decode<'r, Postgres> + Type<Postgres>,
+{
+    match row.try_get(col) {
+        Ok(v) => Ok(Some(v)),
+        Err(sqlx::Error::ColumnNotFound(_)) => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn try_get_ts(row: &PgRow, col: &str) -> Result<Option<Timestamp>, sqlx::Error> {
+    let ts: Option<DateTime<Utc>> = try_get_optional(row, col)?.flatten();
+    Ok(ts.map(to_ts))
+}
+
+fn try_get_ids(row: &PgRow, col: &str) -> Result<Vec<u32>, sqlx::Error> {
+    let ids: Option<Vec<i32>> = try_get_optional(row, col)?.flatten();
+    Ok(ids
+        .unwrap_or_default()
+        .into_iter()
+        .map(|id| id as u32)
+        .collect())
+}
+
+fn to_ts(t: DateTime<Utc>) -> Timestamp {
+    Timestamp {
+        seconds: t.timestamp(),
+        nanos: t.timestamp_subsec_nanos() as i32,
+    }
+}
+
+/// prost Timestamp doesn't support serde, (de)serialize it as a rfc3339 string
+pub(crate) mod serde_ts {
+    use chrono::{DateTime, TimeZone, Utc};
+    use prost_types::Timestamp;
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    pub fn serialize<S>(ts: &Option<Timestamp>, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        ts.and_then(|ts| Utc.timestamp_opt(ts.seconds, ts.nanos as u32).single())
+            .serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D>(deserializer: D) -> Result<Option<Timestamp>, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let ts: Option<DateTime<Utc>> = Option::deserialize(deserializer)?;
+        Ok(ts.map(super::to_ts))
+    }
+}
@@ -8,6 +8,8 @@ pub struct AppConfig {
     pub server: ServerConfig,
     pub db_url: String,
     pub auth: AuthConfig,
+    #[serde(default)]
+    pub raw_query: RawQueryConfig,
 }
 #[derive(Debug, Serialize, Deserialize)]
 pub struct ServerConfig {
@@ -19,17 +21,62 @@ pub struct AuthConfig {
     pub sk: String,
 }
 
+/// RawQuery runs client provided sql, it's disabled unless enabled here
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RawQueryConfig {
+    pub enabled: bool,
+    // statement timeout in milliseconds
+    pub timeout: u64,
+    // max rows returned for one query
+    pub max_rows: u32,
+    // connection of a role which may only read user_stats, required if enabled
+    #[serde(default)]
+    pub db_url: Option<String>,
+}
+
+impl Default for RawQueryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            timeout: 5000,
+            max_rows: 1000,
+            db_url: None,
+        }
+    }
+}
+
 impl AppConfig {
     pub fn load() -> anyhow::Result<Self> {
-        match (
+        let config: Self = match (
             File::open("user_stat.yml"),
             File::open("/etc/config/user_stat.yml"),
             std::env::var("USER_STAT_CONFIG"),
         ) {
-            (Ok(file), _, _) => Ok(serde_yaml::from_reader(file)?),
-            (_, Ok(file), _) => Ok(serde_yaml::from_reader(file)?),
-            (_, _, Ok(path)) => Ok(serde_yaml::from_str(&path)?),
+            (Ok(file), _, _) => serde_yaml::from_reader(file)?,
+            (_, Ok(file), _) => serde_yaml::from_reader(file)?,
+            (_, _, Ok(path)) => serde_yaml::from_str(&path)?,
             _ => bail!("no config file found"),
+        };
+        config.raw_query.validate()?;
+        Ok(config)
+    }
+}
+
+impl RawQueryConfig {
+    pub fn validate(&self) -> anyhow::Result<()> {
+        if !self.enabled {
+            return Ok(());
+        }
+        // the sql isn't inspected, it must not run as the service role
+        if self.db_url.is_none() {
+            bail!("raw_query.db_url is required when RawQuery is enabled");
+        }
+        if self.timeout == 0 {
+            bail!("raw_query.timeout must be positive");
+        }
+        if self.max_rows == 0 {
+            bail!("raw_query.max_rows must be positive");
         }
+        Ok(())
     }
 }
@@ -4,10 +4,14 @@ use futures::Stream;
 use sqlx::PgPool;
 use tonic::{async_trait, Request, Response, Status};
 
-pub use config::AppConfig;
+pub use config::{AppConfig, RawQueryConfig};
 
 use crate::pb::user_stats_server::UserStats;
-use crate::pb::{QueryRequest, RawQueryRequest, User};
+use crate::pb::{
+    CountResponse, ExplainResponse, GetPreferencesRequest, MarkNotifiedRequest,
+    MarkNotifiedResponse, Preferences, QueryPageResponse, QueryRequest, RawQueryRequest,
+    UpdatePreferencesRequest, User,
+};
 
 pub mod abi;
 mod config;
@@ -22,6 +26,8 @@ pub struct UserStatsService {
 pub struct UserStatsServiceInner {
     config: AppConfig,
     pool: PgPool,
+    // RawQuery runs as a read-only role if it's configured
+    raw_query_pool: PgPool,
 }
 
 type ServiceResult<T> = Result<Response<T>, Status>;
@@ -35,6 +41,10 @@ impl UserStats for UserStatsService {
         self.query(request.into_inner()).await
     }
 
+    async fn query_page(&self, request: Request<QueryRequest>) -> ServiceResult<QueryPageResponse> {
+        self.query_page(request.into_inner()).await
+    }
+
     type RawQueryStream = ResponseStream;
 
     async fn raw_query(
@@ -43,6 +53,35 @@ impl UserStats for UserStatsService {
     ) -> ServiceResult<Self::RawQueryStream> {
         self.raw_query(request.into_inner()).await
     }
+
+    async fn count(&self, request: Request<QueryRequest>) -> ServiceResult<CountResponse> {
+        self.count(request.into_inner()).await
+    }
+
+    async fn explain(&self, request: Request<QueryRequest>) -> ServiceResult<ExplainResponse> {
+        self.explain(request.into_inner()).await
+    }
+
+    async fn mark_notified(
+        &self,
+        request: Request<MarkNotifiedRequest>,
+    ) -> ServiceResult<MarkNotifiedResponse> {
+        self.mark_notified(request.into_inner()).await
+    }
+
+    async fn get_preferences(
+        &self,
+        request: Request<GetPreferencesRequest>,
+    ) -> ServiceResult<Preferences> {
+        self.get_preferences(request.into_inner()).await
+    }
+
+    async fn update_preferences(
+        &self,
+        request: Request<UpdatePreferencesRequest>,
+    ) -> ServiceResult<Preferences> {
+        self.update_preferences(request.into_inner()).await
+    }
 }
 
 #[cfg(feature = "test-util")]
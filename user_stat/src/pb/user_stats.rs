@@ -1,7 +1,7 @@
 // This file is @generated by prost-build.
 #[derive(serde::Serialize, serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
-#[derive(sqlx::FromRow, derive_builder::Builder)]
+#[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
 #[allow(clippy::derive_partial_eq_without_eq)]
 #[derive(Clone, PartialEq, ::prost::Message)]
@@ -12,6 +12,41 @@ pub struct User {
     #[prost(string, tag = "2")]
     #[builder(setter(into))]
     pub name: ::prost::alloc::string::String,
+    /// content ids the user started but not finished yet
+    #[prost(uint32, repeated, tag = "3")]
+    pub started_but_not_finished: ::prost::alloc::vec::Vec<u32>,
+    #[prost(enumeration = "Gender", tag = "4")]
+    pub gender: i32,
+    #[prost(message, optional, tag = "5")]
+    #[serde(default, with = "crate::abi::serde_ts")]
+    pub created_at: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "6")]
+    #[serde(default, with = "crate::abi::serde_ts")]
+    pub last_visited_at: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "7")]
+    #[serde(default, with = "crate::abi::serde_ts")]
+    pub last_watched_at: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(uint32, repeated, tag = "8")]
+    pub recent_watched: ::prost::alloc::vec::Vec<u32>,
+    #[prost(uint32, repeated, tag = "9")]
+    pub viewed_but_not_started: ::prost::alloc::vec::Vec<u32>,
+    #[prost(uint32, repeated, tag = "10")]
+    pub finished: ::prost::alloc::vec::Vec<u32>,
+    #[prost(message, optional, tag = "11")]
+    #[serde(default, with = "crate::abi::serde_ts")]
+    pub last_email_notification: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "12")]
+    #[serde(default, with = "crate::abi::serde_ts")]
+    pub last_in_app_notification: ::core::option::Option<::prost_types::Timestamp>,
+    #[prost(message, optional, tag = "13")]
+    #[serde(default, with = "crate::abi::serde_ts")]
+    pub last_sms_notification: ::core::option::Option<::prost_types::Timestamp>,
+    /// preferred language of notifications, e.g. en or zh-CN, empty if unknown
+    #[prost(string, tag = "14")]
+    pub locale: ::prost::alloc::string::String,
+    /// IANA time zone, e.g. Asia/Shanghai, empty if unknown
+    #[prost(string, tag = "15")]
+    pub time_zone: ::prost::alloc::string::String,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -26,6 +61,110 @@ pub struct QueryRequest {
     #[prost(map = "string, message", tag = "2")]
     #[builder(setter(each(name = "id_builder", into)))]
     pub ids: ::std::collections::HashMap<::prost::alloc::string::String, IdQuery>,
+    /// more complex conditions, AND-ed with timestamps and ids
+    #[prost(message, optional, tag = "3")]
+    pub filter: ::core::option::Option<Filter>,
+    /// user_stats columns to return, email and name are always returned,
+    /// empty means all columns
+    #[prost(string, repeated, tag = "4")]
+    #[builder(setter(each(name = "field_builder", into)))]
+    pub fields: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// max users to return, 0 means no limit
+    #[prost(uint32, tag = "5")]
+    pub page_size: u32,
+    #[prost(enumeration = "OrderBy", tag = "6")]
+    pub order_by: i32,
+    /// next_page_token of the previous page
+    #[prost(string, tag = "7")]
+    #[builder(setter(into))]
+    pub page_token: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryPageResponse {
+    #[prost(message, repeated, tag = "1")]
+    pub users: ::prost::alloc::vec::Vec<User>,
+    /// empty if this is the last page
+    #[prost(string, tag = "2")]
+    pub next_page_token: ::prost::alloc::string::String,
+}
+/// a filter expression, groups could be nested
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Filter {
+    #[prost(oneof = "filter::Expr", tags = "1, 2, 3, 4, 5, 6, 7, 8")]
+    pub expr: ::core::option::Option<filter::Expr>,
+}
+/// Nested message and enum types in `Filter`.
+pub mod filter {
+    #[allow(clippy::derive_partial_eq_without_eq)]
+    #[derive(Clone, PartialEq, ::prost::Oneof)]
+    pub enum Expr {
+        #[prost(message, tag = "1")]
+        And(super::FilterGroup),
+        #[prost(message, tag = "2")]
+        Or(super::FilterGroup),
+        #[prost(message, tag = "3")]
+        Not(::prost::alloc::boxed::Box<super::Filter>),
+        #[prost(message, tag = "4")]
+        Time(super::TimeFilter),
+        #[prost(message, tag = "5")]
+        Ids(super::IdFilter),
+        #[prost(message, tag = "6")]
+        Gender(super::GenderFilter),
+        #[prost(message, tag = "7")]
+        Null(super::NullFilter),
+        #[prost(message, tag = "8")]
+        Subscribed(super::SubscribedFilter),
+    }
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct FilterGroup {
+    #[prost(message, repeated, tag = "1")]
+    pub filters: ::prost::alloc::vec::Vec<Filter>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TimeFilter {
+    #[prost(string, tag = "1")]
+    pub column: ::prost::alloc::string::String,
+    #[prost(message, optional, tag = "2")]
+    pub range: ::core::option::Option<TimeQuery>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct IdFilter {
+    #[prost(string, tag = "1")]
+    pub column: ::prost::alloc::string::String,
+    #[prost(enumeration = "IdOp", tag = "2")]
+    pub op: i32,
+    #[prost(uint32, repeated, tag = "3")]
+    pub ids: ::prost::alloc::vec::Vec<u32>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GenderFilter {
+    #[prost(enumeration = "Gender", tag = "1")]
+    pub gender: i32,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct NullFilter {
+    #[prost(string, tag = "1")]
+    pub column: ::prost::alloc::string::String,
+    /// false means IS NOT NULL
+    #[prost(bool, tag = "2")]
+    pub is_null: bool,
+}
+/// users who haven't opted out of the channel or the category
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SubscribedFilter {
+    #[prost(enumeration = "NotificationChannel", tag = "1")]
+    pub channel: i32,
+    #[prost(string, tag = "2")]
+    pub category: ::prost::alloc::string::String,
 }
 #[derive(derive_builder::Builder)]
 #[builder(setter(into, strip_option), default)]
@@ -56,6 +195,208 @@ pub struct RawQueryRequest {
     #[builder(setter(into))]
     pub query: ::prost::alloc::string::String,
 }
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct CountResponse {
+    #[prost(uint64, tag = "1")]
+    pub count: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MarkNotifiedRequest {
+    #[prost(enumeration = "NotificationChannel", tag = "1")]
+    pub channel: i32,
+    #[prost(string, repeated, tag = "2")]
+    pub emails: ::prost::alloc::vec::Vec<::prost::alloc::string::String>,
+    /// now if not set
+    #[prost(message, optional, tag = "3")]
+    pub notified_at: ::core::option::Option<::prost_types::Timestamp>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MarkNotifiedResponse {
+    /// unknown emails are ignored
+    #[prost(uint64, tag = "1")]
+    pub updated: u64,
+}
+/// an explicit choice of a user, users are subscribed to everything by default
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Preference {
+    #[prost(enumeration = "NotificationChannel", tag = "1")]
+    pub channel: i32,
+    /// campaign category, e.g. marketing, empty means all categories of the channel
+    #[prost(string, tag = "2")]
+    pub category: ::prost::alloc::string::String,
+    #[prost(bool, tag = "3")]
+    pub subscribed: bool,
+    /// ignored in updates
+    #[prost(message, optional, tag = "4")]
+    pub updated_at: ::core::option::Option<::prost_types::Timestamp>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GetPreferencesRequest {
+    #[prost(string, tag = "1")]
+    pub email: ::prost::alloc::string::String,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct UpdatePreferencesRequest {
+    #[prost(string, tag = "1")]
+    pub email: ::prost::alloc::string::String,
+    /// preferences of other channels and categories are kept
+    #[prost(message, repeated, tag = "2")]
+    pub preferences: ::prost::alloc::vec::Vec<Preference>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Preferences {
+    #[prost(string, tag = "1")]
+    pub email: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "2")]
+    pub preferences: ::prost::alloc::vec::Vec<Preference>,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ExplainResponse {
+    /// generated sql, parameters are shown as $1, $2, ...
+    #[prost(string, tag = "1")]
+    pub sql: ::prost::alloc::string::String,
+    /// output of EXPLAIN
+    #[prost(string, tag = "2")]
+    pub plan: ::prost::alloc::string::String,
+}
+/// pages are ordered by (column, email), nulls come last
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum OrderBy {
+    /// no order, pagination falls back to email
+    Unspecified = 0,
+    Email = 1,
+    CreatedAt = 2,
+    LastVisitedAt = 3,
+    LastWatchedAt = 4,
+}
+impl OrderBy {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            OrderBy::Unspecified => "ORDER_BY_UNSPECIFIED",
+            OrderBy::Email => "ORDER_BY_EMAIL",
+            OrderBy::CreatedAt => "ORDER_BY_CREATED_AT",
+            OrderBy::LastVisitedAt => "ORDER_BY_LAST_VISITED_AT",
+            OrderBy::LastWatchedAt => "ORDER_BY_LAST_WATCHED_AT",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ORDER_BY_UNSPECIFIED" => Some(Self::Unspecified),
+            "ORDER_BY_EMAIL" => Some(Self::Email),
+            "ORDER_BY_CREATED_AT" => Some(Self::CreatedAt),
+            "ORDER_BY_LAST_VISITED_AT" => Some(Self::LastVisitedAt),
+            "ORDER_BY_LAST_WATCHED_AT" => Some(Self::LastWatchedAt),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum IdOp {
+    /// column contains all the ids
+    Contains = 0,
+    /// column contains any of the ids
+    Overlaps = 1,
+    /// column doesn't contain all the ids, empty column included
+    NotContains = 2,
+}
+impl IdOp {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            IdOp::Contains => "ID_OP_CONTAINS",
+            IdOp::Overlaps => "ID_OP_OVERLAPS",
+            IdOp::NotContains => "ID_OP_NOT_CONTAINS",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "ID_OP_CONTAINS" => Some(Self::Contains),
+            "ID_OP_OVERLAPS" => Some(Self::Overlaps),
+            "ID_OP_NOT_CONTAINS" => Some(Self::NotContains),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum Gender {
+    Unknown = 0,
+    Male = 1,
+    Female = 2,
+}
+impl Gender {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            Gender::Unknown => "GENDER_UNKNOWN",
+            Gender::Male => "GENDER_MALE",
+            Gender::Female => "GENDER_FEMALE",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "GENDER_UNKNOWN" => Some(Self::Unknown),
+            "GENDER_MALE" => Some(Self::Male),
+            "GENDER_FEMALE" => Some(Self::Female),
+            _ => None,
+        }
+    }
+}
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum NotificationChannel {
+    Unspecified = 0,
+    Email = 1,
+    InApp = 2,
+    Sms = 3,
+}
+impl NotificationChannel {
+    /// String value of the enum field names used in the ProtoBuf definition.
+    ///
+    /// The values are not transformed in any way and thus are considered stable
+    /// (if the ProtoBuf definition does not change) and safe for programmatic use.
+    pub fn as_str_name(&self) -> &'static str {
+        match self {
+            NotificationChannel::Unspecified => "NOTIFICATION_CHANNEL_UNSPECIFIED",
+            NotificationChannel::Email => "NOTIFICATION_CHANNEL_EMAIL",
+            NotificationChannel::InApp => "NOTIFICATION_CHANNEL_IN_APP",
+            NotificationChannel::Sms => "NOTIFICATION_CHANNEL_SMS",
+        }
+    }
+    /// Creates an enum from field names used in the ProtoBuf definition.
+    pub fn from_str_name(value: &str) -> ::core::option::Option<Self> {
+        match value {
+            "NOTIFICATION_CHANNEL_UNSPECIFIED" => Some(Self::Unspecified),
+            "NOTIFICATION_CHANNEL_EMAIL" => Some(Self::Email),
+            "NOTIFICATION_CHANNEL_IN_APP" => Some(Self::InApp),
+            "NOTIFICATION_CHANNEL_SMS" => Some(Self::Sms),
+            _ => None,
+        }
+    }
+}
 /// Generated client implementations.
 pub mod user_stats_client {
     #![allow(unused_variables, dead_code, missing_docs, clippy::let_unit_value)]
@@ -158,6 +499,24 @@ pub mod user_stats_client {
                 .insert(GrpcMethod::new("user_stats.UserStats", "Query"));
             self.inner.server_streaming(req, path, codec).await
         }
+        /// one page of users, use next_page_token to fetch the next one
+        pub async fn query_page(
+            &mut self,
+            request: impl tonic::IntoRequest<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::QueryPageResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/QueryPage");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "QueryPage"));
+            self.inner.unary(req, path, codec).await
+        }
         pub async fn raw_query(
             &mut self,
             request: impl tonic::IntoRequest<super::RawQueryRequest>,
@@ -176,6 +535,98 @@ pub mod user_stats_client {
                 .insert(GrpcMethod::new("user_stats.UserStats", "RawQuery"));
             self.inner.server_streaming(req, path, codec).await
         }
+        /// how many users a query matches
+        pub async fn count(
+            &mut self,
+            request: impl tonic::IntoRequest<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::CountResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/Count");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "Count"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// the sql and the plan postgres uses for a query
+        pub async fn explain(
+            &mut self,
+            request: impl tonic::IntoRequest<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::ExplainResponse>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/Explain");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "Explain"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// set last_<channel>_notification of the users after notifying them
+        pub async fn mark_notified(
+            &mut self,
+            request: impl tonic::IntoRequest<super::MarkNotifiedRequest>,
+        ) -> std::result::Result<tonic::Response<super::MarkNotifiedResponse>, tonic::Status>
+        {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/MarkNotified");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "MarkNotified"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// notification preferences of a user
+        pub async fn get_preferences(
+            &mut self,
+            request: impl tonic::IntoRequest<super::GetPreferencesRequest>,
+        ) -> std::result::Result<tonic::Response<super::Preferences>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path = http::uri::PathAndQuery::from_static("/user_stats.UserStats/GetPreferences");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "GetPreferences"));
+            self.inner.unary(req, path, codec).await
+        }
+        /// opt in or out of channels and categories
+        pub async fn update_preferences(
+            &mut self,
+            request: impl tonic::IntoRequest<super::UpdatePreferencesRequest>,
+        ) -> std::result::Result<tonic::Response<super::Preferences>, tonic::Status> {
+            self.inner.ready().await.map_err(|e| {
+                tonic::Status::new(
+                    tonic::Code::Unknown,
+                    format!("Service was not ready: {}", e.into()),
+                )
+            })?;
+            let codec = tonic::codec::ProstCodec::default();
+            let path =
+                http::uri::PathAndQuery::from_static("/user_stats.UserStats/UpdatePreferences");
+            let mut req = request.into_request();
+            req.extensions_mut()
+                .insert(GrpcMethod::new("user_stats.UserStats", "UpdatePreferences"));
+            self.inner.unary(req, path, codec).await
+        }
     }
 }
 /// Generated server implementations.
@@ -194,6 +645,11 @@ pub mod user_stats_server {
             &self,
             request: tonic::Request<super::QueryRequest>,
         ) -> std::result::Result<tonic::Response<Self::QueryStream>, tonic::Status>;
+        /// one page of users, use next_page_token to fetch the next one
+        async fn query_page(
+            &self,
+            request: tonic::Request<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::QueryPageResponse>, tonic::Status>;
         /// Server streaming response type for the RawQuery method.
         type RawQueryStream: tonic::codegen::tokio_stream::Stream<
                 Item = std::result::Result<super::User, tonic::Status>,
@@ -203,6 +659,31 @@ pub mod user_stats_server {
             &self,
             request: tonic::Request<super::RawQueryRequest>,
         ) -> std::result::Result<tonic::Response<Self::RawQueryStream>, tonic::Status>;
+        /// how many users a query matches
+        async fn count(
+            &self,
+            request: tonic::Request<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::CountResponse>, tonic::Status>;
+        /// the sql and the plan postgres uses for a query
+        async fn explain(
+            &self,
+            request: tonic::Request<super::QueryRequest>,
+        ) -> std::result::Result<tonic::Response<super::ExplainResponse>, tonic::Status>;
+        /// set last_<channel>_notification of the users after notifying them
+        async fn mark_notified(
+            &self,
+            request: tonic::Request<super::MarkNotifiedRequest>,
+        ) -> std::result::Result<tonic::Response<super::MarkNotifiedResponse>, tonic::Status>;
+        /// notification preferences of a user
+        async fn get_preferences(
+            &self,
+            request: tonic::Request<super::GetPreferencesRequest>,
+        ) -> std::result::Result<tonic::Response<super::Preferences>, tonic::Status>;
+        /// opt in or out of channels and categories
+        async fn update_preferences(
+            &self,
+            request: tonic::Request<super::UpdatePreferencesRequest>,
+        ) -> std::result::Result<tonic::Response<super::Preferences>, tonic::Status>;
     }
     #[derive(Debug)]
     pub struct UserStatsServer<T: UserStats> {
@@ -320,6 +801,45 @@ pub mod user_stats_server {
                     };
                     Box::pin(fut)
                 }
+                "/user_stats.UserStats/QueryPage" => {
+                    #[allow(non_camel_case_types)]
+                    struct QueryPageSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::QueryRequest> for QueryPageSvc<T> {
+                        type Response = super::QueryPageResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::QueryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as UserStats>::query_page(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = QueryPageSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 "/user_stats.UserStats/RawQuery" => {
                     #[allow(non_camel_case_types)]
                     struct RawQuerySvc<T: UserStats>(pub Arc<T>);
@@ -363,6 +883,207 @@ pub mod user_stats_server {
                     };
                     Box::pin(fut)
                 }
+                "/user_stats.UserStats/Count" => {
+                    #[allow(non_camel_case_types)]
+                    struct CountSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::QueryRequest> for CountSvc<T> {
+                        type Response = super::CountResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::QueryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move { <T as UserStats>::count(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = CountSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/Explain" => {
+                    #[allow(non_camel_case_types)]
+                    struct ExplainSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::QueryRequest> for ExplainSvc<T> {
+                        type Response = super::ExplainResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::QueryRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut =
+                                async move { <T as UserStats>::explain(&inner, request).await };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = ExplainSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/MarkNotified" => {
+                    #[allow(non_camel_case_types)]
+                    struct MarkNotifiedSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::MarkNotifiedRequest> for MarkNotifiedSvc<T> {
+                        type Response = super::MarkNotifiedResponse;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::MarkNotifiedRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as UserStats>::mark_notified(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = MarkNotifiedSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/GetPreferences" => {
+                    #[allow(non_camel_case_types)]
+                    struct GetPreferencesSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::GetPreferencesRequest>
+                        for GetPreferencesSvc<T>
+                    {
+                        type Response = super::Preferences;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::GetPreferencesRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as UserStats>::get_preferences(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = GetPreferencesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
+                "/user_stats.UserStats/UpdatePreferences" => {
+                    #[allow(non_camel_case_types)]
+                    struct UpdatePreferencesSvc<T: UserStats>(pub Arc<T>);
+                    impl<T: UserStats> tonic::server::UnaryService<super::UpdatePreferencesRequest>
+                        for UpdatePreferencesSvc<T>
+                    {
+                        type Response = super::Preferences;
+                        type Future = BoxFuture<tonic::Response<Self::Response>, tonic::Status>;
+                        fn call(
+                            &mut self,
+                            request: tonic::Request<super::UpdatePreferencesRequest>,
+                        ) -> Self::Future {
+                            let inner = Arc::clone(&self.0);
+                            let fut = async move {
+                                <T as UserStats>::update_preferences(&inner, request).await
+                            };
+                            Box::pin(fut)
+                        }
+                    }
+                    let accept_compression_encodings = self.accept_compression_encodings;
+                    let send_compression_encodings = self.send_compression_encodings;
+                    let max_decoding_message_size = self.max_decoding_message_size;
+                    let max_encoding_message_size = self.max_encoding_message_size;
+                    let inner = self.inner.clone();
+                    let fut = async move {
+                        let inner = inner.0;
+                        let method = UpdatePreferencesSvc(inner);
+                        let codec = tonic::codec::ProstCodec::default();
+                        let mut grpc = tonic::server::Grpc::new(codec)
+                            .apply_compression_config(
+                                accept_compression_encodings,
+                                send_compression_encodings,
+                            )
+                            .apply_max_message_size_config(
+                                max_decoding_message_size,
+                                max_encoding_message_size,
+                            );
+                        let res = grpc.unary(method, req).await;
+                        Ok(res)
+                    };
+                    Box::pin(fut)
+                }
                 _ => Box::pin(async move {
                     Ok(http::Response::builder()
                         .status(200)
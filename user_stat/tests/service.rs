@@ -46,9 +46,169 @@ async fn query_should_work() -> anyhow::Result<()> {
     Ok(())
 }
 
+#[tokio::test]
+async fn count_should_work() -> anyhow::Result<()> {
+    let addr = start_server().await?;
+    let mut client = UserStatsClient::connect(format!("http://{}", addr)).await?;
+
+    let req = pb::QueryRequestBuilder::default()
+        .id_builder((
+            "viewed_but_not_started".to_string(),
+            pb::IdQueryBuilder::default().ids(vec![270437]).build()?,
+        ))
+        .build()?;
+    let res = client.count(req).await?.into_inner();
+    assert!(res.count > 0);
+    Ok(())
+}
+
+#[tokio::test]
+async fn query_with_unknown_column_should_fail() -> anyhow::Result<()> {
+    let addr = start_server().await?;
+    let mut client = UserStatsClient::connect(format!("http://{}", addr)).await?;
+
+    let req = pb::QueryRequestBuilder::default()
+        .timestamp_builder((
+            "1=1; DELETE FROM user_stats; --".to_string(),
+            pb::TimeQueryBuilder::default().lower(to_ts(100)).build()?,
+        ))
+        .build()?;
+    let err = client.query(req).await.unwrap_err();
+    assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    Ok(())
+}
+
+#[tokio::test]
+async fn mark_notified_should_work() -> anyhow::Result<()> {
+    let addr = start_server().await?;
+    let mut client = UserStatsClient::connect(format!("http://{}", addr)).await?;
+
+    let req = pb::QueryRequestBuilder::default().page_size(2u32).build()?;
+    let users = client.query_page(req).await?.into_inner().users;
+    let emails = users.iter().map(|u| u.email.clone()).collect::<Vec<_>>();
+
+    let notified_at = to_ts(0);
+    let req = pb::MarkNotifiedRequest {
+        channel: pb::NotificationChannel::InApp as i32,
+        emails: vec![emails[0].clone(), "unknown@acme.org".to_string()],
+        notified_at: Some(notified_at),
+    };
+    let res = client.mark_notified(req).await?.into_inner();
+    assert_eq!(1, res.updated);
+
+    // users notified within the cooldown are skipped
+    let cooldown = pb::Filter::or(vec![
+        pb::Filter::is_null("last_in_app_notification"),
+        pb::Filter::time(
+            "last_in_app_notification",
+            pb::TimeQueryBuilder::default().upper(to_ts(1)).build()?,
+        ),
+    ]);
+    let req = pb::QueryRequestBuilder::default()
+        .page_size(2u32)
+        .build()?
+        .and_filter(cooldown);
+    let users = client.query_page(req).await?.into_inner().users;
+    assert!(users.iter().all(|u| u.email != emails[0]));
+    assert!(users.iter().any(|u| u.email == emails[1]));
+
+    // an older notification doesn't override a newer one
+    let req = pb::MarkNotifiedRequest {
+        channel: pb::NotificationChannel::InApp as i32,
+        emails: vec![emails[0].clone()],
+        notified_at: Some(to_ts(10)),
+    };
+    client.mark_notified(req).await?;
+    let req = pb::QueryRequestBuilder::default()
+        .timestamp_builder((
+            "last_in_app_notification".to_string(),
+            pb::TimeQueryBuilder::default().lower(to_ts(1)).build()?,
+        ))
+        .build()?;
+    let users = client.query(req).await?.into_inner();
+    let users = users.collect::<Result<Vec<_>, _>>().await?;
+    assert!(users.iter().any(|u| u.email == emails[0]));
+
+    let req = pb::MarkNotifiedRequest::default();
+    let err = client.mark_notified(req).await.unwrap_err();
+    assert_eq!(err.code(), tonic::Code::InvalidArgument);
+    Ok(())
+}
+
+#[tokio::test]
+async fn preferences_should_filter_opted_out_users() -> anyhow::Result<()> {
+    let addr = start_server().await?;
+    let mut client = UserStatsClient::connect(format!("http://{}", addr)).await?;
+
+    let req = pb::QueryRequestBuilder::default().page_size(2u32).build()?;
+    let users = client.query_page(req).await?.into_inner().users;
+    let email = users[0].email.clone();
+    let category = format!("test-{}", nanoid::nanoid!());
+    let other = format!("test-{}", nanoid::nanoid!());
+
+    // opt out of the whole channel except the category
+    let req = pb::UpdatePreferencesRequest {
+        email: email.clone(),
+        preferences: vec![
+            pb::Preference::new(pb::NotificationChannel::Sms, "", false),
+            pb::Preference::new(pb::NotificationChannel::Sms, &category, true),
+        ],
+    };
+    let res = client.update_preferences(req).await?.into_inner();
+    let prefs = client
+        .get_preferences(pb::GetPreferencesRequest {
+            email: email.clone(),
+        })
+        .await?
+        .into_inner();
+    assert_eq!(res, prefs);
+    assert!(prefs
+        .preferences
+        .iter()
+        .any(|p| p.category == category && p.subscribed));
+
+    let query = |category: &str| {
+        pb::QueryRequestBuilder::default()
+            .page_size(2u32)
+            .build()
+            .unwrap()
+            .and_filter(pb::Filter::subscribed(
+                pb::NotificationChannel::Sms,
+                category,
+            ))
+    };
+    let users = client
+        .query_page(query(&category))
+        .await?
+        .into_inner()
+        .users;
+    assert_eq!(email, users[0].email);
+    let users = client.query_page(query(&other)).await?.into_inner().users;
+    assert!(users.iter().all(|u| u.email != email));
+
+    // opt in again
+    let req = pb::UpdatePreferencesRequest {
+        email: email.clone(),
+        preferences: vec![pb::Preference::new(pb::NotificationChannel::Sms, "", true)],
+    };
+    client.update_preferences(req).await?;
+    let users = client.query_page(query(&other)).await?.into_inner().users;
+    assert_eq!(email, users[0].email);
+
+    let req = pb::GetPreferencesRequest {
+        email: "unknown@acme.org".to_string(),
+    };
+    let err = client.get_preferences(req).await.unwrap_err();
+    assert_eq!(err.code(), tonic::Code::NotFound);
+    Ok(())
+}
+
 async fn start_server() -> anyhow::Result<SocketAddr> {
     let port = thread_rng().gen_range(50001..65500);
-    let config = AppConfig::load().expect("Failed to load config");
+    let mut config = AppConfig::load().expect("Failed to load config");
+    // raw query is disabled in the shipped config, the tests run it as the service role
+    config.raw_query.enabled = true;
+    config.raw_query.db_url = Some(config.db_url.clone());
     let addr = format!("[::1]:{}", port).parse()?;
 
     let svc = UserStatsService::new(config).await;